@@ -3,6 +3,7 @@ use primality_jones::*;
 use num_bigint::BigUint;
 use num_traits::{One, Zero};
 use rayon::prelude::*;
+use std::sync::Arc;
 
 fn bench_lucas_lehmer_small(c: &mut Criterion) {
     let mut group = c.benchmark_group("Lucas-Lehmer Small");
@@ -12,7 +13,7 @@ fn bench_lucas_lehmer_small(c: &mut Criterion) {
     let small_primes = [2, 3, 5, 7, 13, 17, 19, 31];
     
     for &p in &small_primes {
-        group.bench_function(&format!("M{}", p), |b| {
+        group.bench_function(format!("M{}", p), |b| {
             b.iter(|| lucas_lehmer_test(black_box(p)))
         });
     }
@@ -28,7 +29,7 @@ fn bench_lucas_lehmer_medium(c: &mut Criterion) {
     let medium_primes = [61, 89, 107, 127];
     
     for &p in &medium_primes {
-        group.bench_function(&format!("M{}", p), |b| {
+        group.bench_function(format!("M{}", p), |b| {
             b.iter(|| lucas_lehmer_test(black_box(p)))
         });
     }
@@ -44,7 +45,7 @@ fn bench_lucas_lehmer_large(c: &mut Criterion) {
     let large_primes = [521, 607, 1279];
     
     for &p in &large_primes {
-        group.bench_function(&format!("M{}", p), |b| {
+        group.bench_function(format!("M{}", p), |b| {
             b.iter(|| lucas_lehmer_test(black_box(p)))
         });
     }
@@ -59,7 +60,7 @@ fn bench_mod_mp_optimization(c: &mut Criterion) {
     // Test the optimized mod_mp function against standard modulo
     let p = 31;
     let mp = (BigUint::one() << p) - BigUint::one();
-    let test_values = vec![
+    let test_values = [
         BigUint::from(1000u32),
         BigUint::from(10000u32),
         BigUint::from(100000u32),
@@ -67,11 +68,11 @@ fn bench_mod_mp_optimization(c: &mut Criterion) {
     ];
     
     for (i, k) in test_values.iter().enumerate() {
-        group.bench_function(&format!("mod_mp_{}", i), |b| {
+        group.bench_function(format!("mod_mp_{}", i), |b| {
             b.iter(|| mod_mp(black_box(k), black_box(p)))
         });
         
-        group.bench_function(&format!("standard_mod_{}", i), |b| {
+        group.bench_function(format!("standard_mod_{}", i), |b| {
             b.iter(|| black_box(k) % black_box(&mp))
         });
     }
@@ -79,6 +80,29 @@ fn bench_mod_mp_optimization(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_mod_mp_worst_case(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Modulo Worst Case");
+    group.sample_size(100);
+    
+    // In the Lucas-Lehmer loop mod_mp reduces s^2 with s < M_p, so the largest
+    // input it ever sees is (M_p - 1)^2, just under M_p^2
+    for p in [127u64, 521, 4423, 11213, 44497] {
+        let mp = (BigUint::one() << p) - BigUint::one();
+        let s = &mp - BigUint::one();
+        let k = &s * &s;
+        
+        group.bench_function(format!("mod_mp_p{}", p), |b| {
+            b.iter(|| mod_mp(black_box(&k), black_box(p)))
+        });
+        
+        group.bench_function(format!("standard_mod_p{}", p), |b| {
+            b.iter(|| black_box(&k) % black_box(&mp))
+        });
+    }
+    
+    group.finish();
+}
+
 fn bench_miller_rabin_vs_lucas_lehmer(c: &mut Criterion) {
     let mut group = c.benchmark_group("Miller-Rabin vs Lucas-Lehmer");
     group.sample_size(50);
@@ -86,14 +110,14 @@ fn bench_miller_rabin_vs_lucas_lehmer(c: &mut Criterion) {
     let test_exponents = [31, 61, 89, 107, 127];
     
     for &p in &test_exponents {
-        group.bench_function(&format!("Miller-Rabin_M{}", p), |b| {
+        group.bench_function(format!("Miller-Rabin_M{}", p), |b| {
             b.iter(|| {
                 let start_time = std::time::Instant::now();
-                miller_rabin_test(black_box(p), 5, start_time, std::time::Duration::from_secs(30))
+                miller_rabin_test_quiet(black_box(p), 5, start_time, std::time::Duration::from_secs(30))
             })
         });
         
-        group.bench_function(&format!("Lucas-Lehmer_M{}", p), |b| {
+        group.bench_function(format!("Lucas-Lehmer_M{}", p), |b| {
             b.iter(|| lucas_lehmer_test(black_box(p)))
         });
     }
@@ -170,7 +194,7 @@ fn bench_memory_usage(c: &mut Criterion) {
     let large_exponents = [521, 607, 1279];
     
     for &p in &large_exponents {
-        group.bench_function(&format!("memory_M{}", p), |b| {
+        group.bench_function(format!("memory_M{}", p), |b| {
             b.iter(|| {
                 // This will allocate large BigUint values
                 let mp = (BigUint::one() << p) - BigUint::one();
@@ -189,8 +213,8 @@ fn bench_correctness_verification(c: &mut Criterion) {
     group.sample_size(100);
     
     // Benchmark the verification of known results
-    let known_primes = [2, 3, 5, 7, 13, 17, 19, 31, 61, 89, 107, 127];
-    let known_composites = [11, 23, 29, 37, 41, 43, 47, 53, 59, 67, 71, 73, 79, 83, 97];
+    let known_primes: Vec<u64> = known_mersenne_primes().iter().copied().take_while(|&p| p <= 127).collect();
+    let known_composites: Vec<u64> = known_composite_exponents().iter().copied().take_while(|&p| p <= 97).collect();
     
     group.bench_function("verify_known_primes", |b| {
         b.iter(|| {
@@ -220,7 +244,7 @@ fn bench_performance_regression_detection(c: &mut Criterion) {
     let critical_exponents = [31, 127, 521];
     
     for &p in &critical_exponents {
-        group.bench_function(&format!("critical_lucas_lehmer_M{}", p), |b| {
+        group.bench_function(format!("critical_lucas_lehmer_M{}", p), |b| {
             b.iter(|| lucas_lehmer_test(black_box(p)))
         });
     }
@@ -270,7 +294,7 @@ fn bench_scalability(c: &mut Criterion) {
     let exponents = [127, 521, 607, 1279];
     
     for &p in &exponents {
-        group.bench_function(&format!("scalability_M{}", p), |b| {
+        group.bench_function(format!("scalability_M{}", p), |b| {
             b.iter(|| lucas_lehmer_test(black_box(p)))
         });
     }
@@ -278,12 +302,323 @@ fn bench_scalability(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_prime_cache(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Prime Cache");
+    group.sample_size(10);
+
+    // A realistic screening batch: every prime exponent below 5000
+    let batch: Vec<u64> = (2..5000).filter(|&p| is_prime(p)).collect();
+
+    group.bench_function("batch_trial_factoring_uncached", |b| {
+        b.iter(|| {
+            for &p in &batch {
+                black_box(check_mersenne_candidate(p, CheckLevel::TrialFactoring));
+            }
+        })
+    });
+
+    let cache = Arc::new(PrimeCache::new());
+    let config = CheckConfig {
+        prime_cache: Some(Arc::clone(&cache)),
+        ..CheckConfig::default()
+    };
+    group.bench_function("batch_trial_factoring_shared_cache", |b| {
+        b.iter(|| {
+            for &p in &batch {
+                black_box(check_mersenne_candidate_with_config(p, CheckLevel::TrialFactoring, &config));
+            }
+        })
+    });
+    println!(
+        "Prime cache: {} entries, hit rate {:.1}%",
+        cache.len(),
+        cache.hit_rate() * 100.0
+    );
+
+    group.finish();
+}
+
+fn bench_montgomery_lucas_lehmer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Montgomery vs mod_mp");
+    group.sample_size(10);
+
+    // Mersenne prime exponents from M607 through M9941
+    let exponents = [607u64, 1279, 2203, 2281, 3217, 4253, 4423, 9689, 9941];
+
+    for &p in &exponents {
+        group.bench_function(format!("mod_mp_M{}", p), |b| {
+            b.iter(|| lucas_lehmer_test(black_box(p)))
+        });
+
+        group.bench_function(format!("montgomery_M{}", p), |b| {
+            b.iter(|| lucas_lehmer_test_montgomery(black_box(p)))
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_mod_mp_in_place(c: &mut Criterion) {
+    let mut group = c.benchmark_group("In-place mod_mp");
+    group.sample_size(50);
+
+    // A full M1279 Lucas-Lehmer run through each squaring path
+    let p = 1279u64;
+
+    group.bench_function("allocating_M1279", |b| {
+        b.iter(|| {
+            let mut s = BigUint::from(4u32);
+            for _ in 0..p - 2 {
+                s = square_and_subtract_two_mod_mp(&s, black_box(p));
+            }
+            s
+        })
+    });
+
+    group.bench_function("scratch_M1279", |b| {
+        b.iter(|| {
+            let mut scratch = ModMpScratch::new(black_box(p));
+            let mut s = BigUint::from(4u32);
+            for _ in 0..p - 2 {
+                scratch.square_and_subtract_two(&mut s);
+            }
+            s
+        })
+    });
+
+    group.finish();
+}
+
+fn bench_cached_modulus(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Cached M_p");
+    group.sample_size(20);
+
+    // A full M2203 Lucas-Lehmer run, building M_p for every reduction, once
+    // up front, and once inside ModMpScratch
+    let p = 2203u64;
+    let two = BigUint::from(2u32);
+
+    group.bench_function("rebuilt_M2203", |b| {
+        b.iter(|| {
+            let mut s = BigUint::from(4u32);
+            for _ in 0..p - 2 {
+                let mp = (BigUint::one() << black_box(p)) - BigUint::one();
+                s = mod_mp_with_modulus(&(&s * &s + &mp - &two), &mp, p);
+            }
+            s
+        })
+    });
+
+    group.bench_function("cached_M2203", |b| {
+        b.iter(|| {
+            let mp = (BigUint::one() << black_box(p)) - BigUint::one();
+            let mut s = BigUint::from(4u32);
+            for _ in 0..p - 2 {
+                s = mod_mp_with_modulus(&(&s * &s + &mp - &two), &mp, p);
+            }
+            s
+        })
+    });
+
+    group.bench_function("scratch_M2203", |b| {
+        b.iter(|| {
+            let mut scratch = ModMpScratch::new(black_box(p));
+            let mut s = BigUint::from(4u32);
+            for _ in 0..p - 2 {
+                scratch.square_and_subtract_two(&mut s);
+            }
+            s
+        })
+    });
+
+    group.finish();
+}
+
+fn bench_trial_factoring(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Trial Factoring");
+    group.sample_size(10);
+
+    // Neither M127 (prime) nor M1277 has a factor below 10^8, so every
+    // candidate up to the limit is examined
+    let limit = 100_000_000u64;
+    for p in [127u64, 1277] {
+        group.bench_function(format!("is_prime_modpow_M{}_1e8", p), |b| {
+            b.iter(|| check_small_factors(black_box(p), black_box(limit)))
+        });
+
+        group.bench_function(format!("sieved_M{}_1e8", p), |b| {
+            b.iter(|| check_small_factors_sieved(black_box(p), black_box(limit)))
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_p_minus_1_stage2(c: &mut Criterion) {
+    let mut group = c.benchmark_group("P-1 Stage 2");
+    group.sample_size(10);
+
+    // Every composite M_p with prime p in 100..400
+    let batch: Vec<u64> = (100..400)
+        .filter(|&p| is_prime(p) && !known_mersenne_primes().contains(&p))
+        .collect();
+    let (b1, b2) = (1000, 10_000);
+
+    let found = |b2: u64| batch.iter().filter(|&&p| pollard_p_minus_1_with_stage2(p, b1, b2).is_some()).count();
+    println!(
+        "P-1 on {} composite Mersennes, B1 = {}: stage 1 found {} factors, stage 2 to B2 = {} found {}",
+        batch.len(),
+        b1,
+        found(0),
+        b2,
+        found(b2)
+    );
+
+    group.bench_function("stage1_B1_1000", |b| {
+        b.iter(|| batch.iter().filter(|&&p| pollard_p_minus_1(black_box(p), b1).is_some()).count())
+    });
+
+    group.bench_function("stage2_B2_10000", |b| {
+        b.iter(|| {
+            batch
+                .iter()
+                .filter(|&&p| pollard_p_minus_1_with_stage2(black_box(p), b1, b2).is_some())
+                .count()
+        })
+    });
+
+    group.finish();
+}
+
+fn bench_u128_lucas_lehmer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("u128 Lucas-Lehmer");
+    group.sample_size(100);
+
+    // The BigUint loop lucas_lehmer_test used for M31 before the native path
+    group.bench_function("biguint_M31", |b| {
+        b.iter(|| lucas_lehmer_test_checked(black_box(31)).unwrap().is_prime)
+    });
+
+    group.bench_function("u128_M31", |b| {
+        b.iter(|| lucas_lehmer_test_u128(black_box(31)))
+    });
+
+    group.finish();
+}
+
+fn bench_square_into(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Squaring");
+    group.sample_size(50);
+
+    // Full Lucas-Lehmer runs squaring with `s * s` inside ModMpScratch and with
+    // square_into into a buffer kept across iterations. M607 and M1279 are
+    // prime, so s never drops below 2 and the subtraction cannot underflow
+    for p in [607u64, 1279] {
+        group.bench_function(format!("mul_M{}", p), |b| {
+            b.iter(|| {
+                let mut scratch = ModMpScratch::new(black_box(p));
+                let mut s = BigUint::from(4u32);
+                for _ in 0..p - 2 {
+                    scratch.square_and_subtract_two(&mut s);
+                }
+                s
+            })
+        });
+
+        group.bench_function(format!("square_into_M{}", p), |b| {
+            b.iter(|| {
+                let mut scratch = ModMpScratch::new(black_box(p));
+                let mut s = BigUint::from(4u32);
+                let mut squared = BigUint::zero();
+                for _ in 0..p - 2 {
+                    square_into(&s, &mut squared);
+                    squared -= 2u32;
+                    scratch.reduce(&mut squared);
+                    std::mem::swap(&mut s, &mut squared);
+                }
+                s
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_mod_mp_single_pass(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Single-pass mod_mp");
+    group.sample_size(100);
+
+    // One Lucas-Lehmer squaring step, (s^2 - 2) mod M_p, on a full-size
+    // residue, and the reduction of that square on its own
+    let two = BigUint::from(2u32);
+    for p in [1279u64, 4423] {
+        let mp = (BigUint::one() << p) - BigUint::one();
+        let s = BigUint::from(3u32).modpow(&BigUint::from(p), &mp);
+        let square = &s * &s - &two;
+
+        group.bench_function(format!("step_mod_mp_M{}", p), |b| {
+            b.iter(|| mod_mp(&(black_box(&s) * &s - &two), p))
+        });
+        group.bench_function(format!("step_with_modulus_M{}", p), |b| {
+            b.iter(|| mod_mp_with_modulus(&(black_box(&s) * &s - &two), &mp, p))
+        });
+        group.bench_function(format!("step_single_pass_M{}", p), |b| {
+            b.iter(|| mod_mp_single_pass(&(black_box(&s) * &s - &two), &mp, p))
+        });
+
+        group.bench_function(format!("reduce_with_modulus_M{}", p), |b| {
+            b.iter(|| mod_mp_with_modulus(black_box(&square), &mp, p))
+        });
+        group.bench_function(format!("reduce_single_pass_M{}", p), |b| {
+            b.iter(|| mod_mp_single_pass(black_box(&square), &mp, p))
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_is_prime_deterministic(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Exponent Primality");
+    group.sample_size(50);
+
+    // Exponents near 10^9, a mix of primes and composites
+    let exponents: Vec<u64> = (1_000_000_000..1_000_001_000).collect();
+
+    group.bench_function("is_prime_near_1e9", |b| {
+        b.iter(|| exponents.iter().filter(|&&n| is_prime(black_box(n))).count())
+    });
+
+    group.bench_function("is_prime_deterministic_near_1e9", |b| {
+        b.iter(|| exponents.iter().filter(|&&n| is_prime_deterministic(black_box(n))).count())
+    });
+
+    group.finish();
+}
+
+fn bench_prime_sieve(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Prime Sieve");
+    group.sample_size(20);
+
+    let limit = 1_000_000u64;
+
+    group.bench_function("prime_sieve_1e6", |b| {
+        b.iter(|| prime_sieve(black_box(limit)).count())
+    });
+
+    group.bench_function("is_prime_loop_1e6", |b| {
+        b.iter(|| (0..=black_box(limit)).filter(|&n| is_prime(n)).count())
+    });
+
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_lucas_lehmer_small,
     bench_lucas_lehmer_medium,
     bench_lucas_lehmer_large,
     bench_mod_mp_optimization,
+    bench_mod_mp_worst_case,
     bench_miller_rabin_vs_lucas_lehmer,
     bench_check_mersenne_candidate_levels,
     bench_property_verification,
@@ -292,5 +627,16 @@ criterion_group!(
     bench_performance_regression_detection,
     bench_parallel_performance,
     bench_scalability,
+    bench_prime_cache,
+    bench_is_prime_deterministic,
+    bench_montgomery_lucas_lehmer,
+    bench_mod_mp_in_place,
+    bench_cached_modulus,
+    bench_trial_factoring,
+    bench_p_minus_1_stage2,
+    bench_u128_lucas_lehmer,
+    bench_square_into,
+    bench_mod_mp_single_pass,
+    bench_prime_sieve,
 );
 criterion_main!(benches); 
\ No newline at end of file
@@ -0,0 +1,31 @@
+/*
+ * Calling primality_jones from C.
+ *
+ *   cargo rustc --release --lib --features ffi --crate-type cdylib
+ *   cc examples/c/check.c -Iinclude -Ltarget/release -lprimality_jones -o check
+ *   LD_LIBRARY_PATH=target/release ./check 127
+ */
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "primality_jones.h"
+
+int main(int argc, char **argv) {
+    uint64_t p = argc > 1 ? strtoull(argv[1], NULL, 10) : 127;
+
+    printf("%llu is %s\n", (unsigned long long)p, pj_is_prime(p) ? "prime" : "not prime");
+
+    uint64_t factor = pj_check_small_factors(p, 1000000);
+    if (factor != 0) {
+        printf("M%llu has the factor %llu\n", (unsigned long long)p, (unsigned long long)factor);
+    }
+
+    PjCheckResult results[8];
+    size_t n = pj_check(p, PJ_LEVEL_LUCAS_LEHMER, results, 8);
+    for (size_t i = 0; i < n && i < 8; i++) {
+        printf("stage %u: %s%s (%llu ns)\n", results[i].kind, results[i].passed ? "passed" : "failed",
+               results[i].inconclusive ? ", inconclusive" : "", (unsigned long long)results[i].time_ns);
+    }
+
+    return pj_lucas_lehmer_test(p) ? 0 : 1;
+}
@@ -34,6 +34,8 @@ def check_mersenne_number(p, level=None):
         passed = "✓" if result["passed"] else "✗"
         time_str = format_time(result["time_taken_ns"])
         print(f"{passed} Check {i}: {result['message']} (took {time_str})")
+        if result["factor"] is not None:
+            print(f"  factor of M{p}: {result['factor']}")
         all_passed &= result["passed"]
     
     if all_passed:
@@ -67,5 +69,14 @@ def main():
         print(f"Found small factor: {factor}")
     print(f"Lucas-Lehmer test: {'passed' if pj.lucas_lehmer(p) else 'failed'}")
 
+    # Example 5: The fast Mersenne reduction on Python ints
+    print("\nExample 5: Arithmetic modulo M127")
+    k = 3**200
+    assert pj.mod_mp(k, 127) == k % (2**127 - 1)
+    s = 4
+    for _ in range(127 - 2):
+        s = pj.square_and_subtract_two_mod_mp(s, 127)
+    print(f"Lucas-Lehmer residue of M127 by hand: {s}")
+
 if __name__ == "__main__":
     main() 
\ No newline at end of file
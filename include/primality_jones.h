@@ -0,0 +1,85 @@
+#ifndef PRIMALITY_JONES_H
+#define PRIMALITY_JONES_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit by hand. */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+// Stage kind of a `PjCheckResult`: the exponent pre-screen
+#define PJ_KIND_EXPONENT_PRIME 0
+
+// Stage kind of a `PjCheckResult`: trial factoring
+#define PJ_KIND_TRIAL_FACTOR 1
+
+// Stage kind of a `PjCheckResult`: Miller-Rabin
+#define PJ_KIND_MILLER_RABIN 2
+
+// Stage kind of a `PjCheckResult`: Lucas-Lehmer
+#define PJ_KIND_LUCAS_LEHMER 3
+
+// Stage kind of a `PjCheckResult`: stage-1 P-1 factoring
+#define PJ_KIND_P_MINUS_ONE 4
+
+// Check level for `pj_check`: pre-screen only
+#define PJ_LEVEL_PRE_SCREEN 0
+
+// Check level for `pj_check`: up to trial factoring
+#define PJ_LEVEL_TRIAL_FACTORING 1
+
+// Check level for `pj_check`: up to P-1 factoring
+#define PJ_LEVEL_P_MINUS_ONE 2
+
+// Check level for `pj_check`: up to Miller-Rabin
+#define PJ_LEVEL_PROBABILISTIC 3
+
+// Check level for `pj_check`: every stage, ending with Lucas-Lehmer
+#define PJ_LEVEL_LUCAS_LEHMER 4
+
+// Check level for `pj_check`: every stage at greater depth, as `CheckLevel::Full`
+#define PJ_LEVEL_FULL 5
+
+// One stage of a `pj_check` run, the C view of `CheckResult`
+//
+// The message is left out so that no strings need freeing.
+typedef struct PjCheckResult {
+  // Which stage this is, one of the `PJ_KIND_*` constants
+  uint32_t kind;
+  // Whether the stage passed
+  bool passed;
+  // The stage could not reach a verdict (for example it timed out)
+  bool inconclusive;
+  // Whether the stage found a factor of M_p
+  bool has_factor;
+  // The factor, or 0 when there is none or it does not fit in 64 bits
+  uint64_t factor;
+  // How long the stage took, in nanoseconds
+  uint64_t time_ns;
+} PjCheckResult;
+
+// Whether M_p is prime, by the Lucas-Lehmer test (false for p < 2)
+bool pj_lucas_lehmer_test(uint64_t p);
+
+// Whether `n` is prime
+bool pj_is_prime(uint64_t n);
+
+// A factor of M_p no greater than `limit`, or 0 if trial factoring finds none
+// (always 0 for `limit` < 3)
+uint64_t pj_check_small_factors(uint64_t p, uint64_t limit);
+
+// Run `check_mersenne_candidate` on M_p and copy the stages into `out`
+//
+// `level` is one of the `PJ_LEVEL_*` constants. At most `capacity` results
+// are written to `out`, which may be null when `capacity` is 0. Returns the
+// number of stages the check produced, which can exceed `capacity`; a buffer
+// of 8 results is always enough. Returns 0, writing nothing, if `level` is
+// not a valid level.
+//
+// # Safety
+//
+// Unless `capacity` is 0, `out` must point to `capacity` writable
+// `PjCheckResult`s.
+size_t pj_check(uint64_t p, uint32_t level, struct PjCheckResult *out, size_t capacity);
+
+#endif  /* PRIMALITY_JONES_H */
@@ -0,0 +1,331 @@
+/*!
+Checking many candidates: splitting and sampling ranges of exponents,
+running them in parallel, searching upward, and summarizing the results.
+*/
+
+use crate::{
+    check_mersenne_candidate, check_mersenne_candidate_with_config, is_prime, next_prime, working_bytes, CheckConfig,
+    CheckKind, CheckLevel, CheckResult, Exponent,
+};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::time::Duration;
+
+/// Summarize which stages passed as a single integer
+///
+/// Bit i is set when the stage of the i-th `CheckKind` ran and passed:
+///
+/// | Bit | Value | Stage                       |
+/// |-----|-------|-----------------------------|
+/// | 0   | 1     | `CheckKind::ExponentPrime`  |
+/// | 1   | 2     | `CheckKind::TrialFactor`    |
+/// | 2   | 4     | `CheckKind::MillerRabin`    |
+/// | 3   | 8     | `CheckKind::LucasLehmer`    |
+/// | 4   | 16    | `CheckKind::PMinusOne`      |
+///
+/// A clear bit means the stage failed or was never reached, so a candidate
+/// that passed every stage of a `CheckLevel::LucasLehmer` run has mask 31.
+///
+/// # Example
+///
+/// ```
+/// use primality_jones::{check_mersenne_candidate, stage_bitmask, CheckLevel};
+///
+/// assert_eq!(stage_bitmask(&check_mersenne_candidate(31, CheckLevel::LucasLehmer)), 0b11111);
+/// assert_eq!(stage_bitmask(&check_mersenne_candidate(29, CheckLevel::LucasLehmer)), 0b0001);
+/// ```
+pub fn stage_bitmask(results: &[CheckResult]) -> u32 {
+    results
+        .iter()
+        .filter(|r| r.passed)
+        .fold(0, |mask, r| mask | 1 << r.kind as u32)
+}
+
+/// Where the time of a `check_mersenne_candidate` run went, from `summarize`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CheckSummary {
+    /// Time of all stages together
+    pub total_time: Duration,
+    /// Each stage that ran and how long it took, in the order they ran
+    pub stage_times: Vec<(CheckKind, Duration)>,
+    /// The stage that ruled M_p out, if one did; an inconclusive stage
+    /// does not count
+    pub eliminated_by: Option<CheckKind>,
+    /// Estimated peak memory for working values, in bytes, as in
+    /// `ResourceEstimate::bytes_required`; 0 if only the pre-screen and trial
+    /// factoring ran, which work in `u64`s
+    pub peak_memory_bytes: u64,
+}
+
+/// Aggregate the results of a check of M_p into a `CheckSummary`
+///
+/// Takes the results of `check_mersenne_candidate` (or `MersenneCheck`) for
+/// the same `p`, which is needed for the memory estimate.
+///
+/// # Example
+///
+/// ```
+/// use primality_jones::{check_mersenne_candidate, summarize, CheckKind, CheckLevel};
+///
+/// let summary = summarize(29, &check_mersenne_candidate(29, CheckLevel::LucasLehmer));
+/// assert_eq!(summary.eliminated_by, Some(CheckKind::TrialFactor)); // 233 divides M29
+/// assert_eq!(summary.stage_times.len(), 2);
+/// assert_eq!(summary.peak_memory_bytes, 0);
+/// ```
+pub fn summarize(p: impl Into<Exponent>, results: &[CheckResult]) -> CheckSummary {
+    let p = p.into().get();
+    let stage_times: Vec<(CheckKind, Duration)> = results.iter().map(|r| (r.kind, r.time_taken)).collect();
+    let works_modulo_mp = |kind: CheckKind| {
+        matches!(kind, CheckKind::PMinusOne | CheckKind::MillerRabin | CheckKind::LucasLehmer)
+    };
+    CheckSummary {
+        total_time: stage_times.iter().map(|&(_, time)| time).sum(),
+        eliminated_by: results.iter().find(|r| !r.passed && !r.inconclusive).map(|r| r.kind),
+        peak_memory_bytes: if results.iter().any(|r| works_modulo_mp(r.kind)) { working_bytes(p) } else { 0 },
+        stage_times,
+    }
+}
+
+/// Growth rate of Lucas-Lehmer cost with the exponent, used by `partition_work`
+///
+/// A test runs p - 2 squarings of p-bit numbers, and num-bigint squares with
+/// Karatsuba/Toom-3, at roughly p^1.585 each, giving about p^2.585 overall.
+pub(crate) const LL_COST_EXPONENT: f64 = 2.585;
+
+/// Split `start..end` into up to `units` ranges of roughly equal Lucas-Lehmer cost
+///
+/// Larger exponents cost far more to test, so the ranges near `end` are much
+/// narrower than those near `start`. The returned ranges are half-open,
+/// contiguous and cover `start..end` exactly. Fewer than `units` ranges may be
+/// returned when the range is too narrow to split that many ways.
+///
+/// # Example
+///
+/// ```
+/// use primality_jones::partition_work;
+///
+/// let units = partition_work(1_000, 100_000, 4);
+/// assert_eq!(units.len(), 4);
+/// assert_eq!(units[0].0, 1_000);
+/// assert_eq!(units[3].1, 100_000);
+/// // The first unit spans many more exponents than the last
+/// assert!(units[0].1 - units[0].0 > units[3].1 - units[3].0);
+/// ```
+pub fn partition_work(start: u64, end: u64, units: usize) -> Vec<(u64, u64)> {
+    if start >= end || units == 0 {
+        return Vec::new();
+    }
+
+    // Cumulative cost up to x is proportional to x^(a + 1); work in units of
+    // `end` so the powers stay small
+    let scale = end as f64;
+    let cumulative = |x: u64| (x as f64 / scale).powf(LL_COST_EXPONENT + 1.0);
+    let inverse = |c: f64| (c.powf(1.0 / (LL_COST_EXPONENT + 1.0)) * scale).round() as u64;
+    let (low, high) = (cumulative(start), cumulative(end));
+
+    let mut ranges = Vec::with_capacity(units);
+    let mut from = start;
+    for i in 1..=units {
+        let to = if i == units {
+            end
+        } else {
+            inverse(low + (high - low) * i as f64 / units as f64).clamp(from, end)
+        };
+        if to > from {
+            ranges.push((from, to));
+            from = to;
+        }
+    }
+    ranges
+}
+
+/// Ranges at most this wide are sampled by listing their primes first
+const SAMPLE_ENUMERATION_LIMIT: u64 = 1 << 20;
+
+/// Pick a reproducible random sample of prime exponents from `start..end`
+///
+/// Each prime in the range is equally likely to be chosen, and the same
+/// `seed` always yields the same sample (for a given version of `rand`). If
+/// the range holds fewer than `count` primes, all of them are returned. The
+/// sample is sorted in ascending order.
+///
+/// # Arguments
+///
+/// * `start` - Lower bound of the range (inclusive)
+/// * `end` - Upper bound of the range (exclusive)
+/// * `count` - How many exponents to pick
+/// * `seed` - Seed for the random number generator
+///
+/// # Example
+///
+/// ```
+/// use primality_jones::sample_candidates;
+///
+/// let sample = sample_candidates(1_000, 100_000, 10, 42);
+/// assert_eq!(sample.len(), 10);
+/// assert_eq!(sample, sample_candidates(1_000, 100_000, 10, 42));
+/// ```
+pub fn sample_candidates(start: u64, end: u64, count: usize, seed: u64) -> Vec<u64> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    if start >= end || count == 0 {
+        return Vec::new();
+    }
+
+    // Every range of this width below 2^64 holds well over width / 64 primes, so
+    // rejection sampling is only used when it is sure to find `count` of them
+    let width = end - start;
+    let mut sample = if width <= SAMPLE_ENUMERATION_LIMIT || count as u64 > width / 64 {
+        // Small range or large sample: list the primes and pick from them directly
+        let primes: Vec<u64> = (start..end).filter(|&n| is_prime(n)).collect();
+        primes.choose_multiple(&mut rng, count).copied().collect()
+    } else {
+        // Large range: draw integers until enough distinct primes have turned up
+        let mut chosen = HashSet::with_capacity(count);
+        while chosen.len() < count {
+            let n = rng.gen_range(start..end);
+            if is_prime(n) {
+                chosen.insert(n);
+            }
+        }
+        chosen.into_iter().collect::<Vec<_>>()
+    };
+    sample.sort_unstable();
+    sample
+}
+
+/// Process multiple Mersenne candidates in parallel
+///
+/// This function allows efficient processing of multiple candidates
+/// by utilizing all available CPU cores.
+///
+/// # Arguments
+///
+/// * `candidates` - Vector of Mersenne exponents to test
+/// * `level` - How thorough the testing should be
+///
+/// # Returns
+///
+/// Vector of (exponent, results) pairs
+///
+/// # Example
+///
+/// ```
+/// use primality_jones::{CheckLevel, process_candidates_parallel};
+///
+/// let candidates = vec![31, 61, 89, 107, 127];
+/// let results = process_candidates_parallel(candidates, CheckLevel::LucasLehmer);
+/// 
+/// for (p, candidate_results) in results {
+///     if candidate_results.iter().all(|r| r.passed) {
+///         println!("M{} is prime!", p);
+///     }
+/// }
+/// ```
+pub fn process_candidates_parallel(candidates: Vec<u64>, level: CheckLevel) -> Vec<(u64, Vec<CheckResult>)> {
+    process_candidates_parallel_with_config(candidates, level, &CheckConfig::default())
+}
+
+/// Process multiple Mersenne candidates in parallel with custom options
+///
+/// Like `process_candidates_parallel`, but every candidate is checked with
+/// `config`, as in `check_mersenne_candidate_with_config`.
+pub fn process_candidates_parallel_with_config(
+    candidates: Vec<u64>,
+    level: CheckLevel,
+    config: &CheckConfig,
+) -> Vec<(u64, Vec<CheckResult>)> {
+    candidates.into_par_iter()
+        .map(|p| (p, check_mersenne_candidate_with_config(p, level, config)))
+        .collect()
+}
+
+/// Check a slice of Mersenne exponents in parallel
+///
+/// Runs `check_mersenne_candidate` for each exponent on rayon's global
+/// thread pool. The output keeps the order of `ps`, so the results for
+/// `ps[i]` are always at index `i`.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::{check_candidates_parallel, CheckLevel};
+///
+/// let results = check_candidates_parallel(&[31, 37, 61], CheckLevel::TrialFactoring);
+/// let exponents: Vec<u64> = results.iter().map(|(p, _)| *p).collect();
+/// assert_eq!(exponents, [31, 37, 61]);
+/// assert!(!results[1].1.iter().all(|r| r.passed)); // 223 divides M37
+/// ```
+pub fn check_candidates_parallel(ps: &[u64], level: CheckLevel) -> Vec<(u64, Vec<CheckResult>)> {
+    ps.par_iter()
+        .map(|&p| (p, check_mersenne_candidate(p, level)))
+        .collect()
+}
+
+/// Check a slice of Mersenne exponents on at most `max_threads` threads
+///
+/// Like `check_candidates_parallel`, but runs on a dedicated pool of
+/// `max_threads` threads, which also bounds the parallel trial factoring and
+/// Miller-Rabin inside each check. A `max_threads` of 0 lets rayon choose,
+/// as it does for its global pool.
+///
+/// # Panics
+///
+/// Panics if the operating system cannot create the threads.
+pub fn check_candidates_parallel_with_threads(
+    ps: &[u64],
+    level: CheckLevel,
+    max_threads: usize,
+) -> Vec<(u64, Vec<CheckResult>)> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(max_threads)
+        .build()
+        .expect("failed to start the thread pool")
+        .install(|| check_candidates_parallel(ps, level))
+}
+
+/// The largest prime that fits in a `u64`, where `search_mersenne_primes` stops
+pub(crate) const LARGEST_U64_PRIME: u64 = 18_446_744_073_709_551_557;
+
+/// Search upward from `from` for exponents whose M_p passes `level`
+///
+/// Steps through the prime exponents p ≥ `from` with `next_prime`, runs
+/// `check_mersenne_candidate(p, level)` on each, and yields p whenever every
+/// stage passed. Nothing is checked until the iterator is advanced, and no
+/// state is kept beyond the last exponent tried, so a search stopped after
+/// yielding p resumes exactly where it left off by starting again at p + 1.
+/// `prime_sieve` would need an upper limit fixed in advance, which an
+/// open-ended search does not have; next to the checks, finding the next
+/// prime exponent costs nothing.
+///
+/// Below `CheckLevel::LucasLehmer` the exponents yielded are only candidates
+/// that survived the cheaper stages. With `CheckLevel::LucasLehmer` they are
+/// exactly the Mersenne primes, but the search is effectively unbounded work:
+/// the test costs grow roughly with the cube of p and Mersenne primes thin
+/// out, so each one takes far longer to reach than the last. Bound such a
+/// loop yourself, with `take`, by exponent, or by time.
+///
+/// The iterator ends only after the largest prime below 2^64.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::{search_mersenne_primes, CheckLevel};
+///
+/// let found: Vec<u64> = search_mersenne_primes(100, CheckLevel::LucasLehmer).take(2).collect();
+/// assert_eq!(found, [107, 127]);
+///
+/// // Resume after the last one found
+/// assert_eq!(search_mersenne_primes(108, CheckLevel::LucasLehmer).next(), Some(127));
+/// ```
+pub fn search_mersenne_primes(from: u64, level: CheckLevel) -> impl Iterator<Item = u64> {
+    let first = match from {
+        0..=2 => Some(2),
+        _ => (from - 1 < LARGEST_U64_PRIME).then(|| next_prime(from - 1)),
+    };
+    std::iter::successors(first, |&p| (p < LARGEST_U64_PRIME).then(|| next_prime(p)))
+        .filter(move |&p| check_mersenne_candidate(p, level).iter().all(|r| r.passed))
+}
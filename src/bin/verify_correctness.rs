@@ -36,7 +36,7 @@ fn run_empirical_verification() {
     
     // Test 1: Known Mersenne primes
     println!("Testing known Mersenne primes...");
-    let known_primes = [2, 3, 5, 7, 13, 17, 19, 31, 61, 89, 107, 127, 521, 607, 1279, 2203, 2281];
+    let known_primes: Vec<u64> = known_mersenne_primes().iter().copied().take_while(|&p| p <= 2281).collect();
     let mut prime_correct = 0;
     
     for &p in &known_primes {
@@ -53,7 +53,7 @@ fn run_empirical_verification() {
     
     // Test 2: Known composite Mersenne numbers
     println!("\nTesting known composite Mersenne numbers...");
-    let known_composites = [11, 23, 29, 37, 41, 43, 47, 53, 59, 67, 71, 73, 79, 83, 97, 101, 103, 109, 113, 131];
+    let known_composites: Vec<u64> = known_composite_exponents().iter().copied().take_while(|&p| p <= 131).collect();
     let mut composite_correct = 0;
     
     for &p in &known_composites {
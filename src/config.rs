@@ -0,0 +1,383 @@
+/*!
+Options for the checking pipeline, the files they can be loaded from, and
+the optional features this build was compiled with.
+*/
+
+use crate::{estimate_resources, CheckLevel, Exponent, PrimalityBackend, PrimalityError, PrimeCache};
+use num_bigint::BigUint;
+use num_traits::One;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Optional features this crate can be built with, and whether this build has them
+const OPTIONAL_FEATURES: [(&str, bool); 6] = [
+    ("python", cfg!(feature = "pyo3")),
+    ("fft", cfg!(feature = "fft")),
+    ("serde", cfg!(feature = "serde")),
+    ("ffi", cfg!(feature = "ffi")),
+    ("wasm", cfg!(feature = "wasm")),
+    ("primenet", cfg!(feature = "primenet")),
+];
+
+/// What this build of the crate can do, for tools that drive it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    /// The crate version, e.g. "0.2.0"
+    pub version: &'static str,
+    /// Optional features compiled into this build: "python" (the `pyo3`
+    /// bindings), "fft" (the `dwt` module), "serde" (serialization of
+    /// check results), "ffi" (the C entry points in `ffi`), "wasm" (the
+    /// JavaScript bindings in `wasm`) and "primenet" (the online list of
+    /// known Mersenne primes in `primenet`).
+    pub features: Vec<&'static str>,
+}
+
+impl Capabilities {
+    /// Whether the named optional feature is compiled in
+    pub fn has(&self, feature: &str) -> bool {
+        self.features.contains(&feature)
+    }
+}
+
+/// Report the crate version and which optional features are compiled in
+///
+/// # Examples
+///
+/// ```
+/// let caps = primality_jones::capabilities();
+/// assert_eq!(caps.version, env!("CARGO_PKG_VERSION"));
+/// println!("python bindings: {}", caps.has("python"));
+/// ```
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        features: OPTIONAL_FEATURES
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| *name)
+            .collect(),
+    }
+}
+
+/// Largest candidate factor the trial factoring stage tries by default
+pub const DEFAULT_TRIAL_FACTOR_LIMIT: u64 = 1_000_000;
+
+/// Miller-Rabin rounds the probabilistic stage runs by default
+pub const DEFAULT_MILLER_RABIN_ROUNDS: u32 = 5;
+
+/// Time limit for the probabilistic stage by default
+pub const DEFAULT_PROBABILISTIC_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Bit level `CheckLevel::Full` takes trial factoring to, after searching up
+/// to `trial_factor_limit` as usual
+pub const FULL_TRIAL_FACTOR_BITS: u32 = 32;
+
+/// Fewest Miller-Rabin rounds `CheckLevel::Full` runs
+pub const FULL_MILLER_RABIN_ROUNDS: u32 = 20;
+
+/// Ratio of the stage-2 bound B2 to B1 for P-1 at `CheckLevel::Full`
+pub const FULL_P_MINUS_1_B2_MULTIPLIER: u64 = 20;
+
+/// Options for `check_mersenne_candidate_with_config`
+///
+/// The default configuration reproduces `check_mersenne_candidate` exactly:
+///
+/// | Option                  | Default         |
+/// |-------------------------|-----------------|
+/// | `prime_cache`           | none            |
+/// | `known_factors`         | empty           |
+/// | `trial_factor_limit`    | 1,000,000       |
+/// | `miller_rabin_rounds`   | 5               |
+/// | `probabilistic_timeout` | 300 seconds     |
+/// | `lucas_lehmer_timeout`  | none (no limit) |
+/// | `max_memory_bytes`      | none (no limit) |
+/// | `cross_check_backend`   | none            |
+///
+/// The fields can be set directly, or with the builder methods starting from
+/// `CheckConfig::new()`:
+///
+/// ```
+/// use primality_jones::{check_mersenne_candidate_with_config, CheckConfig, CheckLevel};
+/// use std::time::Duration;
+///
+/// let config = CheckConfig::new()
+///     .miller_rabin_rounds(10)
+///     .trial_limit(10_000_000)
+///     .probabilistic_timeout(Duration::from_secs(600));
+/// let results = check_mersenne_candidate_with_config(61, CheckLevel::Probabilistic, &config);
+/// assert!(results.iter().all(|r| r.passed));
+/// ```
+#[derive(Debug, Clone)]
+pub struct CheckConfig {
+    /// Memo table for `is_prime`, shared across every candidate checked with
+    /// this configuration (`None` disables memoization)
+    pub prime_cache: Option<Arc<PrimeCache>>,
+    /// Factors already known for particular exponents, for example from
+    /// `load_known_factors`. A prime exponent with a listed factor that really
+    /// divides M_p is reported composite at the trial factoring stage without
+    /// any search; listed values that do not divide M_p are ignored.
+    pub known_factors: HashMap<u64, Vec<BigUint>>,
+    /// Largest candidate factor q = 2kp + 1 tried by trial factoring
+    /// (default `DEFAULT_TRIAL_FACTOR_LIMIT`)
+    pub trial_factor_limit: u64,
+    /// Rounds run by the Miller-Rabin stage (default `DEFAULT_MILLER_RABIN_ROUNDS`)
+    pub miller_rabin_rounds: u32,
+    /// Time limit for the Miller-Rabin stage, counted from the start of that
+    /// stage (default `DEFAULT_PROBABILISTIC_TIMEOUT`). A test that runs out
+    /// of time is reported as inconclusive.
+    pub probabilistic_timeout: Duration,
+    /// Time limit for the Lucas-Lehmer stage (`None`, the default, for no
+    /// limit). A test that runs out of time is reported as inconclusive.
+    pub lucas_lehmer_timeout: Option<Duration>,
+    /// Memory budget for the stages that compute modulo M_p (`None`, the
+    /// default, for no limit). When `estimate_resources` puts the working set
+    /// above it, those stages are not started; see `CheckConfig::check_memory`.
+    pub max_memory_bytes: Option<u64>,
+    /// A second backend that repeats a finished Lucas-Lehmer test (`None`,
+    /// the default, to trust the first run). When the two final residues
+    /// disagree, the stage is reported as inconclusive with a
+    /// `BackendsDisagree` message instead of a verdict. This roughly doubles
+    /// the cost of the stage, and the second run is not bound by
+    /// `lucas_lehmer_timeout`.
+    pub cross_check_backend: Option<Arc<dyn PrimalityBackend>>,
+}
+
+impl Default for CheckConfig {
+    fn default() -> Self {
+        Self {
+            prime_cache: None,
+            known_factors: HashMap::new(),
+            trial_factor_limit: DEFAULT_TRIAL_FACTOR_LIMIT,
+            miller_rabin_rounds: DEFAULT_MILLER_RABIN_ROUNDS,
+            probabilistic_timeout: DEFAULT_PROBABILISTIC_TIMEOUT,
+            lucas_lehmer_timeout: None,
+            max_memory_bytes: None,
+            cross_check_backend: None,
+        }
+    }
+}
+
+impl CheckConfig {
+    /// The default configuration, as a starting point for the builder methods
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Share `cache` for primality tests on exponents and trial factors
+    pub fn prime_cache(mut self, cache: Arc<PrimeCache>) -> Self {
+        self.prime_cache = Some(cache);
+        self
+    }
+
+    /// Use `factors` as the table of known factors
+    pub fn known_factors(mut self, factors: HashMap<u64, Vec<BigUint>>) -> Self {
+        self.known_factors = factors;
+        self
+    }
+
+    /// Try trial factors up to `limit`
+    pub fn trial_limit(mut self, limit: u64) -> Self {
+        self.trial_factor_limit = limit;
+        self
+    }
+
+    /// Run `rounds` Miller-Rabin rounds
+    pub fn miller_rabin_rounds(mut self, rounds: u32) -> Self {
+        self.miller_rabin_rounds = rounds;
+        self
+    }
+
+    /// Give the Miller-Rabin stage `timeout` to finish
+    pub fn probabilistic_timeout(mut self, timeout: Duration) -> Self {
+        self.probabilistic_timeout = timeout;
+        self
+    }
+
+    /// Give the Lucas-Lehmer stage `timeout` to finish
+    pub fn lucas_lehmer_timeout(mut self, timeout: Duration) -> Self {
+        self.lucas_lehmer_timeout = Some(timeout);
+        self
+    }
+
+    /// Refuse to start stages whose working set would exceed `bytes`
+    pub fn max_memory_bytes(mut self, bytes: u64) -> Self {
+        self.max_memory_bytes = Some(bytes);
+        self
+    }
+
+    /// Repeat finished Lucas-Lehmer tests on `backend` and compare residues
+    pub fn cross_check_backend(mut self, backend: Arc<dyn PrimalityBackend>) -> Self {
+        self.cross_check_backend = Some(backend);
+        self
+    }
+
+    /// Check that checking M_p up to `level` fits in `max_memory_bytes`
+    ///
+    /// The working set is `estimate_resources(p, level).bytes_required`, so
+    /// the pre-screen and trial factoring, which never hold a value the size
+    /// of M_p, always fit. The pipeline makes this check before P-1 and
+    /// reports a failure as an inconclusive P-1 stage; call it yourself to get
+    /// the error before anything runs.
+    ///
+    /// # Errors
+    ///
+    /// `WouldExceedMemory` if the estimate is over the budget.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use primality_jones::{CheckConfig, CheckLevel, PrimalityError};
+    ///
+    /// let config = CheckConfig::new().max_memory_bytes(1 << 20);
+    /// assert!(config.check_memory(82_589_933, CheckLevel::TrialFactoring).is_ok());
+    /// assert!(matches!(
+    ///     config.check_memory(82_589_933, CheckLevel::LucasLehmer),
+    ///     Err(PrimalityError::WouldExceedMemory { .. })
+    /// ));
+    /// ```
+    pub fn check_memory(&self, p: impl Into<Exponent>, level: CheckLevel) -> Result<(), PrimalityError> {
+        let p = p.into().get();
+        let Some(max_bytes) = self.max_memory_bytes else {
+            return Ok(());
+        };
+        let required_bytes = estimate_resources(p, level).bytes_required;
+        if required_bytes > max_bytes {
+            Err(PrimalityError::WouldExceedMemory { exponent: p, required_bytes, max_bytes })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Read a table of known Mersenne factors, one exponent per line
+///
+/// Each line has the form `M11: 23, 89` (the `M` is optional). Blank lines and
+/// lines starting with `#` are skipped, and an exponent listed on several lines
+/// collects the factors from all of them.
+///
+/// # Errors
+///
+/// Fails if the file cannot be read or a line cannot be parsed; the error
+/// message gives the line number.
+///
+/// # Examples
+///
+/// ```no_run
+/// use primality_jones::{check_mersenne_candidate_with_config, load_known_factors, CheckConfig, CheckLevel};
+///
+/// let config = CheckConfig {
+///     known_factors: load_known_factors("factors.txt")?,
+///     ..CheckConfig::default()
+/// };
+/// let results = check_mersenne_candidate_with_config(67, CheckLevel::LucasLehmer, &config);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn load_known_factors<P: AsRef<Path>>(path: P) -> io::Result<HashMap<u64, Vec<BigUint>>> {
+    let content = fs::read_to_string(path)?;
+    let mut known_factors: HashMap<u64, Vec<BigUint>> = HashMap::new();
+
+    for (line_num, line) in content.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: expected 'M<exponent>: <factor>, ...', got '{}'", line_num + 1, trimmed),
+            )
+        };
+
+        let (exponent, factors) = trimmed.split_once(':').ok_or_else(invalid)?;
+        let p = exponent
+            .trim()
+            .trim_start_matches(['M', 'm'])
+            .parse::<u64>()
+            .map_err(|_| invalid())?;
+        let entry = known_factors.entry(p).or_default();
+        for factor in factors.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            entry.push(factor.parse::<BigUint>().map_err(|_| invalid())?);
+        }
+    }
+
+    Ok(known_factors)
+}
+
+/// Read the Mersenne exponents from a Prime95/GIMPS `worktodo.txt`
+///
+/// Every line accepted by `parse_worktodo_line` contributes its exponent, in
+/// file order. Anything else (worker headers such as `[Worker #1]`, comments,
+/// other work types and malformed entries) is skipped, so an existing
+/// worktodo can be used as it is.
+///
+/// # Errors
+///
+/// Fails only if the file cannot be read.
+///
+/// # Examples
+///
+/// ```no_run
+/// use primality_jones::{check_candidates_parallel, parse_worktodo, CheckLevel};
+///
+/// let exponents = parse_worktodo("worktodo.txt")?;
+/// let results = check_candidates_parallel(&exponents, CheckLevel::LucasLehmer);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn parse_worktodo<P: AsRef<Path>>(path: P) -> io::Result<Vec<u64>> {
+    let content = fs::read_to_string(path)?;
+    Ok(content.lines().filter_map(parse_worktodo_line).collect())
+}
+
+/// The Mersenne exponent assigned by one line of a `worktodo.txt`
+///
+/// Understands the Lucas-Lehmer and PRP work types:
+///
+/// * `Test=[AID,]p,...` and `DoubleCheck=[AID,]p,...`
+/// * `PRP=[AID,]k,b,n,c,...` and `PRPDC=...`, when they name a Mersenne
+///   number, that is k = 1, b = 2 and c = -1 (giving n)
+///
+/// The optional assignment ID is either `N/A` or 32 hex digits. Returns
+/// `None` for any other line, including exponents below 2.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::parse_worktodo_line;
+///
+/// assert_eq!(parse_worktodo_line("Test=N/A,127,68,1"), Some(127));
+/// assert_eq!(parse_worktodo_line("PRP=1,2,136279841,-1"), Some(136279841));
+/// assert_eq!(parse_worktodo_line("PRP=1,3,1000,-1"), None); // not a Mersenne number
+/// assert_eq!(parse_worktodo_line("[Worker #1]"), None);
+/// ```
+pub fn parse_worktodo_line(line: &str) -> Option<u64> {
+    let (work_type, args) = line.trim().split_once('=')?;
+    let mut fields = args.split(',').map(str::trim).peekable();
+    let is_assignment_id =
+        |f: &&str| *f == "N/A" || (f.len() == 32 && f.bytes().all(|b| b.is_ascii_hexdigit()));
+    fields.next_if(is_assignment_id);
+
+    let p = match work_type.trim() {
+        "Test" | "DoubleCheck" => fields.next()?.parse::<u64>().ok()?,
+        "PRP" | "PRPDC" => {
+            let (k, b, n, c) = (fields.next()?, fields.next()?, fields.next()?, fields.next()?);
+            if (k, b, c) != ("1", "2", "-1") {
+                return None;
+            }
+            n.parse::<u64>().ok()?
+        }
+        _ => return None,
+    };
+    (p >= 2).then_some(p)
+}
+
+/// Whether `q` is a proper factor of M_p, that is neither 1 nor M_p itself
+pub(crate) fn is_proper_mersenne_factor(p: u64, q: &BigUint) -> bool {
+    q > &BigUint::one()
+        && q.bits() <= p
+        && q != &((BigUint::one() << p) - BigUint::one())
+        && BigUint::from(2u32).modpow(&BigUint::from(p), q).is_one()
+}
@@ -0,0 +1,451 @@
+/*!
+Lucas-Lehmer testing with FFT squaring, for exponents where schoolbook
+`BigUint` multiplication becomes the bottleneck.
+
+Squaring modulo M_p is done with the irrational-base discrete weighted
+transform (IBDWT) of Crandall and Fagin, the method GIMPS uses. M_p is split
+into n digits of ⌈p/n⌉ or ⌊p/n⌋ bits, digit j starting at bit ⌈pj/n⌉. Scaling
+digit j by the weight 2^(⌈pj/n⌉ - pj/n) turns squaring modulo 2^p - 1 into a
+plain cyclic convolution of length n, which a complex FFT computes in
+O(n log n) operations. The wrap-around that `mod_mp` performs with a shift and
+an add is absorbed by the cyclic convolution itself.
+
+Digits are kept balanced, in [-2^(b-1), 2^(b-1)), which keeps convolution
+outputs small and the floating-point roundoff well below 0.5. Each squaring
+measures that roundoff; if it ever reaches `MAX_ROUNDOFF` the test is rerun
+at twice the transform length, so a result is never built on a misrounded
+digit. `dwt_max_roundoff` reports it for a given exponent, to show how much
+margin is left. `DwtBackend` offers the test as a `PrimalityBackend`, so its
+residues can be checked against schoolbook arithmetic.
+
+This module is only compiled with the `fft` feature.
+*/
+
+use crate::{mod_mp_with_modulus, validate_exponent, Exponent, PrimalityBackend, PrimalityError};
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f64::consts::PI;
+
+/// Largest distance from the nearest integer a convolution output may have
+/// before the squaring is considered unreliable
+const MAX_ROUNDOFF: f64 = 0.25;
+
+/// Budget in bits for a convolution output: 2(b - 1) + log2(n), where b is
+/// the largest digit size. This bounds the worst case where every product
+/// lines up; in practice outputs are far smaller, and the largest roundoff
+/// seen for exponents up to 80000 is about 0.005.
+const CONVOLUTION_BITS: u32 = 46;
+
+/// 1.5 · 2^52, see `Transform::square_and_subtract_two`
+const ROUNDING_CONSTANT: f64 = 6_755_399_441_055_744.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn add(self, other: Complex) -> Complex {
+        Complex { re: self.re + other.re, im: self.im + other.im }
+    }
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex { re: self.re - other.re, im: self.im - other.im }
+    }
+
+    fn scale(self, factor: f64) -> Complex {
+        Complex { re: self.re * factor, im: self.im * factor }
+    }
+
+    fn conj(self) -> Complex {
+        Complex { re: self.re, im: -self.im }
+    }
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex {
+            re: self.re * other.re - self.im * other.im,
+            im: self.re * other.im + self.im * other.re,
+        }
+    }
+}
+
+/// Precomputed weights, digit sizes and twiddle factors for one (p, n) pair
+///
+/// The n weighted digits are real, so they are packed in pairs into a complex
+/// FFT of length n/2, halving the transform work.
+struct Transform {
+    p: u64,
+    n: usize,
+    /// Size in bits of each digit
+    bits: Vec<u32>,
+    /// The IBDWT weights 2^(⌈pj/n⌉ - pj/n)
+    weights: Vec<f64>,
+    /// 1 / ((n/2) · weight), undoing both the weight and the unscaled inverse FFT
+    unweights: Vec<f64>,
+    /// Roots of unity for each butterfly pass of the length-n/2 FFT: the pass
+    /// combining blocks of size h uses e^(-πik/h) for k < h, stored from
+    /// index h - 1
+    twiddles: Vec<Complex>,
+    /// The same roots conjugated, for the inverse transform
+    inverse_twiddles: Vec<Complex>,
+    /// Bit-reversal permutation of 0..n/2
+    reversed: Vec<usize>,
+    /// e^(-2πik/n) for k < n/2, which separates the packed even and odd digits
+    unpack: Vec<Complex>,
+}
+
+impl Transform {
+    fn new(p: u64, n: usize) -> Self {
+        assert!(
+            n.is_power_of_two() && n >= 2 && n as u64 <= p,
+            "transform length {} does not suit M{}",
+            n,
+            p
+        );
+        let n64 = n as u64;
+        let m = n / 2;
+        let start = |j: u64| (p * j).div_ceil(n64);
+        let bits = (0..n64).map(|j| (start(j + 1) - start(j)) as u32).collect();
+        let weights: Vec<f64> = (0..n64)
+            .map(|j| ((start(j) * n64 - p * j) as f64 / n as f64).exp2())
+            .collect();
+        let unweights = weights.iter().map(|w| 1.0 / (m as f64 * w)).collect();
+        let root = |k: usize, order: usize| {
+            let angle = -2.0 * PI * k as f64 / order as f64;
+            Complex { re: angle.cos(), im: angle.sin() }
+        };
+        let twiddles: Vec<Complex> = (0..m.trailing_zeros())
+            .flat_map(|level| {
+                let half = 1usize << level;
+                (0..half).map(move |k| root(k, 2 * half))
+            })
+            .collect();
+        let inverse_twiddles = twiddles.iter().map(|w| w.conj()).collect();
+        let shift = usize::BITS - m.trailing_zeros();
+        let reversed = (0..m)
+            .map(|i| if m > 1 { i.reverse_bits() >> shift } else { 0 })
+            .collect();
+        let unpack = (0..m).map(|k| root(k, n)).collect();
+        Self { p, n, bits, weights, unweights, twiddles, inverse_twiddles, reversed, unpack }
+    }
+
+    /// Smallest power-of-two length whose convolution outputs fit the budget
+    fn default_length(p: u64) -> usize {
+        let mut n = 2usize;
+        loop {
+            let digit_bits = p.div_ceil(n as u64) as u32;
+            // Every digit needs at least one bit, so n never exceeds p
+            if 2 * (digit_bits - 1) + n.trailing_zeros() <= CONVOLUTION_BITS || 2 * n as u64 > p {
+                return n;
+            }
+            n *= 2;
+        }
+    }
+
+    /// In-place radix-2 FFT of length n/2; `inverse` conjugates the twiddles
+    /// and skips the 1/(n/2)
+    fn fft(&self, data: &mut [Complex], inverse: bool) {
+        for (i, &j) in self.reversed.iter().enumerate() {
+            if i < j {
+                data.swap(i, j);
+            }
+        }
+
+        let twiddles = if inverse { &self.inverse_twiddles } else { &self.twiddles };
+        let mut half = 1;
+        while half < data.len() {
+            let roots = &twiddles[half - 1..2 * half - 1];
+            for chunk in data.chunks_mut(2 * half) {
+                let (low, high) = chunk.split_at_mut(half);
+                for ((even, odd), &w) in low.iter_mut().zip(high.iter_mut()).zip(roots) {
+                    let t = odd.mul(w);
+                    *odd = even.sub(t);
+                    *even = even.add(t);
+                }
+            }
+            half *= 2;
+        }
+    }
+
+    /// Square the length-n spectrum held packed in `z` at bins k and m - k
+    ///
+    /// `a` is bin k of the packed transform and `b` bin (m - k) mod m. Splits
+    /// them into the spectra of the even and odd digits, rebuilds bins k and
+    /// k + n/2 of the full transform, squares those, and packs the result
+    /// back the same way. Returns the new value for bin k.
+    fn square_packed_bin(&self, k: usize, a: Complex, b: Complex) -> Complex {
+        let even = a.add(b.conj()).scale(0.5);
+        let odd = a.sub(b.conj()).mul(Complex { re: 0.0, im: -0.5 });
+        let twisted = odd.mul(self.unpack[k]);
+        let low = even.add(twisted);
+        let high = even.sub(twisted);
+        let (low, high) = (low.mul(low), high.mul(high));
+        let even = low.add(high).scale(0.5);
+        let odd = low.sub(high).scale(0.5).mul(self.unpack[k].conj());
+        even.add(odd.mul(Complex { re: 0.0, im: 1.0 }))
+    }
+
+    /// Replace `digits` by the balanced digits of x² - 2 mod M_p
+    ///
+    /// `scratch` must hold n/2 values; it is only reused to avoid an
+    /// allocation per squaring. Returns the largest distance of a convolution
+    /// output from the nearest integer, so the caller can reject a squaring
+    /// that rounded wrongly.
+    fn square_and_subtract_two(&self, digits: &mut [i64], scratch: &mut [Complex]) -> f64 {
+        for (j, x) in scratch.iter_mut().enumerate() {
+            *x = Complex {
+                re: digits[2 * j] as f64 * self.weights[2 * j],
+                im: digits[2 * j + 1] as f64 * self.weights[2 * j + 1],
+            };
+        }
+        self.fft(scratch, false);
+        let m = scratch.len();
+        for k in 0..=m / 2 {
+            let mirror = (m - k) % m;
+            let (a, b) = (scratch[k], scratch[mirror]);
+            scratch[k] = self.square_packed_bin(k, a, b);
+            if mirror != k {
+                scratch[mirror] = self.square_packed_bin(mirror, b, a);
+            }
+        }
+        self.fft(scratch, true);
+
+        let mut roundoff: f64 = 0.0;
+        for (j, x) in scratch.iter().enumerate() {
+            for (i, value) in [(2 * j, x.re), (2 * j + 1, x.im)] {
+                let value = value * self.unweights[i];
+                // Adding and removing 1.5 · 2^52 rounds to the nearest integer
+                // for |value| < 2^51, much faster than `f64::round`
+                let rounded = (value + ROUNDING_CONSTANT) - ROUNDING_CONSTANT;
+                roundoff = roundoff.max((value - rounded).abs());
+                digits[i] = rounded as i64;
+            }
+        }
+        digits[0] -= 2;
+        self.carry(digits);
+        roundoff
+    }
+
+    /// Bring every digit back into its balanced range
+    ///
+    /// A carry out of the top digit re-enters at digit 0, since 2^p ≡ 1 (mod M_p).
+    fn carry(&self, digits: &mut [i64]) {
+        let mut carry = 0i64;
+        let mut normalize = |j: usize, carry: &mut i64| {
+            let b = self.bits[j];
+            let value = digits[j] + *carry;
+            // Two's complement makes the mask a Euclidean remainder mod 2^b
+            let mut digit = value & ((1 << b) - 1);
+            if digit >= 1 << (b - 1) {
+                digit -= 1 << b;
+            }
+            digits[j] = digit;
+            *carry = (value - digit) >> b;
+        };
+        for j in 0..self.n {
+            normalize(j, &mut carry);
+        }
+        let mut j = 0;
+        while carry != 0 {
+            normalize(j, &mut carry);
+            j = (j + 1) % self.n;
+        }
+    }
+
+    /// The value the digits represent, reduced mod M_p
+    fn to_biguint(&self, digits: &[i64]) -> BigUint {
+        let mut positive = BigUint::zero();
+        let mut negative = BigUint::zero();
+        let mut shift = 0u64;
+        for (&d, &b) in digits.iter().zip(&self.bits) {
+            let magnitude = BigUint::from(d.unsigned_abs()) << shift;
+            if d >= 0 {
+                positive += magnitude;
+            } else {
+                negative += magnitude;
+            }
+            shift += b as u64;
+        }
+        let mp = (BigUint::one() << self.p) - BigUint::one();
+        (mod_mp_with_modulus(&positive, &mp, self.p) + &mp - mod_mp_with_modulus(&negative, &mp, self.p)) % mp
+    }
+
+    /// Run the Lucas-Lehmer sequence for M_p with this transform
+    ///
+    /// Returns the final residue, or `None` if a squaring exceeded `MAX_ROUNDOFF`.
+    fn lucas_lehmer(&self) -> Option<BigUint> {
+        let mut digits = vec![0i64; self.n];
+        let mut scratch = vec![Complex { re: 0.0, im: 0.0 }; self.n / 2];
+        digits[0] = 4;
+        self.carry(&mut digits);
+        for _ in 0..self.p - 2 {
+            if self.square_and_subtract_two(&mut digits, &mut scratch) >= MAX_ROUNDOFF {
+                return None;
+            }
+        }
+        Some(self.to_biguint(&digits))
+    }
+}
+
+/// Final Lucas-Lehmer residue for M_p, computed with IBDWT squaring
+///
+/// Starts at the default transform length for p and doubles it whenever a
+/// squaring's roundoff is too large to trust.
+fn lucas_lehmer_residue_fft(p: u64) -> BigUint {
+    let mut n = Transform::default_length(p);
+    loop {
+        if let Some(residue) = Transform::new(p, n).lucas_lehmer() {
+            return residue;
+        }
+        n *= 2;
+    }
+}
+
+/// Largest roundoff of one squaring modulo M_p at the transform length
+/// `lucas_lehmer_test_fft` starts with
+///
+/// Squares a value whose balanced digits are pseudo-random, as the digits of
+/// a Lucas-Lehmer value are once it fills M_p, and returns the largest
+/// distance of any convolution output from the nearest integer. A digit is
+/// misrounded at 0.5, and `lucas_lehmer_test_fft` stops trusting a squaring
+/// at 0.25, so this shows how much margin the transform has at this size.
+/// The digits are seeded from p, so the result is reproducible. Returns 0 for
+/// p < 2, which has no transform.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::dwt::dwt_max_roundoff;
+///
+/// let roundoff = dwt_max_roundoff(86_243);
+/// assert!(roundoff > 0.0 && roundoff < 0.25);
+/// ```
+pub fn dwt_max_roundoff(p: impl Into<Exponent>) -> f64 {
+    let p = p.into().get();
+    if p < 2 {
+        return 0.0;
+    }
+    let transform = Transform::new(p, Transform::default_length(p));
+    let mut rng = StdRng::seed_from_u64(p);
+    let mut digits: Vec<i64> = transform
+        .bits
+        .iter()
+        .map(|&b| rng.gen_range(-(1i64 << (b - 1))..1i64 << (b - 1)))
+        .collect();
+    let mut scratch = vec![Complex { re: 0.0, im: 0.0 }; transform.n / 2];
+    transform.square_and_subtract_two(&mut digits, &mut scratch)
+}
+
+/// Perform the Lucas-Lehmer test on M_p with FFT-based squaring
+///
+/// Gives the same answer as `lucas_lehmer_test`, but each squaring costs
+/// O(p log p) instead of the O(p²) of schoolbook `BigUint` multiplication,
+/// which pays off for exponents in the tens of thousands and beyond.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::dwt::lucas_lehmer_test_fft;
+///
+/// assert!(lucas_lehmer_test_fft(521));  // M521 is prime
+/// assert!(!lucas_lehmer_test_fft(523)); // M523 is composite
+/// ```
+pub fn lucas_lehmer_test_fft(p: impl Into<Exponent>) -> bool {
+    let p = p.into().get();
+    if p < 2 {
+        return false;
+    }
+    if p == 2 {
+        return true;
+    }
+    lucas_lehmer_residue_fft(p).is_zero()
+}
+
+/// The Lucas-Lehmer test with IBDWT squaring, as a `PrimalityBackend`
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::dwt::DwtBackend;
+/// use primality_jones::{CrossChecked, PrimalityBackend, SchoolbookBackend};
+///
+/// let backend = CrossChecked { primary: DwtBackend, secondary: SchoolbookBackend };
+/// assert!(backend.lucas_lehmer_residue(1279).unwrap() == 0u32.into()); // M1279 is prime
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DwtBackend;
+
+impl PrimalityBackend for DwtBackend {
+    fn name(&self) -> &str {
+        "dwt"
+    }
+
+    fn lucas_lehmer_residue(&self, p: u64) -> Result<BigUint, PrimalityError> {
+        validate_exponent(p)?;
+        if p == 2 {
+            return Ok(BigUint::zero());
+        }
+        Ok(lucas_lehmer_residue_fft(p))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::square_and_subtract_two_mod_mp;
+
+    #[test]
+    fn test_squaring_matches_mod_mp() {
+        for p in [3u64, 5, 7, 31, 61, 89, 127, 521, 1279] {
+            let transform = Transform::new(p, Transform::default_length(p));
+            let mut digits = vec![0i64; transform.n];
+            let mut scratch = vec![Complex { re: 0.0, im: 0.0 }; transform.n / 2];
+            digits[0] = 4;
+            transform.carry(&mut digits);
+            let mut s = BigUint::from(4u32);
+            for i in 0..50.min(p - 2) {
+                assert!(transform.square_and_subtract_two(&mut digits, &mut scratch) < MAX_ROUNDOFF);
+                s = square_and_subtract_two_mod_mp(&s, p);
+                assert_eq!(transform.to_biguint(&digits), &s % ((BigUint::one() << p) - 1u32), "M{} iteration {}", p, i + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_dwt_max_roundoff() {
+        // The largest exponent each length is used for has the largest digits
+        // and so the most roundoff
+        for log_n in 1..=20u32 {
+            let n = 1usize << log_n;
+            let p = n as u64 * u64::from((CONVOLUTION_BITS - log_n) / 2 + 1);
+            assert_eq!(Transform::default_length(p), n);
+            assert_eq!(Transform::default_length(p + 1), 2 * n);
+            let roundoff = dwt_max_roundoff(p);
+            assert!(roundoff < MAX_ROUNDOFF, "M{} at length {}: {}", p, n, roundoff);
+        }
+        assert_eq!(dwt_max_roundoff(1), 0.0);
+        assert_eq!(dwt_max_roundoff(9941), dwt_max_roundoff(9941));
+    }
+
+    #[test]
+    fn test_dwt_backend_agrees_with_schoolbook() {
+        let backend = crate::CrossChecked { primary: DwtBackend, secondary: crate::SchoolbookBackend };
+        for p in [2u64, 3, 11, 89, 521, 523, 2203, 4423] {
+            let residue = backend.lucas_lehmer_residue(p).unwrap();
+            assert_eq!(residue.is_zero(), crate::lucas_lehmer_test(p), "M{}", p);
+        }
+        assert!(matches!(DwtBackend.lucas_lehmer_residue(1), Err(PrimalityError::InvalidExponent(1))));
+    }
+
+    #[test]
+    fn test_carry_wraps_around() {
+        // 2^p ≡ 1 (mod M_p), so a value of exactly 2^p must come back as 1
+        let transform = Transform::new(7, 2);
+        let mut digits = vec![0i64, 1 << transform.bits[1]];
+        transform.carry(&mut digits);
+        assert_eq!(transform.to_biguint(&digits), BigUint::one());
+    }
+}
@@ -0,0 +1,179 @@
+/*!
+Verdicts and errors for the tests that can fail to reach a verdict.
+*/
+
+use crate::{format_res64, Exponent, MillerRabinResult};
+use std::fmt;
+use std::io;
+
+/// What a primality test established about M_p
+///
+/// Unlike the `bool` returned by `miller_rabin_test` and `lucas_lehmer_test`,
+/// this says how much a verdict is worth: a Lucas-Lehmer answer is a proof,
+/// a Miller-Rabin pass is only evidence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckOutcome {
+    /// Proven prime by the Lucas-Lehmer test
+    DefinitelyPrime,
+    /// Proven composite by the Lucas-Lehmer test
+    DefinitelyComposite,
+    /// Passed every round of a probabilistic test
+    ProbablyPrime,
+    /// A probabilistic test found a witness. This is certain too, but unlike
+    /// `DefinitelyComposite` it comes from a test that cannot prove primality.
+    Composite,
+    /// The test ran out of time before reaching a verdict
+    Inconclusive,
+}
+
+impl CheckOutcome {
+    /// Whether the outcome rules M_p out
+    pub fn is_composite(self) -> bool {
+        matches!(self, CheckOutcome::DefinitelyComposite | CheckOutcome::Composite)
+    }
+}
+
+impl From<MillerRabinResult> for CheckOutcome {
+    fn from(result: MillerRabinResult) -> Self {
+        match result {
+            MillerRabinResult::ProbablyPrime => CheckOutcome::ProbablyPrime,
+            MillerRabinResult::Composite => CheckOutcome::Composite,
+            MillerRabinResult::Inconclusive => CheckOutcome::Inconclusive,
+        }
+    }
+}
+
+/// Why a primality test could not reach a verdict
+///
+/// Returned by the functions that give a `CheckOutcome`, where a plain `bool`
+/// would have to report every one of these as "not prime".
+#[derive(Debug)]
+pub enum PrimalityError {
+    /// The time allowed ran out before the test reached a verdict
+    TimedOut,
+    /// The exponent does not identify a Mersenne number worth testing
+    /// (M_0 = 0 and M_1 = 1 are neither prime nor composite)
+    InvalidExponent(u64),
+    /// A checkpoint file was readable but does not describe a valid state
+    CheckpointCorrupt(String),
+    /// Reading or writing a checkpoint failed
+    Io(io::Error),
+    /// A Lucas-Lehmer value failed its error check, so the computation went
+    /// wrong somewhere; on correct arithmetic this points to faulty hardware
+    ConsistencyCheckFailed {
+        /// The Mersenne exponent being tested
+        exponent: u64,
+        /// Iterations completed when the check failed
+        iteration: u64,
+    },
+    /// The working set for M_p is estimated to exceed the memory budget, so
+    /// the test was not started
+    WouldExceedMemory {
+        /// The Mersenne exponent that was to be tested
+        exponent: u64,
+        /// Estimated working set, from `estimate_resources`
+        required_bytes: u64,
+        /// The budget, `CheckConfig::max_memory_bytes`
+        max_bytes: u64,
+    },
+    /// Two backends computed different final Lucas-Lehmer residues, so at
+    /// least one of them is wrong and neither verdict can be trusted
+    BackendsDisagree {
+        /// The Mersenne exponent being tested
+        exponent: u64,
+        /// `PrimalityBackend::name` of the first backend
+        primary: String,
+        /// Low 64 bits of the first backend's residue
+        primary_res64: u64,
+        /// `PrimalityBackend::name` of the second backend
+        secondary: String,
+        /// Low 64 bits of the second backend's residue
+        secondary_res64: u64,
+    },
+}
+
+impl fmt::Display for PrimalityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrimalityError::TimedOut => write!(f, "test timed out before reaching a verdict"),
+            // M_0 = 0 and M_1 = 1, so the exponent doubles as the value
+            PrimalityError::InvalidExponent(p @ (0 | 1)) => {
+                write!(f, "M_{p} = {p} is neither prime nor composite")
+            }
+            PrimalityError::InvalidExponent(p) => write!(f, "invalid exponent {p}"),
+            PrimalityError::CheckpointCorrupt(reason) => write!(f, "corrupt checkpoint: {reason}"),
+            PrimalityError::Io(e) => write!(f, "checkpoint I/O failed: {e}"),
+            PrimalityError::ConsistencyCheckFailed { exponent, iteration } => write!(
+                f,
+                "Lucas-Lehmer value for M{exponent} failed its error check after {iteration} iterations; \
+                 the hardware may be faulty"
+            ),
+            PrimalityError::WouldExceedMemory { exponent, required_bytes, max_bytes } => write!(
+                f,
+                "checking M{exponent} needs about {required_bytes} bytes, more than the limit of {max_bytes}"
+            ),
+            PrimalityError::BackendsDisagree { exponent, primary, primary_res64, secondary, secondary_res64 } => {
+                write!(
+                    f,
+                    "Lucas-Lehmer residues for M{exponent} disagree: {primary} gives res64 {}, {secondary} gives {}",
+                    format_res64(*primary_res64),
+                    format_res64(*secondary_res64)
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for PrimalityError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PrimalityError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PrimalityError {
+    fn from(e: io::Error) -> Self {
+        PrimalityError::Io(e)
+    }
+}
+
+impl From<PrimalityError> for io::Error {
+    fn from(e: PrimalityError) -> Self {
+        match e {
+            PrimalityError::Io(e) => e,
+            PrimalityError::TimedOut => io::Error::new(io::ErrorKind::TimedOut, e.to_string()),
+            PrimalityError::WouldExceedMemory { .. } => io::Error::new(io::ErrorKind::OutOfMemory, e.to_string()),
+            _ => io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+        }
+    }
+}
+
+/// Check that `p` names a Mersenne number that is either prime or composite
+///
+/// M_0 = 0 and M_1 = 1 are neither, so asking whether they are prime is a
+/// mistake rather than a question with the answer "no". The functions that
+/// return a `CheckOutcome` call this first, and `check_mersenne_candidate`
+/// reports such exponents as inconclusive rather than composite.
+///
+/// # Errors
+///
+/// `InvalidExponent` if p is 0 or 1.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::{validate_exponent, PrimalityError};
+///
+/// assert!(validate_exponent(2).is_ok());
+/// assert!(matches!(validate_exponent(1), Err(PrimalityError::InvalidExponent(1))));
+/// ```
+pub fn validate_exponent(p: impl Into<Exponent>) -> Result<(), PrimalityError> {
+    let p = p.into().get();
+    if p < 2 {
+        Err(PrimalityError::InvalidExponent(p))
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,220 @@
+/*!
+Estimates of the time and memory a check will take, made before running it.
+*/
+
+use crate::{
+    default_p_minus_1_bound, lucas_lehmer_iterations, mersenne_digit_count, CheckKind, CheckLevel, Exponent,
+    DEFAULT_MILLER_RABIN_ROUNDS, FULL_MILLER_RABIN_ROUNDS, FULL_P_MINUS_1_B2_MULTIPLIER,
+};
+use std::time::Duration;
+
+/// Rough cost of checking one candidate, as returned by `estimate_resources`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceEstimate {
+    /// Number of decimal digits in M_p
+    pub decimal_digits: u64,
+    /// Memory for the working values of the most demanding stage, in bytes
+    pub bytes_required: u64,
+    /// Expected wall-clock time for every stage up to the chosen level,
+    /// assuming the candidate passes them all
+    pub estimated_duration: Duration,
+}
+
+/// Measured seconds per Lucas-Lehmer iteration (one
+/// `square_and_subtract_two_mod_mp`) in a release build, by exponent
+pub(crate) const LUCAS_LEHMER_ITERATION_SECONDS: [(u64, f64); 9] = [
+    (1_279, 4.1e-7),
+    (4_423, 4.8e-6),
+    (9_941, 1.48e-5),
+    (21_701, 4.9e-5),
+    (44_497, 1.51e-4),
+    (86_243, 3.3e-4),
+    (132_049, 6.75e-4),
+    (216_091, 1.34e-3),
+    (756_839, 9.5e-3),
+];
+
+/// Measured seconds per Miller-Rabin round on M_p in a release build, by exponent
+///
+/// A round works modulo M_p without the shift-and-add reduction, so it costs
+/// several complete Lucas-Lehmer tests.
+const MILLER_RABIN_ROUND_SECONDS: [(u64, f64); 5] = [
+    (1_279, 1.9e-3),
+    (4_423, 7.3e-2),
+    (9_941, 0.745),
+    (21_701, 10.1),
+    (44_497, 70.2),
+];
+
+/// Stage-1 P-1 costs about this many Lucas-Lehmer iterations per unit of B1
+const P_MINUS_1_ITERATIONS_PER_B1: f64 = 8.0;
+
+/// Stage-2 P-1 costs about this many Lucas-Lehmer iterations per prime
+/// between B1 and B2: two multiplications modulo M_p, measured at M9941 to
+/// M86243 with B2 = 20 * B1
+const P_MINUS_1_STAGE_2_ITERATIONS_PER_PRIME: f64 = 2.0;
+
+/// Interpolate a table of measured costs on a log-log scale, extending the
+/// nearest segment beyond either end
+pub(crate) fn interpolate_cost(table: &[(u64, f64)], p: u64) -> f64 {
+    let i = table.partition_point(|&(q, _)| q < p).clamp(1, table.len() - 1);
+    let (x0, y0) = ((table[i - 1].0 as f64).ln(), table[i - 1].1.ln());
+    let (x1, y1) = ((table[i].0 as f64).ln(), table[i].1.ln());
+    let x = (p.max(2) as f64).ln();
+    (y0 + (x - x0) * (y1 - y0) / (x1 - x0)).exp()
+}
+
+/// Memory for the working values of a stage that computes modulo M_p, in bytes
+///
+/// s, its double-length square and the multiplication temporaries come to
+/// about eight p-bit values.
+pub(crate) fn working_bytes(p: u64) -> u64 {
+    p.div_ceil(64).saturating_mul(64)
+}
+
+/// Estimate the digits, memory and time needed to check M_p up to `level`
+///
+/// Times come from per-iteration costs measured at reference exponents from
+/// M1279 to M756839 on a single core of a release build, interpolated between
+/// them and extrapolated beyond. They are meant for warnings and progress
+/// displays, not promises: expect a factor of two either way on other
+/// hardware, and much less when a stage finds a factor early. Trial
+/// factoring and the pre-screen take well under a second at the default
+/// limits and are not counted. Miller-Rabin rounds are assumed to run in
+/// parallel on rayon's current thread pool. At `CheckLevel::Full` the P-1
+/// stage includes its second stage and Miller-Rabin its extra rounds.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::{estimate_resources, CheckLevel};
+///
+/// let estimate = estimate_resources(82_589_933, CheckLevel::LucasLehmer);
+/// assert_eq!(estimate.decimal_digits, 24_862_048);
+/// assert!(estimate.estimated_duration.as_secs() > 24 * 3600); // days, not hours
+/// ```
+pub fn estimate_resources(p: impl Into<Exponent>, level: CheckLevel) -> ResourceEstimate {
+    let p = p.into().get();
+    let decimal_digits = mersenne_digit_count(p);
+    if p < 2 || level < CheckLevel::PMinusOne {
+        return ResourceEstimate { decimal_digits, bytes_required: 0, estimated_duration: Duration::ZERO };
+    }
+
+    let seconds: f64 = pipeline_stages(level).map(|kind| stage_seconds(p, kind, level)).sum();
+    ResourceEstimate {
+        decimal_digits,
+        bytes_required: working_bytes(p),
+        estimated_duration: Duration::try_from_secs_f64(seconds).unwrap_or(Duration::MAX),
+    }
+}
+
+/// The stages a check up to `level` runs when none rules M_p out, in order
+fn pipeline_stages(level: CheckLevel) -> impl Iterator<Item = CheckKind> {
+    [
+        CheckKind::ExponentPrime,
+        CheckKind::TrialFactor,
+        CheckKind::PMinusOne,
+        CheckKind::MillerRabin,
+        CheckKind::LucasLehmer,
+    ]
+    .into_iter()
+    .filter(move |&kind| CheckLevel::from(kind) <= level)
+}
+
+/// Estimated seconds of one stage of a check at `level`, for `p >= 2`; see
+/// `estimate_resources` for where the costs come from
+fn stage_seconds(p: u64, kind: CheckKind, level: CheckLevel) -> f64 {
+    let iteration = || interpolate_cost(&LUCAS_LEHMER_ITERATION_SECONDS, p);
+    match kind {
+        CheckKind::PMinusOne => {
+            let b1 = default_p_minus_1_bound(p);
+            let mut iterations = P_MINUS_1_ITERATIONS_PER_B1 * b1 as f64;
+            // Full adds stage 2, with about x / ln x primes below x
+            if level == CheckLevel::Full {
+                let primes_below = |x: u64| x as f64 / (x as f64).ln();
+                let b2 = b1.saturating_mul(FULL_P_MINUS_1_B2_MULTIPLIER);
+                iterations += P_MINUS_1_STAGE_2_ITERATIONS_PER_PRIME * (primes_below(b2) - primes_below(b1));
+            }
+            iterations * iteration()
+        }
+        // The pipeline skips Miller-Rabin above this size
+        CheckKind::MillerRabin if p <= 332_000_000 => {
+            let rounds = if level == CheckLevel::Full { FULL_MILLER_RABIN_ROUNDS } else { DEFAULT_MILLER_RABIN_ROUNDS };
+            let waves = rounds.div_ceil(rayon::current_num_threads().max(1) as u32);
+            waves as f64 * interpolate_cost(&MILLER_RABIN_ROUND_SECONDS, p)
+        }
+        CheckKind::LucasLehmer => lucas_lehmer_iterations(p) as f64 * iteration(),
+        CheckKind::ExponentPrime | CheckKind::TrialFactor | CheckKind::MillerRabin => 0.0,
+    }
+}
+
+/// One stage of a `CheckPlan`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlannedStage {
+    /// The check this stage runs, as in `CheckResult::kind`
+    pub kind: CheckKind,
+    /// Rough time the stage takes; zero for the pre-screen and trial
+    /// factoring, and for Miller-Rabin where the pipeline skips it
+    pub estimated_duration: Duration,
+    /// Estimated memory for working values, in bytes, as in
+    /// `ResourceEstimate::bytes_required`; 0 for stages that work in `u64`s
+    pub bytes_required: u64,
+}
+
+/// What a check of M_p would do, from `plan_check`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CheckPlan {
+    /// The exponent p
+    pub exponent: u64,
+    /// The level the check would run to
+    pub level: CheckLevel,
+    /// Each stage that would run, in the order it would run
+    pub stages: Vec<PlannedStage>,
+    /// Time of all stages together, as from `estimate_resources`
+    pub estimated_duration: Duration,
+    /// The largest `bytes_required` of any stage
+    pub peak_memory_bytes: u64,
+}
+
+/// Plan a check of M_p up to `level` without running any of it
+///
+/// Lists the stages `check_mersenne_candidate` would run, in order, each with
+/// its share of `estimate_resources(p, level)`, so a scheduler can decide
+/// whether a job is worth dispatching. The plan assumes no stage rules M_p
+/// out; a real run stops at the first one that does, which for a composite
+/// or out-of-range `p` is the pre-screen. The estimates carry the same
+/// caveats as `estimate_resources`.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::{plan_check, CheckKind, CheckLevel};
+///
+/// let plan = plan_check(82_589_933, CheckLevel::Probabilistic);
+/// let kinds: Vec<CheckKind> = plan.stages.iter().map(|stage| stage.kind).collect();
+/// assert_eq!(kinds, [CheckKind::ExponentPrime, CheckKind::TrialFactor, CheckKind::PMinusOne, CheckKind::MillerRabin]);
+/// assert_eq!(plan.peak_memory_bytes, plan.stages[2].bytes_required);
+/// ```
+pub fn plan_check(p: impl Into<Exponent>, level: CheckLevel) -> CheckPlan {
+    let p = p.into().get();
+    let stages: Vec<PlannedStage> = pipeline_stages(level)
+        .map(|kind| {
+            let works_modulo_mp = p >= 2 && CheckLevel::from(kind) >= CheckLevel::PMinusOne;
+            let seconds = if works_modulo_mp { stage_seconds(p, kind, level) } else { 0.0 };
+            PlannedStage {
+                kind,
+                estimated_duration: Duration::try_from_secs_f64(seconds).unwrap_or(Duration::MAX),
+                bytes_required: if works_modulo_mp { working_bytes(p) } else { 0 },
+            }
+        })
+        .collect();
+    CheckPlan {
+        exponent: p,
+        level,
+        estimated_duration: estimate_resources(p, level).estimated_duration,
+        peak_memory_bytes: stages.iter().map(|stage| stage.bytes_required).max().unwrap_or(0),
+        stages,
+    }
+}
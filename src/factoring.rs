@@ -0,0 +1,584 @@
+/*!
+Factoring methods for finding factors of Mersenne numbers beyond the reach of
+trial division.
+
+Trial factoring and stage-1 P-1 (`pollard_p_minus_1`) run as part of the
+checking pipeline; the methods here are meant to be called directly,
+typically on a Mersenne number that is already known to be composite.
+Williams' p+1 costs about as much as P-1 and finds the factors q with q + 1
+smooth that P-1 misses; ECM and rho are heavier.
+*/
+
+use crate::{gcd, mod_mp_with_modulus, primes_up_to};
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use rand::{thread_rng, Rng};
+
+/// Reduction modulo a fixed n by Barrett's method
+///
+/// Precomputes μ = ⌊4^k / n⌋, where n has k bits, so that each reduction
+/// costs two multiplications and at most two subtractions instead of a long
+/// division. Build one per modulus and reuse it across a factoring run.
+///
+/// The multiplications are subquadratic but `BigUint` division is not, so
+/// this only beats `%` for large moduli: about 1.6 times faster at 16384
+/// bits and 4 times at 200000, but slower below roughly 8000 bits. The ECM
+/// and rho routines here switch to it at `BARRETT_MIN_BITS`.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::factoring::BarrettReducer;
+///
+/// let reducer = BarrettReducer::new(BigUint::from(1_000_003u32));
+/// let x = BigUint::from(123_456_789_012u64);
+/// assert_eq!(reducer.reduce(&x), &x % 1_000_003u32);
+/// ```
+#[derive(Debug, Clone)]
+pub struct BarrettReducer {
+    modulus: BigUint,
+    /// Bit length k of the modulus
+    bits: u64,
+    /// ⌊4^k / n⌋
+    mu: BigUint,
+}
+
+impl BarrettReducer {
+    /// Precompute the Barrett constant for `modulus`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `modulus` is zero.
+    pub fn new(modulus: BigUint) -> Self {
+        assert!(!modulus.is_zero(), "cannot reduce modulo zero");
+        let bits = modulus.bits();
+        let mu = (BigUint::one() << (2 * bits)) / &modulus;
+        Self { modulus, bits, mu }
+    }
+
+    /// The modulus this reducer was built for
+    pub fn modulus(&self) -> &BigUint {
+        &self.modulus
+    }
+
+    /// `x` mod n
+    ///
+    /// Any `x` is accepted, but only values below 4^k, which includes every
+    /// product of two reduced values, take the fast path; larger ones fall
+    /// back to `%`.
+    pub fn reduce(&self, x: &BigUint) -> BigUint {
+        if x.bits() > 2 * self.bits {
+            return x % &self.modulus;
+        }
+        // q underestimates x / n by at most 2
+        let q = ((x >> (self.bits - 1)) * &self.mu) >> (self.bits + 1);
+        let mut r = x - q * &self.modulus;
+        while r >= self.modulus {
+            r -= &self.modulus;
+        }
+        r
+    }
+}
+
+/// Smallest modulus, in bits, for which `Modulus` uses `BarrettReducer`
+/// rather than `%`
+pub const BARRETT_MIN_BITS: u64 = 8192;
+
+/// Arithmetic modulo n, using `mod_mp` when n is a Mersenne number,
+/// Barrett reduction for other large moduli, and `%` otherwise
+struct Modulus<'a> {
+    n: &'a BigUint,
+    reduction: Reduction,
+}
+
+enum Reduction {
+    /// n = 2^p - 1
+    Mersenne(u64),
+    Barrett(BarrettReducer),
+    Remainder,
+}
+
+impl<'a> Modulus<'a> {
+    fn new(n: &'a BigUint) -> Self {
+        let reduction = if (n + 1u32).count_ones() == 1 {
+            Reduction::Mersenne(n.bits())
+        } else if n.bits() >= BARRETT_MIN_BITS {
+            Reduction::Barrett(BarrettReducer::new(n.clone()))
+        } else {
+            Reduction::Remainder
+        };
+        Self { n, reduction }
+    }
+
+    fn reduce(&self, x: &BigUint) -> BigUint {
+        match &self.reduction {
+            Reduction::Mersenne(p) => mod_mp_with_modulus(x, self.n, *p),
+            Reduction::Barrett(reducer) => reducer.reduce(x),
+            Reduction::Remainder => x % self.n,
+        }
+    }
+
+    fn mul(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        self.reduce(&(a * b))
+    }
+
+    fn add(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        self.reduce(&(a + b))
+    }
+
+    /// a - b for a and b already reduced
+    fn sub(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        if a >= b {
+            a - b
+        } else {
+            self.n - b + a
+        }
+    }
+}
+
+/// A point on a Montgomery curve in projective x-only coordinates (X : Z)
+#[derive(Clone)]
+struct Point {
+    x: BigUint,
+    z: BigUint,
+}
+
+/// Double `p` on the curve with (A + 2) / 4 = `a24`
+fn double(m: &Modulus, p: &Point, a24: &BigUint) -> Point {
+    let sum = m.add(&p.x, &p.z);
+    let diff = m.sub(&p.x, &p.z);
+    let sum_sq = m.mul(&sum, &sum);
+    let diff_sq = m.mul(&diff, &diff);
+    let t = m.sub(&sum_sq, &diff_sq);
+    Point {
+        x: m.mul(&sum_sq, &diff_sq),
+        z: m.mul(&t, &m.add(&diff_sq, &m.mul(a24, &t))),
+    }
+}
+
+/// Add `p` and `q`, given their difference `d`
+fn add(m: &Modulus, p: &Point, q: &Point, d: &Point) -> Point {
+    let u = m.mul(&m.sub(&p.x, &p.z), &m.add(&q.x, &q.z));
+    let v = m.mul(&m.add(&p.x, &p.z), &m.sub(&q.x, &q.z));
+    let plus = m.add(&u, &v);
+    let minus = m.sub(&u, &v);
+    Point {
+        x: m.mul(&d.z, &m.mul(&plus, &plus)),
+        z: m.mul(&d.x, &m.mul(&minus, &minus)),
+    }
+}
+
+/// Multiply `p` by `k` with the Montgomery ladder
+fn multiply(m: &Modulus, p: &Point, k: u64, a24: &BigUint) -> Point {
+    if k == 1 {
+        return p.clone();
+    }
+    let mut low = p.clone();
+    let mut high = double(m, p, a24);
+    for bit in (0..63 - k.leading_zeros()).rev() {
+        if (k >> bit) & 1 == 1 {
+            low = add(m, &high, &low, p);
+            high = double(m, &high, a24);
+        } else {
+            high = add(m, &high, &low, p);
+            low = double(m, &low, a24);
+        }
+    }
+    low
+}
+
+/// A factor g of n with 1 < g < n, if `g` is one
+fn proper_factor(g: BigUint, n: &BigUint) -> Option<BigUint> {
+    (!g.is_one() && &g != n).then_some(g)
+}
+
+/// Look for a factor of `n` with stage-1 of the elliptic curve method
+///
+/// Runs up to `curves` random Montgomery curves (Suyama's parametrization),
+/// each multiplying its starting point by every prime power up to `b1`. A
+/// curve finds a factor q of `n` when its group order modulo q is
+/// `b1`-smooth, which unlike P-1 depends on the curve, so trying more curves
+/// keeps giving new chances. When `n` is a Mersenne number the reductions use
+/// `mod_mp`.
+///
+/// Roughly, factors of 10 digits need `b1` around 2,000 and a few dozen
+/// curves, and 20 digits need `b1` around 11,000 and a few hundred.
+///
+/// # Returns
+///
+/// * `Some(factor)` - a proper divisor of `n`, not necessarily prime
+/// * `None` - no curve found a factor
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use num_traits::{One, Zero};
+/// use primality_jones::factoring::ecm_factor;
+///
+/// // M67 = 193707721 * 761838257287
+/// let m67 = (BigUint::one() << 67u32) - BigUint::one();
+/// if let Some(factor) = ecm_factor(&m67, 3000, 200) {
+///     assert!((&m67 % &factor).is_zero());
+/// }
+/// ```
+pub fn ecm_factor(n: &BigUint, b1: u64, curves: u32) -> Option<BigUint> {
+    ecm_factor_with_rng(n, b1, curves, &mut thread_rng())
+}
+
+/// `ecm_factor` with the curves chosen by `rng`
+fn ecm_factor_with_rng(n: &BigUint, b1: u64, curves: u32, rng: &mut impl Rng) -> Option<BigUint> {
+    if n <= &BigUint::from(3u32) {
+        return None;
+    }
+    if !n.bit(0) {
+        return Some(BigUint::from(2u32));
+    }
+
+    let m = Modulus::new(n);
+    let primes = primes_up_to(b1);
+
+    for _ in 0..curves {
+        let sigma = m.reduce(&BigUint::from(rng.gen_range(6u64..u64::MAX)));
+        let u = m.sub(&m.mul(&sigma, &sigma), &m.reduce(&BigUint::from(5u32)));
+        let v = m.mul(&sigma, &m.reduce(&BigUint::from(4u32)));
+        let u3 = m.mul(&m.mul(&u, &u), &u);
+
+        // (A + 2) / 4 = (v - u)^3 (3u + v) / (16 u^3 v)
+        let v_minus_u = m.sub(&v, &u);
+        let numerator = m.mul(
+            &m.mul(&m.mul(&v_minus_u, &v_minus_u), &v_minus_u),
+            &m.add(&m.mul(&u, &BigUint::from(3u32)), &v),
+        );
+        let denominator = m.mul(&m.mul(&u3, &v), &BigUint::from(16u32));
+        let a24 = match denominator.modinv(n) {
+            Some(inverse) => m.mul(&numerator, &inverse),
+            None => match proper_factor(gcd(&denominator, n), n) {
+                Some(factor) => return Some(factor),
+                None => continue,
+            },
+        };
+
+        let mut point = Point { x: u3, z: m.mul(&m.mul(&v, &v), &v) };
+        for &q in &primes {
+            let mut power = q;
+            while power <= b1 / q {
+                power *= q;
+            }
+            point = multiply(&m, &point, power, &a24);
+        }
+
+        if point.z.is_zero() {
+            continue;
+        }
+        if let Some(factor) = proper_factor(gcd(&point.z, n), n) {
+            return Some(factor);
+        }
+    }
+    None
+}
+
+/// Starting values A tried by `williams_p_plus_1`
+///
+/// The discriminants A² - 4 have the squarefree parts 5, 3, 21, 2, 15 and
+/// 77, which between them involve 2, 3, 5, 7 and 11; all six are squares
+/// modulo only about one prime in 32, so almost every q is tried with some
+/// A for which (A² - 4 / q) = -1.
+const P_PLUS_1_SEEDS: [u32; 6] = [3, 4, 5, 6, 8, 9];
+
+/// The Lucas sequence value V_k(a) modulo n
+///
+/// V_0 = 2, V_1 = a and V_(j+1) = a V_j - V_(j-1), computed with the ladder
+/// V_2j = V_j² - 2, V_(2j+1) = V_j V_(j+1) - a. `a` must be reduced.
+fn lucas_v(m: &Modulus, a: &BigUint, k: u64) -> BigUint {
+    let two = m.reduce(&BigUint::from(2u32));
+    // (V_j, V_(j+1)), starting from j = 0
+    let mut low = two.clone();
+    let mut high = a.clone();
+    for bit in (0..u64::BITS - k.leading_zeros()).rev() {
+        let middle = m.sub(&m.mul(&low, &high), a);
+        if (k >> bit) & 1 == 1 {
+            high = m.sub(&m.mul(&high, &high), &two);
+            low = middle;
+        } else {
+            low = m.sub(&m.mul(&low, &low), &two);
+            high = middle;
+        }
+    }
+    low
+}
+
+/// Look for a factor of `n` with stage 1 of Williams' p+1 method
+///
+/// The counterpart of P-1 for factors q where q + 1, rather than q - 1, is
+/// `b1`-smooth. Starting from V = A, V is replaced by the Lucas sequence
+/// value V_e(V) for the largest power e of each prime up to `b1`, and
+/// gcd(V - 2, n) is taken at the end. That finds q when q - (D / q) is
+/// `b1`-smooth, where D = A² - 4 and (D / q) is the Jacobi symbol: p+1 if it
+/// is -1 and P-1 again if it is +1. Neither can be known without q, so a few
+/// starting values with different D are tried in turn. When `n` is a
+/// Mersenne number the reductions use `mod_mp`.
+///
+/// Each starting value costs about as much as P-1 with the same `b1`.
+///
+/// # Returns
+///
+/// * `Some(factor)` - a proper divisor of `n`, not necessarily prime
+/// * `None` - no starting value found a factor
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use num_traits::One;
+/// use primality_jones::factoring::williams_p_plus_1;
+///
+/// // M71 = 228479 * 48544121 * 212885833, where 48544122 = 2 * 3 * 11 * 23 * 113 * 283
+/// // but 48544120 = 2^3 * 5 * 71 * 17093, out of reach of P-1
+/// let m71 = (BigUint::one() << 71u32) - BigUint::one();
+/// assert_eq!(williams_p_plus_1(&m71, 300), Some(BigUint::from(48544121u32)));
+/// ```
+pub fn williams_p_plus_1(n: &BigUint, b1: u64) -> Option<BigUint> {
+    if n <= &BigUint::from(3u32) {
+        return None;
+    }
+    if !n.bit(0) {
+        return Some(BigUint::from(2u32));
+    }
+
+    let m = Modulus::new(n);
+    let primes = primes_up_to(b1);
+    let two = m.reduce(&BigUint::from(2u32));
+
+    for seed in P_PLUS_1_SEEDS {
+        let mut v = m.reduce(&BigUint::from(seed));
+        for &q in &primes {
+            let mut power = q;
+            while power <= b1 / q {
+                power *= q;
+            }
+            v = lucas_v(&m, &v, power);
+        }
+        if let Some(factor) = proper_factor(gcd(&m.sub(&v, &two), n), n) {
+            return Some(factor);
+        }
+    }
+    None
+}
+
+/// Iteration cap used by `pollard_rho`
+pub const DEFAULT_RHO_ITERATIONS: u64 = 1 << 20;
+
+/// Steps between gcd computations in Brent's variant of rho
+const RHO_GCD_BATCH: u64 = 128;
+
+/// Look for a factor of `n` with Pollard's rho method
+///
+/// Uses Brent's cycle detection on x -> x^2 + c, trying c = 1, 2, 3, ... in
+/// turn whenever a cycle closes without splitting `n`, and stops after
+/// `DEFAULT_RHO_ITERATIONS` steps in total. A factor q is typically found
+/// after about sqrt(q) steps, so the default cap reaches factors of around
+/// 12 digits. See `pollard_rho_with_limit` to choose the cap. When `n` is a
+/// Mersenne number the reductions use `mod_mp`.
+///
+/// # Returns
+///
+/// * `Some(factor)` - a proper divisor of `n`, not necessarily prime
+/// * `None` - the iteration cap was reached without finding one, which is
+///   also the answer for a prime `n`
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use num_traits::One;
+/// use primality_jones::factoring::pollard_rho;
+///
+/// // M23 = 47 * 178481
+/// let m23 = (BigUint::one() << 23u32) - BigUint::one();
+/// assert_eq!(pollard_rho(&m23), Some(BigUint::from(47u32)));
+/// ```
+pub fn pollard_rho(n: &BigUint) -> Option<BigUint> {
+    pollard_rho_with_limit(n, DEFAULT_RHO_ITERATIONS)
+}
+
+/// `pollard_rho` that gives up after `max_iterations` steps
+pub fn pollard_rho_with_limit(n: &BigUint, max_iterations: u64) -> Option<BigUint> {
+    if n <= &BigUint::from(3u32) {
+        return None;
+    }
+    if !n.bit(0) {
+        return Some(BigUint::from(2u32));
+    }
+
+    let m = Modulus::new(n);
+    let mut iterations = 0;
+    let mut c = 0u32;
+    while iterations < max_iterations {
+        c += 1;
+        let c_big = m.reduce(&BigUint::from(c));
+        let step = |x: &BigUint| m.add(&m.mul(x, x), &c_big);
+
+        let mut y = BigUint::from(2u32);
+        let mut x = y.clone();
+        let mut saved = y.clone();
+        let mut product = BigUint::one();
+        let mut g = BigUint::one();
+        let mut r = 1;
+        let mut exhausted = false;
+        while g.is_one() {
+            x = y.clone();
+            let advance = r.min(max_iterations - iterations);
+            for _ in 0..advance {
+                y = step(&y);
+            }
+            iterations += advance;
+            let mut k = 0;
+            while k < r && g.is_one() && iterations < max_iterations {
+                saved = y.clone();
+                let batch = RHO_GCD_BATCH.min(r - k).min(max_iterations - iterations);
+                for _ in 0..batch {
+                    y = step(&y);
+                    product = m.mul(&product, &m.sub(&x, &y));
+                }
+                iterations += batch;
+                g = gcd(&product, n);
+                k += RHO_GCD_BATCH;
+            }
+            r *= 2;
+            if g.is_one() && iterations >= max_iterations {
+                exhausted = true;
+                break;
+            }
+        }
+        if exhausted {
+            break;
+        }
+
+        // The batch overshot the collision; replay it one step at a time
+        if &g == n {
+            loop {
+                saved = step(&saved);
+                iterations += 1;
+                g = gcd(&m.sub(&x, &saved), n);
+                if !g.is_one() {
+                    break;
+                }
+            }
+        }
+        if let Some(factor) = proper_factor(g, n) {
+            return Some(factor);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::RandBigInt;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_barrett_matches_remainder() {
+        let mut rng = StdRng::seed_from_u64(1779);
+        let mut moduli: Vec<BigUint> = [1u64, 2, 3, 10, 1 << 32, (1 << 32) + 1, 1_000_003, u64::MAX]
+            .iter()
+            .map(|&n| BigUint::from(n))
+            .collect();
+        moduli.push(BigUint::one() << 200u32);
+        moduli.push((BigUint::one() << 200u32) + 1u32);
+        moduli.extend((0..5).map(|_| rng.gen_biguint(317) | BigUint::one()));
+
+        for n in moduli {
+            let reducer = BarrettReducer::new(n.clone());
+            let mut values = vec![BigUint::zero(), n.clone(), &n - 1u32, &n * &n - 1u32, (&n - 1u32) * (&n - 1u32)];
+            values.extend((0..50).map(|_| rng.gen_biguint_below(&(&n * &n))));
+            // Beyond 4^k, the fallback path
+            values.push(rng.gen_biguint(3 * n.bits() + 10));
+            for x in values {
+                assert_eq!(reducer.reduce(&x), &x % &n, "{} mod {}", x, n);
+            }
+        }
+    }
+
+    #[test]
+    fn test_ecm_factors_m67() {
+        let m67 = (BigUint::one() << 67u32) - BigUint::one();
+        let factors = [BigUint::from(193707721u32), BigUint::from(761838257287u64)];
+        let mut rng = StdRng::seed_from_u64(67);
+        let factor = ecm_factor_with_rng(&m67, 3000, 200, &mut rng).expect("ECM should split M67");
+        assert!(factors.contains(&factor), "{} is not a prime factor of M67", factor);
+    }
+
+    #[test]
+    fn test_ecm_general_modulus() {
+        // 1000003 * 1000033, not a Mersenne number
+        let n = BigUint::from(1000003u64 * 1000033);
+        let mut rng = StdRng::seed_from_u64(1);
+        let factor = ecm_factor_with_rng(&n, 500, 100, &mut rng).unwrap();
+        assert!(factor == BigUint::from(1000003u32) || factor == BigUint::from(1000033u32));
+
+        assert_eq!(ecm_factor(&BigUint::from(1000003u32 * 2), 100, 1), Some(BigUint::from(2u32)));
+        assert_eq!(ecm_factor(&BigUint::from(127u32), 100, 10), None);
+    }
+
+    #[test]
+    fn test_lucas_v() {
+        let n = BigUint::from(1_000_003u32);
+        let m = Modulus::new(&n);
+        let a = BigUint::from(7u32);
+        let (mut previous, mut current) = (BigUint::from(2u32), a.clone());
+        for k in 1..200u64 {
+            assert_eq!(lucas_v(&m, &a, k), current, "V_{}", k);
+            let next = (&a * &current + &n - &previous) % &n;
+            (previous, current) = (current, next);
+        }
+        assert_eq!(lucas_v(&m, &a, 0), BigUint::from(2u32));
+    }
+
+    #[test]
+    fn test_williams_p_plus_1() {
+        // 2472521855491 + 1 = 2^2 * 13 * 31 * 53 * 61 * 67 * 73 * 97, while
+        // 2472521855491 - 1 has the prime factor 16480183 and 1000000000039 ± 1
+        // have factors above 10^4, so only p+1 can split this with B1 = 100
+        let q = BigUint::from(2_472_521_855_491u64);
+        let n = &q * BigUint::from(1_000_000_000_039u64);
+        assert_eq!(williams_p_plus_1(&n, 100), Some(q));
+        assert_eq!(williams_p_plus_1(&n, 90), None);
+
+        // M43 = 431 * 9719 * 2099863: 432 and 9720 are both 400-smooth, so the
+        // gcd collects both factors at once
+        let m43 = (BigUint::one() << 43u32) - BigUint::one();
+        assert_eq!(williams_p_plus_1(&m43, 400), Some(BigUint::from(431u32 * 9719)));
+        let m71 = (BigUint::one() << 71u32) - BigUint::one();
+        assert_eq!(williams_p_plus_1(&m71, 300), Some(BigUint::from(48544121u32)));
+        assert_eq!(williams_p_plus_1(&m71, 120), None);
+
+        assert_eq!(williams_p_plus_1(&BigUint::from(2147483647u32), 1000), None);
+        assert_eq!(williams_p_plus_1(&BigUint::from(1000003u32 * 2), 100), Some(BigUint::from(2u32)));
+        assert_eq!(williams_p_plus_1(&BigUint::from(3u32), 100), None);
+    }
+
+    #[test]
+    fn test_pollard_rho() {
+        let m23 = (BigUint::one() << 23u32) - BigUint::one();
+        let factor = pollard_rho(&m23).unwrap();
+        assert!(factor == BigUint::from(47u32) || factor == BigUint::from(178481u32));
+
+        // M29 = 233 * 1103 * 2089: any proper divisor will do
+        let m29 = (BigUint::one() << 29u32) - BigUint::one();
+        let factor = pollard_rho(&m29).unwrap();
+        assert!((&m29 % &factor).is_zero() && factor > BigUint::one() && factor < m29);
+
+        let n = BigUint::from(1000003u64 * 1000033);
+        let factor = pollard_rho(&n).unwrap();
+        assert!(factor == BigUint::from(1000003u32) || factor == BigUint::from(1000033u32));
+
+        // Primes never split, and the cap is respected
+        assert_eq!(pollard_rho_with_limit(&BigUint::from(2147483647u32), 10_000), None);
+        assert_eq!(pollard_rho_with_limit(&n, 10), None);
+    }
+}
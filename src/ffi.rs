@@ -0,0 +1,189 @@
+/*!
+C-compatible entry points for calling the core tests without Rust or Python.
+
+Every function takes and returns plain integers, `bool`s or caller-owned
+buffers, so nothing allocated here ever has to be freed by the caller and no
+strings cross the boundary. The matching C declarations live in
+`include/primality_jones.h`, generated with
+`cbindgen --config cbindgen.toml --output include/primality_jones.h`.
+
+# ABI stability
+
+The functions and structs here follow semver together with the rest of the
+crate: within a release series (0.2.x) the `pj_` symbols keep their
+signatures, `PjCheckResult` keeps its layout, and `PJ_KIND_*` and
+`PJ_LEVEL_*` keep their values. New functions and new constants may be added
+in any release. Anything else in the crate has no stable ABI.
+
+A panic inside one of these functions aborts the process rather than
+unwinding into C.
+
+This module is only compiled with the `ffi` feature.
+*/
+
+use crate::{
+    check_mersenne_candidate, check_small_factors, is_prime, lucas_lehmer_test, CheckKind, CheckLevel,
+};
+
+/// Stage kind of a `PjCheckResult`: the exponent pre-screen
+pub const PJ_KIND_EXPONENT_PRIME: u32 = 0;
+/// Stage kind of a `PjCheckResult`: trial factoring
+pub const PJ_KIND_TRIAL_FACTOR: u32 = 1;
+/// Stage kind of a `PjCheckResult`: Miller-Rabin
+pub const PJ_KIND_MILLER_RABIN: u32 = 2;
+/// Stage kind of a `PjCheckResult`: Lucas-Lehmer
+pub const PJ_KIND_LUCAS_LEHMER: u32 = 3;
+/// Stage kind of a `PjCheckResult`: stage-1 P-1 factoring
+pub const PJ_KIND_P_MINUS_ONE: u32 = 4;
+
+/// Check level for `pj_check`: pre-screen only
+pub const PJ_LEVEL_PRE_SCREEN: u32 = 0;
+/// Check level for `pj_check`: up to trial factoring
+pub const PJ_LEVEL_TRIAL_FACTORING: u32 = 1;
+/// Check level for `pj_check`: up to P-1 factoring
+pub const PJ_LEVEL_P_MINUS_ONE: u32 = 2;
+/// Check level for `pj_check`: up to Miller-Rabin
+pub const PJ_LEVEL_PROBABILISTIC: u32 = 3;
+/// Check level for `pj_check`: every stage, ending with Lucas-Lehmer
+pub const PJ_LEVEL_LUCAS_LEHMER: u32 = 4;
+/// Check level for `pj_check`: every stage at greater depth, as `CheckLevel::Full`
+pub const PJ_LEVEL_FULL: u32 = 5;
+
+/// One stage of a `pj_check` run, the C view of `CheckResult`
+///
+/// The message is left out so that no strings need freeing.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PjCheckResult {
+    /// Which stage this is, one of the `PJ_KIND_*` constants
+    pub kind: u32,
+    /// Whether the stage passed
+    pub passed: bool,
+    /// The stage could not reach a verdict (for example it timed out)
+    pub inconclusive: bool,
+    /// Whether the stage found a factor of M_p
+    pub has_factor: bool,
+    /// The factor, or 0 when there is none or it does not fit in 64 bits
+    pub factor: u64,
+    /// How long the stage took, in nanoseconds
+    pub time_ns: u64,
+}
+
+/// Whether M_p is prime, by the Lucas-Lehmer test (false for p < 2)
+#[no_mangle]
+pub extern "C" fn pj_lucas_lehmer_test(p: u64) -> bool {
+    lucas_lehmer_test(p)
+}
+
+/// Whether `n` is prime
+#[no_mangle]
+pub extern "C" fn pj_is_prime(n: u64) -> bool {
+    is_prime(n)
+}
+
+/// A factor of M_p no greater than `limit`, or 0 if trial factoring finds none
+/// (always 0 for `limit` < 3)
+#[no_mangle]
+pub extern "C" fn pj_check_small_factors(p: u64, limit: u64) -> u64 {
+    // A panic cannot unwind into C, so keep every edge case on this side
+    if limit < 3 {
+        return 0;
+    }
+    check_small_factors(p, limit).unwrap_or(0)
+}
+
+/// Run `check_mersenne_candidate` on M_p and copy the stages into `out`
+///
+/// `level` is one of the `PJ_LEVEL_*` constants. At most `capacity` results
+/// are written to `out`, which may be null when `capacity` is 0. Returns the
+/// number of stages the check produced, which can exceed `capacity`; a buffer
+/// of 8 results is always enough. Returns 0, writing nothing, if `level` is
+/// not a valid level.
+///
+/// # Safety
+///
+/// Unless `capacity` is 0, `out` must point to `capacity` writable
+/// `PjCheckResult`s.
+#[no_mangle]
+pub unsafe extern "C" fn pj_check(p: u64, level: u32, out: *mut PjCheckResult, capacity: usize) -> usize {
+    let level = match level {
+        PJ_LEVEL_PRE_SCREEN => CheckLevel::PreScreen,
+        PJ_LEVEL_TRIAL_FACTORING => CheckLevel::TrialFactoring,
+        PJ_LEVEL_P_MINUS_ONE => CheckLevel::PMinusOne,
+        PJ_LEVEL_PROBABILISTIC => CheckLevel::Probabilistic,
+        PJ_LEVEL_LUCAS_LEHMER => CheckLevel::LucasLehmer,
+        PJ_LEVEL_FULL => CheckLevel::Full,
+        _ => return 0,
+    };
+
+    let results = check_mersenne_candidate(p, level);
+    for (i, result) in results.iter().take(capacity).enumerate() {
+        let stage = PjCheckResult {
+            kind: match result.kind {
+                CheckKind::ExponentPrime => PJ_KIND_EXPONENT_PRIME,
+                CheckKind::TrialFactor => PJ_KIND_TRIAL_FACTOR,
+                CheckKind::MillerRabin => PJ_KIND_MILLER_RABIN,
+                CheckKind::LucasLehmer => PJ_KIND_LUCAS_LEHMER,
+                CheckKind::PMinusOne => PJ_KIND_P_MINUS_ONE,
+            },
+            passed: result.passed,
+            inconclusive: result.inconclusive,
+            has_factor: result.factor.is_some(),
+            factor: result.factor.as_ref().and_then(|f| u64::try_from(f).ok()).unwrap_or(0),
+            time_ns: u64::try_from(result.time_taken.as_nanos()).unwrap_or(u64::MAX),
+        };
+        // SAFETY: i < capacity, and the caller guarantees `out` has room for
+        // `capacity` results
+        unsafe { out.add(i).write(stage) };
+    }
+    results.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scalar_functions() {
+        assert!(pj_lucas_lehmer_test(127));
+        assert!(!pj_lucas_lehmer_test(11));
+        assert!(pj_is_prime(127));
+        assert!(!pj_is_prime(1));
+        assert_eq!(pj_check_small_factors(11, 1000), 23);
+        assert_eq!(pj_check_small_factors(127, 1000), 0);
+        assert_eq!(pj_check_small_factors(11, 0), 0);
+        assert_eq!(pj_check_small_factors(11, 2), 0);
+    }
+
+    #[test]
+    fn test_pj_check() {
+        let mut out = [PjCheckResult::default(); 8];
+
+        let n = unsafe { pj_check(31, PJ_LEVEL_LUCAS_LEHMER, out.as_mut_ptr(), out.len()) };
+        assert!(n > 1 && n <= out.len());
+        assert!(out[..n].iter().all(|r| r.passed && !r.has_factor));
+        assert_eq!(out[n - 1].kind, PJ_KIND_LUCAS_LEHMER);
+
+        let n = unsafe { pj_check(11, PJ_LEVEL_TRIAL_FACTORING, out.as_mut_ptr(), out.len()) };
+        let last = out[n - 1];
+        assert_eq!(last.kind, PJ_KIND_TRIAL_FACTOR);
+        assert!(!last.passed && last.has_factor);
+        assert_eq!(last.factor, 23);
+
+        // A short buffer still reports how many stages there were
+        let mut one = [PjCheckResult::default(); 1];
+        assert_eq!(unsafe { pj_check(31, PJ_LEVEL_LUCAS_LEHMER, one.as_mut_ptr(), 1) }, n_stages(31));
+        assert_eq!(one[0].kind, PJ_KIND_EXPONENT_PRIME);
+        assert!(unsafe { pj_check(31, PJ_LEVEL_LUCAS_LEHMER, std::ptr::null_mut(), 0) } > 1);
+
+        let n = unsafe { pj_check(31, PJ_LEVEL_FULL, out.as_mut_ptr(), out.len()) };
+        assert_eq!(n, n_stages(31));
+        assert!(out[..n].iter().all(|r| r.passed));
+
+        assert_eq!(unsafe { pj_check(31, 99, out.as_mut_ptr(), out.len()) }, 0);
+    }
+
+    fn n_stages(p: u64) -> usize {
+        check_mersenne_candidate(p, CheckLevel::LucasLehmer).len()
+    }
+}
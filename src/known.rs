@@ -0,0 +1,154 @@
+/*!
+Exponent lists read from files, and the self-check of the test against the
+known Mersenne primes.
+*/
+
+use crate::{known_composite_exponents, known_mersenne_primes, lucas_lehmer_test, primes_up_to, Instant};
+use rayon::prelude::*;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// Read a list of Mersenne exponents from a JSON file
+///
+/// The file holds a single JSON array of non-negative integers, the format of
+/// `test_data/known_mersenne_primes.json` and
+/// `test_data/known_composite_mersenne.json`. Use it to test against a list
+/// newer than the one built into the crate.
+///
+/// # Errors
+///
+/// Fails if the file cannot be read or is not such an array.
+///
+/// # Examples
+///
+/// ```no_run
+/// use primality_jones::{known_mersenne_primes, load_known_primes};
+///
+/// let primes = load_known_primes("test_data/known_mersenne_primes.json")?;
+/// assert!(primes.starts_with(known_mersenne_primes()));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn load_known_primes<P: AsRef<Path>>(path: P) -> io::Result<Vec<u64>> {
+    parse_exponent_list(&fs::read_to_string(path)?)
+}
+
+/// Parse a JSON array of exponents, the format `load_known_primes` reads
+pub(crate) fn parse_exponent_list(content: &str) -> io::Result<Vec<u64>> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+    let list = content
+        .trim()
+        .strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .ok_or_else(|| invalid("expected a JSON array of exponents".to_string()))?;
+    if list.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    list.split(',')
+        .map(|entry| {
+            let entry = entry.trim();
+            entry.parse::<u64>().map_err(|_| invalid(format!("'{entry}' is not an exponent")))
+        })
+        .collect()
+}
+
+/// Largest exponent `verify_against_known` tests, under a minute's work in a
+/// release build
+pub const MAX_VERIFICATION_EXPONENT: u64 = 44_497;
+
+/// How many composites above `known_composite_exponents` `verify_against_known`
+/// samples
+pub(crate) const VERIFICATION_COMPOSITE_SAMPLES: usize = 8;
+
+/// A known result that `lucas_lehmer_test` got wrong
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VerificationMismatch {
+    /// The exponent p of M_p
+    pub exponent: u64,
+    /// Whether M_p is known to be prime
+    pub expected_prime: bool,
+    /// What `lucas_lehmer_test` said
+    pub actual_prime: bool,
+}
+
+/// Outcome of `verify_against_known`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VerificationReport {
+    /// The largest exponent tested for, after capping at `MAX_VERIFICATION_EXPONENT`
+    pub max_exponent: u64,
+    /// Number of known Mersenne primes tested
+    pub primes_checked: usize,
+    /// Number of composite Mersenne numbers tested
+    pub composites_checked: usize,
+    /// Every exponent where the test disagreed with the known answer, in
+    /// ascending order
+    pub mismatches: Vec<VerificationMismatch>,
+    /// How long the run took
+    pub elapsed: Duration,
+}
+
+impl VerificationReport {
+    /// Whether every known result was reproduced
+    pub fn passed(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// Check `lucas_lehmer_test` against the known results up to `max_exponent`
+///
+/// Tests every known Mersenne prime with an exponent up to `max_exponent`,
+/// every entry of `known_composite_exponents` up to it and, beyond those, a
+/// sample of evenly spaced prime exponents whose Mersenne numbers are
+/// composite. The exponents run in parallel. This is an audit of the build
+/// and the machine it runs on: on a correct build the report always passes.
+///
+/// `max_exponent` is capped at `MAX_VERIFICATION_EXPONENT`, so a typo cannot
+/// start a run of hours; the report gives the bound actually used.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::verify_against_known;
+///
+/// let report = verify_against_known(1000);
+/// assert!(report.passed(), "{:?}", report.mismatches);
+/// assert_eq!(report.primes_checked, 14);
+/// ```
+pub fn verify_against_known(max_exponent: u64) -> VerificationReport {
+    let start = Instant::now();
+    let max_exponent = max_exponent.min(MAX_VERIFICATION_EXPONENT);
+
+    let primes: Vec<u64> = known_mersenne_primes().iter().copied().take_while(|&p| p <= max_exponent).collect();
+    let mut composites: Vec<u64> =
+        known_composite_exponents().iter().copied().take_while(|&p| p <= max_exponent).collect();
+    let listed_up_to = known_composite_exponents().last().copied().unwrap_or(0);
+    let unlisted: Vec<u64> = primes_up_to(max_exponent)
+        .into_iter()
+        .filter(|&p| p > listed_up_to && !known_mersenne_primes().contains(&p))
+        .collect();
+    let step = unlisted.len().div_ceil(VERIFICATION_COMPOSITE_SAMPLES).max(1);
+    composites.extend(unlisted.iter().rev().step_by(step));
+
+    let expected = primes.iter().map(|&p| (p, true)).chain(composites.iter().map(|&p| (p, false)));
+    let mut mismatches: Vec<VerificationMismatch> = expected
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .filter_map(|(exponent, expected_prime)| {
+            let actual_prime = lucas_lehmer_test(exponent);
+            (actual_prime != expected_prime).then_some(VerificationMismatch { exponent, expected_prime, actual_prime })
+        })
+        .collect();
+    mismatches.sort_by_key(|m| m.exponent);
+
+    VerificationReport {
+        max_exponent,
+        primes_checked: primes.len(),
+        composites_checked: composites.len(),
+        mismatches,
+        elapsed: start.elapsed(),
+    }
+}
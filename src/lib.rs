@@ -8,6 +8,7 @@ checks to thorough verification.
 # Example
 
 ```rust
+# #[cfg(feature = "std")] {
 use primality_jones::{CheckLevel, check_mersenne_candidate};
 
 let p = 12301; // Test M12301
@@ -18,6 +19,7 @@ if results.iter().all(|r| r.passed) {
 } else {
     println!("M{} is not prime.", p);
 }
+# }
 ```
 
 # Safety and Performance
@@ -30,697 +32,358 @@ For large Mersenne numbers (>100M digits), consider using the GIMPS software
 for definitive primality testing.
 */
 
-use indicatif::{ProgressBar, ProgressStyle};
-use num_bigint::{BigUint, RandBigInt};
-use num_traits::{One, Zero};
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use num_bigint::BigUint;
+use num_traits::One;
+#[cfg(not(feature = "std"))]
+use num_traits::Zero;
 #[cfg(feature = "pyo3")]
 use pyo3::prelude::*;
 #[cfg(feature = "pyo3")]
 use pyo3::types::PyDict;
-use rand::thread_rng;
-use rayon::prelude::*;
-use std::time::{Duration, Instant};
-
-/// Type of primality check performed
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum CheckKind {
-    /// Pre-screen: Check if the exponent p itself is prime
-    ExponentPrime,
-    /// Trial factoring: Check for small factors using special properties
-    TrialFactor,
-    /// Probabilistic: Miller-Rabin test
-    MillerRabin,
-    /// Lucas-Lehmer: The definitive test for Mersenne primes
-    LucasLehmer,
-}
-
-/// Represents the result of a primality check
-#[derive(Debug, Clone)]
-pub struct CheckResult {
-    /// Whether the check passed
-    pub passed: bool,
-    /// Description of the check result
-    pub message: String,
-    /// How long the check took
-    pub time_taken: Duration,
-    /// Type of check that was performed
-    pub kind: CheckKind,
-}
-
-/// Different levels of thoroughness for primality checking
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-pub enum CheckLevel {
-    /// Pre-screen: Check if the exponent p itself is prime
-    PreScreen,
-    /// Trial factoring: Check for small factors using special properties
-    TrialFactoring,
-    /// Probabilistic: Miller-Rabin test (replaces Fermat test)
-    Probabilistic,
-    /// Lucas-Lehmer: The definitive test for Mersenne primes
-    LucasLehmer,
-}
-
-impl From<CheckKind> for CheckLevel {
-    fn from(kind: CheckKind) -> Self {
-        match kind {
-            CheckKind::ExponentPrime => CheckLevel::PreScreen,
-            CheckKind::TrialFactor => CheckLevel::TrialFactoring,
-            CheckKind::MillerRabin => CheckLevel::Probabilistic,
-            CheckKind::LucasLehmer => CheckLevel::LucasLehmer,
-        }
-    }
-}
-
-impl CheckLevel {
-    /// Get a human-readable description of the check level
-    pub fn description(&self) -> String {
-        match self {
-            CheckLevel::PreScreen => "Pre-screen: Check if exponent is prime (instant)".to_string(),
-            CheckLevel::TrialFactoring => {
-                "Trial factoring: Check for small factors (~1 second)".to_string()
-            }
-            CheckLevel::Probabilistic => {
-                "Probabilistic: Miller-Rabin test (seconds to minutes)".to_string()
-            }
-            CheckLevel::LucasLehmer => {
-                "Lucas-Lehmer: Definitive test (minutes to hours)".to_string()
-            }
-        }
-    }
-}
-
-/// Check if a number is prime using trial division or Miller-Rabin for larger values
-///
-/// # Arguments
-///
-/// * `n` - The number to test for primality
-///
-/// # Returns
+#[cfg(feature = "pyo3")]
+use std::sync::Mutex;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+use std::time::Instant;
+// std's Instant panics in the browser
+#[cfg(target_arch = "wasm32")]
+pub use web_time::Instant;
+
+mod mersenne;
+pub use mersenne::{
+    add_mod_mp, mod_mp, mod_mp_assign, mod_mp_limbs, mod_mp_single_pass, mod_mp_with_modulus, mul_mod_mp, pow_mod_mp,
+    residues_equal_mod_mp, square_and_subtract_two_mod_mp, square_into, sub_mod_mp, ModMpScratch,
+    SQUARE_INTO_MAX_LIMBS,
+};
+
+// Everything outside this file and `mersenne` needs std. Each of those modules
+// is gated once here and re-exported, so the public API stays flat.
+#[cfg(feature = "std")]
+mod batch;
+#[cfg(feature = "std")]
+mod config;
+#[cfg(feature = "std")]
+mod error;
+#[cfg(feature = "std")]
+mod estimate;
+#[cfg(feature = "std")]
+mod known;
+#[cfg(feature = "std")]
+mod lucas_lehmer;
+#[cfg(feature = "std")]
+mod pipeline;
+#[cfg(feature = "std")]
+mod primes;
+#[cfg(feature = "std")]
+mod probable;
+#[cfg(feature = "std")]
+mod small_factors;
+#[cfg(feature = "std")]
+mod special;
+#[cfg(feature = "std")]
+pub use batch::*;
+#[cfg(feature = "std")]
+pub use config::*;
+#[cfg(feature = "std")]
+pub use error::*;
+#[cfg(feature = "std")]
+pub use estimate::*;
+#[cfg(feature = "std")]
+pub use known::*;
+#[cfg(feature = "std")]
+pub use lucas_lehmer::*;
+#[cfg(feature = "std")]
+pub use pipeline::*;
+#[cfg(feature = "std")]
+pub use primes::*;
+#[cfg(feature = "std")]
+pub use probable::*;
+#[cfg(feature = "std")]
+pub use small_factors::*;
+#[cfg(feature = "std")]
+pub use special::*;
+
+#[cfg(feature = "std")]
+pub mod factoring;
+#[cfg(feature = "fft")]
+pub mod dwt;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "primenet")]
+pub mod primenet;
+
+/// Exponents of all known Mersenne primes, in ascending order
 ///
-/// * `true` if the number is prime
-/// * `false` if the number is composite or less than 2
+/// Taken from GIMPS; the list ends with M136279841, found in October 2024.
+/// It is complete only up to the exponents GIMPS has double-checked, so a
+/// prime could still turn up between the larger entries.
+pub const KNOWN_MERSENNE_PRIME_EXPONENTS: [u64; 52] = [
+    2, 3, 5, 7, 13, 17, 19, 31, 61, 89, 107, 127, 521, 607, 1279, 2203, 2281, 3217,
+    4253, 4423, 9689, 9941, 11213, 19937, 21701, 23209, 44497, 86243, 110503, 132049,
+    216091, 756839, 859433, 1257787, 1398269, 2976221, 3021377, 6972593, 13466917,
+    20996011, 24036583, 25964951, 30402457, 32582657, 37156667, 42643801, 43112609,
+    57885161, 74207281, 77232917, 82589933, 136279841,
+];
+
+/// Number of known Mersenne primes
+pub const KNOWN_MERSENNE_PRIME_COUNT: usize = KNOWN_MERSENNE_PRIME_EXPONENTS.len();
+
+/// Exponent of the largest known Mersenne prime
 ///
 /// # Examples
 ///
 /// ```
-/// use primality_jones::is_prime;
+/// use primality_jones::{largest_known_mersenne_exponent, KNOWN_MERSENNE_PRIME_COUNT};
 ///
-/// assert!(is_prime(31));
-/// assert!(!is_prime(15));
+/// println!(
+///     "{} Mersenne primes are known, the largest is M{}",
+///     KNOWN_MERSENNE_PRIME_COUNT,
+///     largest_known_mersenne_exponent()
+/// );
 /// ```
-pub fn is_prime(n: u64) -> bool {
-    if n <= 1 {
-        return false;
-    }
-    if n <= 3 {
-        return true;
-    }
-    if n % 2 == 0 || n % 3 == 0 {
-        return false;
-    }
-    
-    // For larger numbers, use Miller-Rabin test which is much faster
-if n > 1_000_000 {
-        // Use deterministic Miller-Rabin with known witnesses for u64 range
-        let witnesses: &[u64] = if n < 2_047 {
-            &[2]
-        } else if n < 1_373_653 {
-            &[2, 3]
-        } else if n < 9_080_191 {
-            &[31, 73]
-        } else if n < 25_326_001 {
-            &[2, 3, 5]
-        } else if n < 3_215_031_751 {
-            &[2, 3, 5, 7]
-        } else if n < 4_759_123_141 {
-            &[2, 7, 61]
-        } else if n < 1_122_004_669_633 {
-            &[2, 13, 23, 1662803]
-        } else if n < 2_152_302_898_747 {
-            &[2, 3, 5, 7, 11]
-        } else if n < 3_474_749_660_383 {
-            &[2, 3, 5, 7, 11, 13]
-        } else if n < 341_550_071_728_321 {
-            &[2, 3, 5, 7, 11, 13, 17]
-        } else {
-            &[2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37]
-        };
-        return miller_rabin_u64(n, witnesses);
-    }
-    
-    // Use trial division for smaller numbers
-    let sqrt_n = (n as f64).sqrt() as u64;
-    let mut i = 5;
-    while i <= sqrt_n {
-        if n % i == 0 || n % (i + 2) == 0 {
-            return false;
-        }
-        i += 6;
-    }
-    true
-}
-
-/// Fast Miller-Rabin primality test for u64 numbers
-/// This is much faster than trial division for large numbers
-fn miller_rabin_u64(n: u64, witnesses: &[u64]) -> bool {
-    if n < 2 {
-        return false;
-    }
-    if n == 2 || n == 3 {
-        return true;
-    }
-    if n % 2 == 0 {
-        return false;
-    }
-
-    // Write n-1 as d * 2^r
-    let mut d = n - 1;
-    let mut r = 0;
-    while d % 2 == 0 {
-        d /= 2;
-        r += 1;
-    }
-
-    'witness_loop: for &a in witnesses {
-        if a >= n {
-            continue;
-        }
-        
-        let mut x = mod_pow_u64(a, d, n);
-        if x == 1 || x == n - 1 {
-            continue 'witness_loop;
-        }
-        
-        for _ in 0..r - 1 {
-            x = mod_mul_u64(x, x, n);
-            if x == n - 1 {
-                continue 'witness_loop;
-            }
-        }
-        
-        return false;
-    }
-    
-    true
-}
-
-/// Fast modular exponentiation for u64
-fn mod_pow_u64(mut base: u64, mut exp: u64, modulus: u64) -> u64 {
-    if modulus == 1 {
-        return 0;
-    }
-    let mut result = 1;
-    base %= modulus;
-    while exp > 0 {
-        if exp % 2 == 1 {
-            result = mod_mul_u64(result, base, modulus);
-        }
-        exp >>= 1;
-        base = mod_mul_u64(base, base, modulus);
-    }
-    result
-}
-
-/// Fast modular multiplication for u64 to avoid overflow
-/// Optimized for large moduli
-fn mod_mul_u64(a: u64, b: u64, modulus: u64) -> u64 {
-    // For huge moduli, use u128 arithmetic which is fast on 64-bit systems
-    ((a as u128 * b as u128) % modulus as u128) as u64
+pub fn largest_known_mersenne_exponent() -> u64 {
+    KNOWN_MERSENNE_PRIME_EXPONENTS[KNOWN_MERSENNE_PRIME_COUNT - 1]
 }
 
-/// Optimized modulo operation for Mersenne numbers M_p = 2^p - 1
-///
-/// This function implements the bitwise trick for computing k mod (2^p - 1):
-/// repeatedly take the bits beyond position p, shift them down, and add them
-/// to the lower p bits until no bits remain above p.
-///
-/// This is much faster than general-purpose modulo for Mersenne numbers.
-///
-/// # Arguments
-///
-/// * `k` - The number to reduce modulo M_p
-/// * `p` - The Mersenne exponent (M_p = 2^p - 1)
+/// Exponents of all known Mersenne primes, in ascending order
 ///
-/// # Returns
-///
-/// * k mod (2^p - 1)
+/// The same list as `KNOWN_MERSENNE_PRIME_EXPONENTS`, and the one to check
+/// results against.
 ///
-/// # Algorithm
+/// # Examples
 ///
-/// For a number k and M_p = 2^p - 1:
-/// 1. While k has bits beyond position p:
-///    a. Take the high bits (bits p and above)
-///    b. Shift them down by p positions
-///    c. Add them to the low bits (bits 0 to p-1)
-/// 2. The result is k mod M_p
+/// ```
+/// use primality_jones::{known_mersenne_primes, lucas_lehmer_test};
 ///
-/// This works because 2^p ≡ 1 (mod M_p), so shifting by p positions
-/// is equivalent to multiplying by 2^p ≡ 1.
-pub fn mod_mp(k: &BigUint, p: u64) -> BigUint {
-    // Handle edge cases first
-    if k.is_zero() {
-        return BigUint::zero();
-    }
-    
-    // Only compute mp if needed
-    if k.bits() <= p {
-        return k.clone();
-    }
-    
-    let mp = (BigUint::one() << p) - BigUint::one();
-    
-    if k == &mp {
-        return BigUint::zero();
-    }
-    
-    let mut result = k.clone();
-    
-    // Optimized reduction loop - unroll for better performance
-    loop {
-        // Check if result fits in p bits
-        if result.bits() <= p {
-            break;
-        }
-        
-        // Split result into high and low parts more efficiently
-        let high_bits = &result >> p;
-        let low_bits = &result & &mp;
-        
-        // Add high bits to low bits
-        result = high_bits + low_bits;
-        
-        // If result is small enough, we're done
-        if result <= mp {
-            break;
-        }
-    }
-    
-    // Final check: if result equals mp, return 0
-    if result == mp {
-        BigUint::zero()
-    } else {
-        result
-    }
+/// for &p in known_mersenne_primes().iter().take_while(|&&p| p <= 127) {
+///     assert!(lucas_lehmer_test(p));
+/// }
+/// ```
+pub fn known_mersenne_primes() -> &'static [u64] {
+    &KNOWN_MERSENNE_PRIME_EXPONENTS
 }
 
-/// Optimized square and subtract 2 modulo M_p for Lucas-Lehmer test
-///
-/// This function computes (s^2 - 2) mod M_p using the optimized modulo
-/// operation, which is much faster than general-purpose arithmetic.
-///
-/// # Arguments
-///
-/// * `s` - The current value in the Lucas-Lehmer sequence
-/// * `p` - The Mersenne exponent (M_p = 2^p - 1)
+/// Prime exponents below 1000 whose Mersenne number is composite
+const KNOWN_COMPOSITE_EXPONENTS: [u64; 154] = [
+    11, 23, 29, 37, 41, 43, 47, 53, 59, 67, 71, 73, 79, 83, 97, 101, 103, 109, 113, 131,
+    137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193, 197, 199, 211, 223, 227,
+    229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307, 311, 313, 317,
+    331, 337, 347, 349, 353, 359, 367, 373, 379, 383, 389, 397, 401, 409, 419, 421, 431,
+    433, 439, 443, 449, 457, 461, 463, 467, 479, 487, 491, 499, 503, 509, 523, 541, 547,
+    557, 563, 569, 571, 577, 587, 593, 599, 601, 613, 617, 619, 631, 641, 643, 647, 653,
+    659, 661, 673, 677, 683, 691, 701, 709, 719, 727, 733, 739, 743, 751, 757, 761, 769,
+    773, 787, 797, 809, 811, 821, 823, 827, 829, 839, 853, 857, 859, 863, 877, 881, 883,
+    887, 907, 911, 919, 929, 937, 941, 947, 953, 967, 971, 977, 983, 991, 997,
+];
+
+/// Prime exponents p below 1000 for which M_p is composite, in ascending order
 ///
-/// # Returns
-///
-/// * (s^2 - 2) mod M_p
-pub fn square_and_subtract_two_mod_mp(s: &BigUint, p: u64) -> BigUint {
-    let squared = s * s;
-    
-    // Direct optimization: subtract 2 before the modulo operation when possible
-    if squared >= BigUint::from(2u32) {
-        let minus_two = squared - BigUint::from(2u32);
-        mod_mp(&minus_two, p)
-    } else {
-        // Handle edge case where squared < 2
-        let mp = (BigUint::one() << p) - BigUint::one();
-        let result = squared + &mp - BigUint::from(2u32);
-        mod_mp(&result, p)
-    }
+/// Together with the known primes up to 607 this covers every prime exponent
+/// below 1000, so it is the natural set of negative cases for testing a
+/// primality test. (A composite exponent always gives a composite M_p, so
+/// only prime exponents are listed.)
+pub fn known_composite_exponents() -> &'static [u64] {
+    &KNOWN_COMPOSITE_EXPONENTS
 }
 
-/// Perform a Miller-Rabin primality test with parallel rounds
+/// A Mersenne exponent p, identifying the Mersenne number M_p = 2^p - 1
 ///
-/// This is an optimized version that runs Miller-Rabin rounds in parallel
-/// for better performance on multi-core systems.
+/// Wrapping the exponent in its own type keeps it from being confused with
+/// residues or factors, which are also plain integers. Every function that
+/// takes a Mersenne exponent accepts `impl Into<Exponent>`, so a bare `u64`
+/// still works; only the `mod_mp` family, where p describes the modulus of
+/// the arithmetic rather than a candidate, takes the `u64` itself.
 ///
-/// # Arguments
-///
-/// * `p` - The Mersenne exponent to test (testing 2^p - 1)
-/// * `k` - Number of rounds of testing (higher k = lower probability of false positive)
-/// * `start_time` - Start time of the test
-/// * `timeout` - Timeout for the test
+/// # Examples
 ///
-/// # Returns
+/// ```
+/// use primality_jones::Exponent;
 ///
-/// * `true` if all tests pass (number is probably prime)
-/// * `false` if any test fails (number is definitely composite)
-pub fn miller_rabin_test_parallel(p: u64, k: u32, start_time: Instant, timeout: Duration) -> bool {
-    let m = (BigUint::one() << p) - BigUint::one();
-    let m_minus_1 = &m - BigUint::one();
-
-    // Write m-1 = 2^s * d where d is odd
-    let mut s = 0;
-    let mut d = m_minus_1.clone();
-    while &d % BigUint::from(2u32) == BigUint::zero() {
-        s += 1;
-        d /= BigUint::from(2u32);
-    }
-
-    // Create progress bar for Miller-Rabin tests
-    let pb = ProgressBar::new(k as u64);
-    pb.set_style(ProgressStyle::default_bar()
-        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} tests ({eta})")
-        .unwrap()
-        .progress_chars("#>-"));
+/// let p = Exponent::new(127).unwrap();
+/// assert_eq!(p.to_string(), "M127");
+/// assert!(Exponent::new(1).is_none());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Exponent(u64);
 
-    // Run Miller-Rabin rounds in parallel
-    let results: Vec<bool> = (0..k).into_par_iter().map(|_| {
-        // Check timeout
-        if start_time.elapsed() > timeout {
-            return false;
+impl Exponent {
+    /// Create an exponent, rejecting 0 and 1 (M_0 = 0 and M_1 = 1 are not candidates)
+    pub fn new(p: u64) -> Option<Self> {
+        if p < 2 {
+            None
+        } else {
+            Some(Exponent(p))
         }
+    }
 
-        // Generate random base between 2 and m-1
-        let mut rng = thread_rng();
-        let a = rng.gen_biguint_range(&BigUint::from(2u32), &m);
-
-        // Compute x = a^d mod m
-        let mut x = a.modpow(&d, &m);
-
-        // If x == 1 or x == m-1, this round passes
-        if x == BigUint::one() || x == m_minus_1 {
-            return true;
+    /// Create an exponent that must itself be prime
+    ///
+    /// M_p can only be prime when p is prime, so this rejects exponents that
+    /// would be eliminated by the pre-screen anyway.
+    #[cfg(feature = "std")]
+    pub fn new_prime(p: u64) -> Option<Self> {
+        if is_prime(p) {
+            Some(Exponent(p))
+        } else {
+            None
         }
+    }
 
-        // Check x^(2^r) mod m for r = 1 to s-1
-        let mut is_witness = true;
-        for _r in 1..s {
-            x = x.modpow(&BigUint::from(2u32), &m);
-
-            if x == m_minus_1 {
-                is_witness = false;
-                break;
-            }
-
-            if x == BigUint::one() {
-                // Found a non-trivial square root of 1, so m is composite
-                return false;
-            }
-        }
+    /// The raw exponent value
+    pub fn get(self) -> u64 {
+        self.0
+    }
 
-        !is_witness
-    }).collect();
+    /// The Mersenne number 2^p - 1 this exponent identifies
+    pub fn mersenne_number(self) -> BigUint {
+        (BigUint::one() << self.0) - BigUint::one()
+    }
+}
 
-    // Update progress bar
-    pb.inc(k as u64);
-    pb.finish_with_message("Completed");
+impl From<u64> for Exponent {
+    fn from(p: u64) -> Self {
+        Exponent(p)
+    }
+}
 
-    // All rounds must pass
-    results.into_iter().all(|passed| passed)
+impl From<Exponent> for u64 {
+    fn from(p: Exponent) -> Self {
+        p.0
+    }
 }
 
-/// Perform a Miller-Rabin primality test with specified parameters
-///
-/// The Miller-Rabin test is a probabilistic primality test that is strictly stronger
-/// than the Fermat test. It can detect all strong pseudoprimes and is the standard
-/// for probabilistic primality testing.
-///
-/// # Arguments
-///
-/// * `p` - The Mersenne exponent to test (testing 2^p - 1)
-/// * `k` - Number of rounds of testing (higher k = lower probability of false positive)
-/// * `start_time` - Start time of the test
-/// * `timeout` - Timeout for the test
-///
-/// # Returns
-///
-/// * `true` if all tests pass (number is probably prime)
-/// * `false` if any test fails (number is definitely composite)
-///
-/// # Algorithm
-///
-/// For a Mersenne number M_p = 2^p - 1:
-/// 1. Write M_p - 1 = 2^s * d where d is odd
-/// 2. For each round, choose a random base a in [2, M_p-1]
-/// 3. Compute x = a^d mod M_p
-/// 4. If x == 1 or x == M_p-1, continue to next round
-/// 5. For r = 1 to s-1, compute x = x^2 mod M_p
-/// 6. If x == 1, the number is composite
-/// 7. If x == M_p-1, continue to next round
-/// 8. If we reach here, the number is composite
-/// 9. If all rounds pass, the number is probably prime
-pub fn miller_rabin_test(p: u64, k: u32, start_time: Instant, timeout: Duration) -> bool {
-    // Use parallel version for better performance
-    miller_rabin_test_parallel(p, k, start_time, timeout)
+impl core::fmt::Display for Exponent {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "M{}", self.0)
+    }
 }
 
-/// Check a Mersenne number candidate with the specified level of thoroughness
-///
-/// This is the main entry point for testing Mersenne number candidates. It performs
-/// a strict pipeline of tests, failing fast if any test fails.
-///
-/// # Arguments
-///
-/// * `p` - The Mersenne exponent to test (testing 2^p - 1)
-/// * `level` - How thorough the testing should be
+/// log10(2) as a 128-bit binary fraction, rounded down
+const LOG10_2_FIXED: u128 = 0x4d10_4d42_7de7_fbcc_47c4_acd6_05be_48bc;
+
+/// Exact number of decimal digits of M_p = 2^p - 1
 ///
-/// # Returns
+/// 2^p is never a power of ten, so M_p has as many digits as 2^p, which is
+/// floor(p log10 2) + 1. Multiplying by `p as f64 * LOG10_2` loses the low
+/// digits of the product as soon as p is large and can land on the wrong
+/// side of an integer; this computes floor(p * L / 2^128) exactly in integer
+/// arithmetic instead, where L / 2^128 is `LOG10_2_FIXED`.
 ///
-/// A vector of `CheckResult`s, one for each test performed. The candidate is
-/// considered promising if all tests pass.
+/// L / 2^128 falls short of log10 2 by less than 2.3 * 10^-40, so
+/// p * L / 2^128 falls short of p log10 2 by less than 4.1 * 10^-21 for p
+/// below 2^64, and the floor is wrong only if p log10 2 is less than that
+/// above an integer. As p grows, each new minimum of the fractional part of
+/// p log10 2 comes at the denominator of a convergent or intermediate
+/// fraction of the continued fraction of log10 2. Going through those below
+/// 2^64, the smallest fractional part is 2.7 * 10^-20, at
+/// p = 4415969241540963378, so the floor is always exact. M_0 = 0 has one
+/// digit.
 ///
 /// # Examples
 ///
 /// ```
-/// use primality_jones::{CheckLevel, check_mersenne_candidate};
-///
-/// let results = check_mersenne_candidate(31, CheckLevel::LucasLehmer);
-/// assert!(results.iter().all(|r| r.passed)); // M31 is prime
+/// use primality_jones::mersenne_digit_count;
 ///
-/// let results = check_mersenne_candidate(32, CheckLevel::TrialFactoring);
-/// assert!(!results.iter().all(|r| r.passed)); // M32 is composite
+/// assert_eq!(mersenne_digit_count(127), 39);
+/// assert_eq!(mersenne_digit_count(521), 157);
+/// assert_eq!(mersenne_digit_count(82_589_933), 24_862_048);
 /// ```
-pub fn check_mersenne_candidate(p: u64, level: CheckLevel) -> Vec<CheckResult> {
-    let mut results = Vec::new();
-    let start_time = Instant::now();
-
-    // PreScreen: Check if the exponent p itself is prime
-    let check_start = Instant::now();
-    let prime_passed = is_prime(p);
-    results.push(CheckResult {
-        passed: prime_passed,
-        message: if prime_passed {
-            "Exponent is prime".to_string()
-        } else {
-            "Exponent is not prime".to_string()
-        },
-        time_taken: check_start.elapsed(),
-        kind: CheckKind::ExponentPrime,
-    });
-
-    if !prime_passed || level == CheckLevel::PreScreen {
-        return results;
-    }
-
-    // TrialFactoring: Check for small factors
-    let check_start = Instant::now();
-    if let Some(factor) = check_small_factors_parallel(p, 1_000_000) {
-        results.push(CheckResult {
-            passed: false,
-            message: format!("Found small factor: {factor}"),
-            time_taken: check_start.elapsed(),
-            kind: CheckKind::TrialFactor,
-        });
-        return results;
-    }
-    results.push(CheckResult {
-        passed: true,
-        message: "No small factors found up to 1M".to_string(),
-        time_taken: check_start.elapsed(),
-        kind: CheckKind::TrialFactor,
-    });
-
-    if level == CheckLevel::TrialFactoring {
-        return results;
-    }
-
-    // Probabilistic: Miller-Rabin test
-    // Skip for very large numbers (>100M digits means p > ~332M)
-    if p > 332_000_000 {
-        results.push(CheckResult {
-            passed: true,
-            message: "Skipped Miller-Rabin test (number too large)".to_string(),
-            time_taken: Duration::from_secs(0),
-            kind: CheckKind::MillerRabin,
-        });
-    } else {
-        let check_start = Instant::now();
-        let timeout = Duration::from_secs(300); // 5 minutes
-        let miller_rabin_passed = miller_rabin_test(p, 5, start_time, timeout);
-        results.push(CheckResult {
-            passed: miller_rabin_passed,
-            message: if miller_rabin_passed {
-                "Passed Miller-Rabin test".to_string()
-            } else {
-                "Failed Miller-Rabin test".to_string()
-            },
-            time_taken: check_start.elapsed(),
-            kind: CheckKind::MillerRabin,
-        });
-
-        if !miller_rabin_passed || level == CheckLevel::Probabilistic {
-            return results;
-        }
-    }
-
-    // LucasLehmer: The definitive test
-    let check_start = Instant::now();
-    let ll_passed = lucas_lehmer_test(p);
-    results.push(CheckResult {
-        passed: ll_passed,
-        message: if ll_passed {
-            "Passed Lucas-Lehmer test (definitive)".to_string()
-        } else {
-            "Failed Lucas-Lehmer test (definitive)".to_string()
-        },
-        time_taken: check_start.elapsed(),
-        kind: CheckKind::LucasLehmer,
-    });
-
-    results
+pub fn mersenne_digit_count(p: impl Into<Exponent>) -> u64 {
+    let p = p.into().get();
+    let p = u128::from(p);
+    let low = (p * (LOG10_2_FIXED as u64 as u128)) >> 64;
+    let floor = ((p * (LOG10_2_FIXED >> 64) + low) >> 64) as u64;
+    floor + 1
 }
 
-/// Check for small factors of a Mersenne number using parallel processing
-///
-/// This is an optimized version that uses parallel processing to check
-/// multiple potential factors simultaneously.
-///
-/// # Arguments
-///
-/// * `p` - The Mersenne exponent
-/// * `limit` - Maximum factor to check up to
+/// Lucas-Lehmer test on M_p for builds without the `std` feature
 ///
-/// # Returns
-///
-/// * `Some(factor)` if a factor is found
-/// * `None` if no factors are found
-pub fn check_small_factors_parallel(p: u64, limit: u64) -> Option<u64> {
-    if !is_prime(p) {
-        return None;
+/// Gives the same answers as the `std` version, but without the Jacobi error
+/// checks of `lucas_lehmer_test_checked`, and it cannot report progress.
+#[cfg(not(feature = "std"))]
+pub fn lucas_lehmer_test(p: impl Into<Exponent>) -> bool {
+    let p = p.into().get();
+    if let Some(is_prime) = lucas_lehmer_test_u128(p) {
+        return is_prime;
     }
-
-    // Calculate the maximum k value to check
-    let max_k = (limit - 1) / (2 * p);
-    
-    // Use parallel iterator to check factors
-    let factor = (1..=max_k).into_par_iter()
-        .map(|k| {
-            let q = 2 * k * p + 1;
-            if q > limit {
-                return None;
-            }
-            
-            // Check if q satisfies the congruence condition
-            if (q % 8 == 1 || q % 8 == 7) && is_prime(q) {
-                // Check if q divides 2^p - 1 using modular arithmetic
-                let remainder = BigUint::from(2u32).modpow(&BigUint::from(p), &BigUint::from(q));
-                if remainder == BigUint::one() {
-                    // Don't count M_p itself as a factor
-                    let m_p = (BigUint::one() << p) - BigUint::one();
-                    if BigUint::from(q) != m_p {
-                        return Some(q);
-                    }
-                }
-            }
-            None
-        })
-        .find_any(|result| result.is_some())
-        .flatten();
-
-    factor
-}
-
-/// Check for small factors of a Mersenne number using special properties
-pub fn check_small_factors(p: u64, limit: u64) -> Option<u64> {
-    // Use parallel version for better performance
-    check_small_factors_parallel(p, limit)
+    let mut scratch = ModMpScratch::new(p);
+    let mut s = BigUint::from(4u32);
+    for _ in 0..p - 2 {
+        scratch.square_and_subtract_two(&mut s);
+    }
+    s.is_zero()
 }
 
-/// Perform the Lucas-Lehmer test for Mersenne number primality
-///
-/// This is the definitive test for Mersenne primes. For a Mersenne number M_p = 2^p - 1:
-/// 1. Start with s = 4
-/// 2. For p-2 iterations, compute s = (s^2 - 2) mod M_p
-/// 3. M_p is prime if and only if the final result is s = 0
+/// Largest exponent `lucas_lehmer_test_u128` handles
 ///
-/// # Arguments
+/// A reduced value is below 2^p, so its square fits in a `u128` while
+/// 2p ≤ 127.
+pub const LUCAS_LEHMER_U128_MAX_EXPONENT: u64 = 63;
+
+/// Lucas-Lehmer test on M_p in native `u128` arithmetic, for p up to 63
 ///
-/// * `p` - The Mersenne exponent to test (testing 2^p - 1)
+/// Squares and reduces with the same fold as `mod_mp`, but in registers,
+/// with no `BigUint` allocated at all. `lucas_lehmer_test` takes this path
+/// whenever it can; it skips the error check of `lucas_lehmer_test_checked`,
+/// which guards long runs and has nothing to catch in a few dozen native
+/// operations.
 ///
 /// # Returns
 ///
-/// * `true` if M_p is prime
-/// * `false` if M_p is composite
+/// * `Some(verdict)` for p ≤ `LUCAS_LEHMER_U128_MAX_EXPONENT`, where p = 0
+///   and p = 1 give `Some(false)` as in `lucas_lehmer_test`
+/// * `None` for larger p
 ///
 /// # Examples
 ///
 /// ```
-/// use primality_jones::lucas_lehmer_test;
+/// use primality_jones::lucas_lehmer_test_u128;
 ///
-/// assert!(lucas_lehmer_test(7));   // M7 = 127 is prime
-/// assert!(!lucas_lehmer_test(11)); // M11 = 2047 is composite
+/// assert_eq!(lucas_lehmer_test_u128(31), Some(true));
+/// assert_eq!(lucas_lehmer_test_u128(59), Some(false));
+/// assert_eq!(lucas_lehmer_test_u128(89), None);
 /// ```
-pub fn lucas_lehmer_test(p: u64) -> bool {
-    if p < 2 {
-        return false;
+pub fn lucas_lehmer_test_u128(p: impl Into<Exponent>) -> Option<bool> {
+    let p = p.into().get();
+    if p > LUCAS_LEHMER_U128_MAX_EXPONENT {
+        return None;
     }
-    
-    // Special case: M2 = 3 is prime
-    if p == 2 {
-        return true;
+    if p < 3 {
+        return Some(p == 2);
     }
-
-    let mut s = BigUint::from(4u32);
-
-    // Perform p-2 iterations of the Lucas-Lehmer sequence
-    for _ in 0..(p - 2) {
-        s = square_and_subtract_two_mod_mp(&s, p);
+    let mp = (1u128 << p) - 1;
+    let mut s: u128 = 4;
+    for _ in 0..p - 2 {
+        let square = s * s;
+        // Two folds bring anything below 2^(2p) to at most M_p
+        let folded = (square & mp) + (square >> p);
+        let mut r = (folded & mp) + (folded >> p);
+        if r == mp {
+            r = 0;
+        }
+        s = if r >= 2 { r - 2 } else { r + mp - 2 };
     }
-
-    // M_p is prime if and only if s = 0
-    s == BigUint::zero()
-}
-
-/// Process multiple Mersenne candidates in parallel
-///
-/// This function allows efficient processing of multiple candidates
-/// by utilizing all available CPU cores.
-///
-/// # Arguments
-///
-/// * `candidates` - Vector of Mersenne exponents to test
-/// * `level` - How thorough the testing should be
-///
-/// # Returns
-///
-/// Vector of (exponent, results) pairs
-///
-/// # Example
-///
-/// ```
-/// use primality_jones::{CheckLevel, process_candidates_parallel};
-///
-/// let candidates = vec![31, 61, 89, 107, 127];
-/// let results = process_candidates_parallel(candidates, CheckLevel::LucasLehmer);
-/// 
-/// for (p, candidate_results) in results {
-///     if candidate_results.iter().all(|r| r.passed) {
-///         println!("M{} is prime!", p);
-///     }
-/// }
-/// ```
-pub fn process_candidates_parallel(candidates: Vec<u64>, level: CheckLevel) -> Vec<(u64, Vec<CheckResult>)> {
-    candidates.into_par_iter()
-        .map(|p| (p, check_mersenne_candidate(p, level)))
-        .collect()
+    Some(s == 0)
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use super::*;
+    use num_bigint::RandBigInt;
+    use num_traits::Zero;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::collections::HashMap;
+    use std::fs;
+    use std::io::{self, Write};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
 
     #[test]
     fn test_is_prime() {
@@ -731,6 +394,20 @@ mod tests {
         assert!(!is_prime(0));
     }
 
+    #[test]
+    fn test_is_prime_squares_of_primes() {
+        // Squares of primes near 2^10, 2^20 and 2^32, on both sides
+        // of the switch from trial division to Miller-Rabin
+        for q in [1021u64, 997, 1048573, 4294967291] {
+            assert!(is_prime(q));
+            assert!(!is_prime(q * q), "{}^2", q);
+            assert!(!is_prime(q * (q + 2)), "{} * {}", q, q + 2);
+        }
+        assert!(is_prime(18446744073709551557)); // largest prime below 2^64
+        assert!(!is_prime(u64::MAX));
+        assert!(!is_prime(4294967291 * 4294967279));
+    }
+
     #[test]
     fn test_miller_rabin_test() {
         // M31 is a known Mersenne prime
@@ -747,6 +424,49 @@ mod tests {
             Instant::now(),
             Duration::from_secs(30)
         ));
+
+        // The quiet variant reaches the same verdicts
+        for p in [31u64, 61, 89, 107, 127] {
+            assert!(miller_rabin_test_quiet(p, 5, Instant::now(), Duration::from_secs(30)), "M{}", p);
+        }
+        for p in [11u64, 32, 67, 101] {
+            assert!(!miller_rabin_test_quiet(p, 5, Instant::now(), Duration::from_secs(30)), "M{}", p);
+        }
+    }
+
+    #[test]
+    fn test_fermat_test() {
+        let three = BigUint::from(3u32);
+        assert!(fermat_test(31, &three));
+        for p in [2u64, 3, 5, 7, 13, 17, 19, 61, 89, 107, 127] {
+            assert!(fermat_test(p, &three), "M{}", p);
+        }
+        for p in [11u64, 23, 29, 37, 41, 43, 47, 53, 59, 67] {
+            assert!(!fermat_test(p, &three), "M{}", p);
+            // Composite M_p with prime p always fool base 2
+            assert!(fermat_test(p, &BigUint::from(2u32)), "M{}", p);
+        }
+        assert!(!fermat_test(0, &three));
+        assert!(!fermat_test(1, &three));
+        // 3 = M_2 itself
+        assert!(fermat_test(2, &three));
+    }
+
+    #[test]
+    fn test_miller_rabin_timeout_is_inconclusive() {
+        let expired = Instant::now() - Duration::from_secs(1);
+        assert_eq!(
+            miller_rabin_test_checked(31, 5, expired, Duration::from_millis(1)),
+            MillerRabinResult::Inconclusive
+        );
+        assert_eq!(
+            miller_rabin_test_checked(61, 5, Instant::now(), Duration::from_secs(30)),
+            MillerRabinResult::ProbablyPrime
+        );
+        assert_eq!(
+            miller_rabin_test_checked(67, 5, Instant::now(), Duration::from_secs(30)),
+            MillerRabinResult::Composite
+        );
     }
 
     #[test]
@@ -760,12 +480,112 @@ mod tests {
         assert!(!results.iter().all(|r| r.passed));
     }
 
+    #[test]
+    fn test_mersenne_check_iterator() {
+        let summary = |r: &CheckResult| (r.kind, r.passed, r.inconclusive, r.factor.clone(), r.message.clone());
+        let levels = [
+            CheckLevel::PreScreen,
+            CheckLevel::TrialFactoring,
+            CheckLevel::PMinusOne,
+            CheckLevel::Probabilistic,
+            CheckLevel::LucasLehmer,
+        ];
+        for p in [1u64, 4, 11, 15, 31, 37, 61, 67, 127] {
+            for level in levels {
+                let streamed: Vec<_> = MersenneCheck::new(p, level).map(|r| summary(&r)).collect();
+                let batch: Vec<_> = check_mersenne_candidate(p, level).iter().map(summary).collect();
+                assert_eq!(streamed, batch, "M{} at {:?}", p, level);
+            }
+        }
+
+        // Stages are run only as they are pulled
+        let mut check = MersenneCheck::new(11, CheckLevel::LucasLehmer);
+        assert_eq!(check.next().map(|r| (r.kind, r.passed)), Some((CheckKind::ExponentPrime, true)));
+        let factor = check.next().unwrap();
+        assert_eq!(factor.factor, Some(BigUint::from(23u32)));
+        assert!(check.next().is_none());
+        assert!(check.next().is_none());
+
+        let config = CheckConfig::new().known_factors(HashMap::from([(67, vec![BigUint::from(193_707_721u64)])]));
+        let results: Vec<_> = MersenneCheck::with_config(67, CheckLevel::LucasLehmer, &config).collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1].message, "Known factor: 193707721");
+    }
+
+    #[test]
+    fn test_plan_check() {
+        let plan = plan_check(82_589_933, CheckLevel::LucasLehmer);
+        let kinds: Vec<CheckKind> = plan.stages.iter().map(|stage| stage.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                CheckKind::ExponentPrime,
+                CheckKind::TrialFactor,
+                CheckKind::PMinusOne,
+                CheckKind::MillerRabin,
+                CheckKind::LucasLehmer
+            ]
+        );
+        // Only the stages modulo M_p cost anything worth counting
+        assert!(plan.stages[..2].iter().all(|s| s.estimated_duration == Duration::ZERO && s.bytes_required == 0));
+        assert!(plan.stages[2..].iter().all(|s| s.estimated_duration > Duration::ZERO));
+        assert_eq!(plan.peak_memory_bytes, estimate_resources(82_589_933, CheckLevel::LucasLehmer).bytes_required);
+        let total: Duration = plan.stages.iter().map(|s| s.estimated_duration).sum();
+        assert!(total.abs_diff(plan.estimated_duration) < Duration::from_millis(1));
+
+        // The plan matches what the pipeline runs when nothing fails
+        let ran: Vec<CheckKind> = check_mersenne_candidate(127, CheckLevel::Full).iter().map(|r| r.kind).collect();
+        assert_eq!(ran, plan_check(127, CheckLevel::Full).stages.iter().map(|s| s.kind).collect::<Vec<_>>());
+        assert_eq!(plan_check(127, CheckLevel::TrialFactoring).stages.len(), 2);
+
+        // Miller-Rabin is still listed where it would be skipped, at no cost
+        let huge = plan_check(400_000_000, CheckLevel::Probabilistic);
+        assert_eq!(huge.stages[3].kind, CheckKind::MillerRabin);
+        assert_eq!(huge.stages[3].estimated_duration, Duration::ZERO);
+
+        let invalid = plan_check(1, CheckLevel::LucasLehmer);
+        assert_eq!((invalid.estimated_duration, invalid.peak_memory_bytes), (Duration::ZERO, 0));
+
+        // Full runs P-1 stage 2 up to B2 = 20 * B1, which the plan must count
+        for p in [9_941u64, 82_589_933] {
+            let p_minus_1 = |level| plan_check(p, level).stages[2].estimated_duration.as_secs_f64();
+            let ratio = p_minus_1(CheckLevel::Full) / p_minus_1(CheckLevel::LucasLehmer);
+            assert!(ratio > 1.2 && ratio < 2.0, "M{}: {}", p, ratio);
+        }
+    }
+
+    #[test]
+    fn test_estimate_resources() {
+        assert_eq!(estimate_resources(2, CheckLevel::LucasLehmer).decimal_digits, 1);
+        assert_eq!(estimate_resources(127, CheckLevel::LucasLehmer).decimal_digits, 39);
+        assert_eq!(estimate_resources(136_279_841, CheckLevel::PreScreen).decimal_digits, 41_024_320);
+        assert_eq!(estimate_resources(136_279_841, CheckLevel::PreScreen).estimated_duration, Duration::ZERO);
+
+        // The reference points are reproduced
+        for (p, seconds) in LUCAS_LEHMER_ITERATION_SECONDS {
+            assert!((interpolate_cost(&LUCAS_LEHMER_ITERATION_SECONDS, p) / seconds - 1.0).abs() < 1e-9);
+        }
+
+        // More work never gets cheaper, whether in p or in level
+        let levels = [CheckLevel::PMinusOne, CheckLevel::Probabilistic, CheckLevel::LucasLehmer];
+        let mut previous = Duration::ZERO;
+        for p in [3u64, 127, 1279, 3000, 44_497, 100_000, 1_000_000, 100_000_000, 1_000_000_000] {
+            let durations: Vec<Duration> = levels.iter().map(|&l| estimate_resources(p, l).estimated_duration).collect();
+            assert!(durations.windows(2).all(|w| w[0] <= w[1]), "M{}: {:?}", p, durations);
+            assert!(durations[0] >= previous, "M{}", p);
+            previous = durations[0];
+            assert!(estimate_resources(p, CheckLevel::LucasLehmer).bytes_required >= p / 8);
+        }
+        assert!(estimate_resources(127, CheckLevel::LucasLehmer).estimated_duration < Duration::from_secs(1));
+        assert_eq!(estimate_resources(u64::MAX, CheckLevel::LucasLehmer).estimated_duration, Duration::MAX);
+    }
+
     #[test]
     fn test_large_numbers() {
         // Test handling of a moderately large number
         let results = check_mersenne_candidate(12301, CheckLevel::PreScreen);
         // Should at least complete without panicking
-        assert!(results.len() > 0);
+        assert!(!results.is_empty());
     }
 
     #[test]
@@ -820,76 +640,1791 @@ mod tests {
     }
 
     #[test]
-    fn test_parallel_processing() {
-        // Test parallel processing of multiple candidates
-        let candidates = vec![31, 61, 89, 107, 127];
-        let results = process_candidates_parallel(candidates.clone(), CheckLevel::LucasLehmer);
-        
-        assert_eq!(results.len(), candidates.len());
-        
-        // Verify that known primes are correctly identified
-        for (p, candidate_results) in results {
-            if p == 31 || p == 61 || p == 89 || p == 107 || p == 127 {
-                assert!(candidate_results.iter().all(|r| r.passed), 
-                    "M{} should be identified as prime", p);
+    fn test_mod_mp_fold_count() {
+        for p in [1u64, 2, 3, 7, 31, 64, 127, 521, 4423] {
+            let mp = (BigUint::one() << p) - BigUint::one();
+            let all_ones = (BigUint::one() << (2 * p)) - BigUint::one();
+            // The largest Lucas-Lehmer input, the largest value below 2^(2p)
+            // (whose second fold lands exactly on M_p), and M_p² itself
+            for k in [(&mp - 1u32) * (&mp - 1u32), all_ones, &mp * &mp] {
+                let (result, folds) = mersenne::mod_mp_with_folds(&k, &mp, p);
+                assert_eq!(result, &k % &mp, "p = {}", p);
+                assert!(folds <= 2, "{} folds for p = {}", folds, p);
             }
+
+            // Wider inputs lose at least p - 1 bits per fold
+            let wide = (BigUint::one() << (10 * p + 5)) - BigUint::one();
+            let (result, folds) = mersenne::mod_mp_with_folds(&wide, &mp, p);
+            assert_eq!(result, &wide % &mp);
+            assert!(u64::from(folds) <= wide.bits().div_ceil(p.max(2) - 1) + 1, "{} folds for p = {}", folds, p);
         }
     }
-}
 
-/// Python module for Mersenne number primality testing
-#[cfg(feature = "pyo3")]
-#[pymodule]
-fn primality_jones(_py: Python, m: &PyModule) -> PyResult<()> {
-    // Expose CheckLevel enum to Python
-    #[pyclass]
-    #[derive(Clone, Copy)]
-    enum PyCheckLevel {
-        PreScreen = 0,
-        TrialFactoring = 1,
-        Probabilistic = 2,
-        LucasLehmer = 3,
+    #[test]
+    fn test_mod_mp_with_modulus() {
+        let mut rng = StdRng::seed_from_u64(1810);
+        for p in [2u64, 3, 31, 64, 89, 521, 2203] {
+            let mp = (BigUint::one() << p) - BigUint::one();
+            for k in [BigUint::zero(), mp.clone(), &mp * 3u32, &mp * &mp - 1u32, rng.gen_biguint(3 * p)] {
+                assert_eq!(mod_mp_with_modulus(&k, &mp, p), &k % &mp, "p = {}", p);
+                assert_eq!(mod_mp_with_modulus(&k, &mp, p), mod_mp(&k, p));
+            }
+        }
     }
 
-    #[pymethods]
-    impl PyCheckLevel {
-        fn description(&self) -> String {
-            match self {
-                PyCheckLevel::PreScreen => {
-                    "Pre-screen: Check if exponent is prime (instant)".to_string()
-                }
-                PyCheckLevel::TrialFactoring => {
-                    "Trial factoring: Check for small factors (~1 second)".to_string()
-                }
-                PyCheckLevel::Probabilistic => {
-                    "Probabilistic: Miller-Rabin test (seconds to minutes)".to_string()
-                }
-                PyCheckLevel::LucasLehmer => {
-                    "Lucas-Lehmer: Definitive test (minutes to hours)".to_string()
-                }
+    #[test]
+    fn test_search_mersenne_primes() {
+        let found: Vec<u64> = search_mersenne_primes(0, CheckLevel::LucasLehmer).take(12).collect();
+        assert_eq!(found, known_mersenne_primes()[..12]);
+
+        // Resuming after any exponent continues the same sequence
+        let resumed: Vec<u64> = search_mersenne_primes(32, CheckLevel::LucasLehmer).take(3).collect();
+        assert_eq!(resumed, [61, 89, 107]);
+        assert_eq!(search_mersenne_primes(31, CheckLevel::LucasLehmer).next(), Some(31));
+
+        // Trial factoring lets some composites through but never drops a prime
+        let candidates: Vec<u64> = search_mersenne_primes(2, CheckLevel::TrialFactoring)
+            .take_while(|&p| p <= 127)
+            .collect();
+        assert!(known_mersenne_primes()[..12].iter().all(|p| candidates.contains(p)));
+        assert!(!candidates.contains(&11)); // 23 divides M11
+
+        // The search ends after the largest prime below 2^64
+        let mut top = search_mersenne_primes(LARGEST_U64_PRIME, CheckLevel::PreScreen);
+        assert_eq!(top.next(), Some(LARGEST_U64_PRIME));
+        assert_eq!(top.next(), None);
+        assert_eq!(search_mersenne_primes(u64::MAX, CheckLevel::PreScreen).next(), None);
+    }
+
+    #[test]
+    fn test_square_into() {
+        let mut rng = StdRng::seed_from_u64(1820);
+        let all_ones = |bits: u64| (BigUint::one() << bits) - BigUint::one();
+        let max = 64 * SQUARE_INTO_MAX_LIMBS as u64;
+        let mut out = BigUint::from(12345u32);
+        for s in [
+            BigUint::zero(),
+            BigUint::one(),
+            all_ones(64),
+            all_ones(max),
+            all_ones(max + 1),
+            rng.gen_biguint(607),
+            rng.gen_biguint(1279),
+            rng.gen_biguint(3000),
+        ] {
+            // Reuses the previous product's buffer, shorter or longer
+            square_into(&s, &mut out);
+            assert_eq!(out, &s * &s, "s has {} bits", s.bits());
+        }
+    }
+
+    #[test]
+    fn test_mod_mp_single_pass() {
+        let mut rng = StdRng::seed_from_u64(1825);
+        for p in [2u64, 3, 31, 64, 89, 521, 1279] {
+            let mp = (BigUint::one() << p) - BigUint::one();
+            let all_ones = (BigUint::one() << (2 * p)) - BigUint::one();
+            for k in [BigUint::zero(), mp.clone(), &mp + 1u32, &mp * &mp, all_ones, rng.gen_biguint(2 * p)] {
+                assert_eq!(mod_mp_single_pass(&k, &mp, p), mod_mp(&k, p), "p = {}, k = {}", p, k);
             }
         }
     }
 
-    /// Check a Mersenne number for primality
-    #[pyfunction]
-    fn check_mersenne(p: u64, level: PyCheckLevel) -> PyResult<Vec<PyObject>> {
-        let check_level = match level {
-            PyCheckLevel::PreScreen => CheckLevel::PreScreen,
-            PyCheckLevel::TrialFactoring => CheckLevel::TrialFactoring,
-            PyCheckLevel::Probabilistic => CheckLevel::Probabilistic,
-            PyCheckLevel::LucasLehmer => CheckLevel::LucasLehmer,
-        };
+    #[test]
+    fn test_mod_mp_limbs_matches_mod_mp() {
+        let from_limbs = |limbs: &[u64]| limbs.iter().rev().fold(BigUint::zero(), |acc, &limb| (acc << 64) + limb);
+        let mut rng = StdRng::seed_from_u64(1778);
+        for p in [1u64, 2, 7, 63, 64, 65, 127, 128, 129, 191, 521, 4423] {
+            let mp = (BigUint::one() << p) - BigUint::one();
+            let mut values = vec![
+                BigUint::zero(),
+                BigUint::one(),
+                mp.clone(),
+                &mp + 1u32,
+                &mp * &mp,
+                (&mp - 1u32) * (&mp - 1u32),
+                (BigUint::one() << (2 * p)) - BigUint::one(),
+            ];
+            values.extend((0..20).map(|_| rng.gen_biguint(3 * p + 100)));
+
+            for k in values {
+                let mut limbs = k.to_u64_digits();
+                mod_mp_limbs(&mut limbs, p);
+                assert_eq!(from_limbs(&limbs), mod_mp(&k, p), "mod_mp_limbs({}, {})", k, p);
+                assert_eq!(limbs, mod_mp(&k, p).to_u64_digits(), "limbs not normalized for p = {}", p);
+            }
+        }
 
-        let results = check_mersenne_candidate(p, check_level);
+        // Trailing zero limbs on input are accepted
+        let mut limbs = vec![200, 0, 0];
+        mod_mp_limbs(&mut limbs, 7);
+        assert_eq!(limbs, vec![73]);
+    }
 
-        Python::with_gil(|py| {
-            results
-                .into_iter()
-                .map(|r| {
-                    let dict = PyDict::new(py);
+    #[test]
+    fn test_mod_mp_arithmetic_matches_remainder() {
+        let mut rng = StdRng::seed_from_u64(1776);
+        for p in [1u64, 2, 3, 5, 7, 13, 31, 61, 64, 89] {
+            let mp = (BigUint::one() << p) - BigUint::one();
+            let mut values = vec![BigUint::zero(), BigUint::one(), mp.clone(), &mp + 1u32, &mp << 1];
+            values.extend((0..8).map(|_| rng.gen_biguint(p + 10)));
+
+            for a in &values {
+                for b in &values {
+                    assert_eq!(mul_mod_mp(a, b, p), (a * b) % &mp, "{} * {} mod M{}", a, b, p);
+                    assert_eq!(add_mod_mp(a, b, p), (a + b) % &mp, "{} + {} mod M{}", a, b, p);
+                    let expected = (a % &mp + &mp - b % &mp) % &mp;
+                    assert_eq!(sub_mod_mp(a, b, p), expected, "{} - {} mod M{}", a, b, p);
+                    assert_eq!(pow_mod_mp(a, b, p), a.modpow(b, &mp), "{} ^ {} mod M{}", a, b, p);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_mod_mp_assign_matches_mod_mp() {
+        let mut rng = StdRng::seed_from_u64(1775);
+        for p in [1u64, 2, 7, 63, 64, 65, 127, 128, 129, 521] {
+            let mp = (BigUint::one() << p) - BigUint::one();
+            let mut values = vec![
+                BigUint::zero(),
+                BigUint::one(),
+                mp.clone(),
+                &mp + 1u32,
+                &mp << 1,
+                &mp * &mp,
+                (&mp - 1u32) * (&mp - 1u32),
+            ];
+            values.extend((0..20).map(|_| rng.gen_biguint(2 * p + 70)));
+
+            let mut scratch = ModMpScratch::new(p);
+            for k in values {
+                let mut in_place = k.clone();
+                mod_mp_assign(&mut in_place, p);
+                assert_eq!(in_place, &k % &mp, "mod_mp_assign({}, {})", k, p);
+
+                let mut reused = k.clone();
+                scratch.reduce(&mut reused);
+                assert_eq!(reused, in_place, "ModMpScratch::reduce({}, {})", k, p);
+            }
+
+            for s in [0u32, 1, 2, 3, 4] {
+                let mut value = BigUint::from(s);
+                scratch.square_and_subtract_two(&mut value);
+                assert_eq!(value, square_and_subtract_two_mod_mp(&BigUint::from(s), p), "s = {}, p = {}", s, p);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cofactor_statuses() {
+        // M11 = 23 * 89
+        let result = test_cofactor(11, &[BigUint::from(23u32)], 10);
+        assert_eq!(result.cofactor, BigUint::from(89u32));
+        assert_eq!(result.status, CofactorStatus::ProbablePrime);
+
+        let result = test_cofactor(11, &[BigUint::from(23u32), BigUint::from(89u32)], 10);
+        assert_eq!(result.status, CofactorStatus::FullyFactored);
+
+        // M29 = 233 * 1103 * 2089, so removing 233 leaves a composite
+        let result = test_cofactor(29, &[BigUint::from(233u32)], 10);
+        assert_eq!(result.cofactor, BigUint::from(1103u32 * 2089));
+        assert_eq!(result.status, CofactorStatus::Composite);
+
+        // 7 does not divide M11
+        let result = test_cofactor(11, &[BigUint::from(7u32)], 10);
+        assert_eq!(result.status, CofactorStatus::InvalidFactor(BigUint::from(7u32)));
+    }
+
+    #[test]
+    fn test_reporter_sees_every_stage() {
+        use std::sync::Mutex;
+
+        #[derive(Default)]
+        struct Recorder {
+            stages: Mutex<Vec<CheckKind>>,
+            last_progress: Mutex<Option<(u64, u64)>>,
+        }
+
+        impl ProgressReporter for Recorder {
+            fn on_progress(&self, kind: CheckKind, current: u64, total: u64) {
+                assert_eq!(kind, CheckKind::LucasLehmer);
+                *self.last_progress.lock().unwrap() = Some((current, total));
+            }
+
+            fn on_stage_complete(&self, result: &CheckResult) {
+                self.stages.lock().unwrap().push(result.kind);
+            }
+        }
+
+        let recorder = Recorder::default();
+        let results = check_mersenne_candidate_with_reporter(31, CheckLevel::LucasLehmer, &recorder);
+
+        let kinds: Vec<CheckKind> = results.iter().map(|r| r.kind).collect();
+        assert_eq!(*recorder.stages.lock().unwrap(), kinds);
+        assert_eq!(*recorder.last_progress.lock().unwrap(), Some((29, 29)));
+    }
+
+    #[test]
+    fn test_reporter_can_stop_the_check() {
+        use std::sync::atomic::AtomicU64;
+
+        /// Asks to stop once `limit` Lucas-Lehmer iterations or stages are seen
+        struct StopAfter {
+            limit: u64,
+            seen: AtomicU64,
+        }
+
+        impl ProgressReporter for StopAfter {
+            fn on_progress(&self, _kind: CheckKind, current: u64, _total: u64) {
+                self.seen.fetch_max(current, Ordering::Relaxed);
+            }
+
+            fn on_stage_complete(&self, _result: &CheckResult) {
+                if self.limit == 0 {
+                    self.seen.store(1, Ordering::Relaxed);
+                }
+            }
+
+            fn should_stop(&self) -> bool {
+                self.seen.load(Ordering::Relaxed) >= self.limit.max(1)
+            }
+        }
+
+        // Stopping after the first stage runs nothing more
+        let reporter = StopAfter { limit: 0, seen: AtomicU64::new(0) };
+        let results = check_mersenne_candidate_with_reporter(521, CheckLevel::LucasLehmer, &reporter);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].kind, CheckKind::ExponentPrime);
+
+        // Stopping part way through the Lucas-Lehmer test leaves it inconclusive
+        let reporter = StopAfter { limit: 100, seen: AtomicU64::new(0) };
+        let results = check_mersenne_candidate_with_reporter(521, CheckLevel::LucasLehmer, &reporter);
+        let last = results.last().unwrap();
+        assert_eq!(last.kind, CheckKind::LucasLehmer);
+        assert!(!last.passed && last.inconclusive);
+        assert!(last.message.starts_with("Lucas-Lehmer test stopped after"), "{}", last.message);
+    }
+
+    #[test]
+    fn test_exponent_newtype() {
+        assert!(Exponent::new(0).is_none());
+        assert!(Exponent::new(1).is_none());
+        assert_eq!(Exponent::new(12).map(Exponent::get), Some(12));
+        assert!(Exponent::new_prime(12).is_none());
+
+        let p = Exponent::new_prime(7).unwrap();
+        assert_eq!(p.to_string(), "M7");
+        assert_eq!(p.mersenne_number(), BigUint::from(127u32));
+        assert_eq!(u64::from(p), 7);
+
+        // Typed and raw exponents run the same pipeline
+        let typed = check_mersenne_candidate(p, CheckLevel::LucasLehmer);
+        let raw = check_mersenne_candidate(7, CheckLevel::LucasLehmer);
+        assert_eq!(typed.len(), raw.len());
+        assert!(typed.iter().all(|r| r.passed));
+
+        // So do the individual tests
+        let p = Exponent::new_prime(89).unwrap();
+        assert!(lucas_lehmer_test(p));
+        assert_eq!(lucas_lehmer_residue(p), (true, 0));
+        assert_eq!(check_small_factors(Exponent::new_prime(11).unwrap(), 1000), Some(23));
+        assert_eq!(plan_check(p, CheckLevel::LucasLehmer), plan_check(89, CheckLevel::LucasLehmer));
+    }
+
+    #[test]
+    fn test_pepin() {
+        // F0 through F4 are the only known Fermat primes
+        for n in 0..=4 {
+            assert!(pepin_test(n), "F{} should be prime", n);
+        }
+        assert!(!pepin_test(5)); // F5 = 641 * 6700417
+        assert!(!pepin_test(6)); // F6 = 274177 * 67280421310721
+
+        // F_n = 1·2^(2^n) + 1 is a Proth number
+        for n in 1..=8 {
+            assert_eq!(pepin_test(n), proth_test(1, 1 << n), "F{}", n);
+        }
+    }
+
+    #[test]
+    fn test_llr_known_riesel_primes() {
+        // n >= 2 with 3·2^n - 1 prime (OEIS A002235)
+        let prime_n = [2u64, 3, 4, 6, 7, 11, 18, 34, 38, 43, 55, 64, 76, 94, 103, 143, 206, 216];
+        for n in 2..=220 {
+            assert_eq!(llr_test(3, n), prime_n.contains(&n), "3·2^{} - 1", n);
+        }
+
+        // k = 1 is the Lucas-Lehmer test
+        for p in 2..=130 {
+            assert_eq!(llr_test(1, p), lucas_lehmer_test(p), "M{}", p);
+        }
+
+        // Even k is folded into the power of two
+        assert_eq!(llr_test(6, 17), llr_test(3, 18));
+    }
+
+    #[test]
+    fn test_llr_agrees_with_baillie_psw() {
+        for k in (1u64..=45).step_by(2) {
+            for n in 2..=80 {
+                if n < 64 && k >= 1 << n {
+                    continue;
+                }
+                let big_n = (BigUint::from(k) << n) - BigUint::one();
+                assert_eq!(llr_test(k, n), baillie_psw(&big_n), "{}·2^{} - 1", k, n);
+            }
+        }
+    }
+
+    #[test]
+    fn test_proth_known_primes() {
+        assert!(proth_test(3, 2)); // 13
+
+        // n >= 2 with 3·2^n + 1 prime (OEIS A002253)
+        let prime_n = [2u64, 5, 6, 8, 12, 18, 30, 36, 41, 66, 189, 201, 209];
+        for n in 2..=210 {
+            assert_eq!(proth_test(3, n), prime_n.contains(&n), "3·2^{} + 1", n);
+        }
+
+        for k in (1u64..=45).step_by(2) {
+            for n in 1..=80 {
+                if n < 64 && k >= 1 << n {
+                    continue;
+                }
+                let big_n = (BigUint::from(k) << n) + BigUint::one();
+                assert_eq!(proth_test(k, n), baillie_psw(&big_n), "{}·2^{} + 1", k, n);
+            }
+        }
+    }
+
+    #[test]
+    fn test_miller_rabin_with_bases() {
+        let start = Instant::now();
+        let timeout = Duration::from_secs(30);
+        let bases = |list: &[u32]| list.iter().map(|&a| BigUint::from(a)).collect::<Vec<_>>();
+
+        // M11 = 2047 = 23 * 89 is the smallest strong pseudoprime to base 2
+        assert!(miller_rabin_test_with_bases(11, &bases(&[2]), start, timeout));
+        assert!(!miller_rabin_test_with_bases(11, &bases(&[2, 3]), start, timeout));
+        // Bases congruent to 0, 1 or -1 never find a witness
+        assert!(miller_rabin_test_with_bases(4, &bases(&[1, 14, 15, 16]), start, timeout));
+        assert!(!miller_rabin_test_with_bases(4, &bases(&[4]), start, timeout));
+        // No bases, no evidence against primality
+        assert!(miller_rabin_test_with_bases(11, &[], start, timeout));
+
+        for &p in known_mersenne_primes().iter().take_while(|&&p| p <= 127) {
+            assert!(miller_rabin_test_with_bases(p, &bases(&[2, 3, 5, 7]), start, timeout), "M{}", p);
+        }
+        for p in [1u64, 4, 23, 29, 67] {
+            assert!(!miller_rabin_test_with_bases(p, &bases(&[2, 3, 5, 7]), start, timeout), "M{}", p);
+        }
+        assert!(!miller_rabin_test_with_bases(31, &bases(&[2]), start, Duration::ZERO));
+    }
+
+    #[test]
+    fn test_next_prime() {
+        assert_eq!(next_prime(0), 2);
+        assert_eq!(next_prime(1), 2);
+        assert_eq!(next_prime(2), 3);
+        assert_eq!(next_prime(3), 5);
+        assert_eq!(next_prime(127), 131);
+        assert_eq!(next_prime(1_000_000_000), 1_000_000_007);
+        assert_eq!(next_prime(u64::MAX - 80), 18_446_744_073_709_551_557);
+
+        // Stepping with next_prime visits exactly the sieved primes
+        let mut stepped = vec![next_prime(0)];
+        while *stepped.last().unwrap() < 10_000 {
+            stepped.push(next_prime(*stepped.last().unwrap()));
+        }
+        stepped.pop();
+        assert_eq!(stepped, prime_sieve(10_000).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    #[should_panic(expected = "no prime above")]
+    fn test_next_prime_past_largest_u64_prime() {
+        next_prime(18_446_744_073_709_551_557);
+    }
+
+    #[test]
+    fn test_prime_sieve() {
+        let limits = [0u64, 1, 2, 3, 100, SIEVE_SEGMENT_LEN - 1, SIEVE_SEGMENT_LEN, SIEVE_SEGMENT_LEN + 1, 600_000];
+        for limit in limits {
+            let sieved: Vec<u64> = prime_sieve(limit).collect();
+            let expected: Vec<u64> = (0..=limit).filter(|&n| is_prime(n)).collect();
+            assert_eq!(sieved, expected, "limit {}", limit);
+        }
+
+        // Several segments in, the base primes still cover the tail
+        let tail: Vec<u64> = prime_sieve(2_000_000).skip_while(|&p| p < 1_999_000).collect();
+        let expected: Vec<u64> = (1_999_000..=2_000_000u64).filter(|&n| is_prime(n)).collect();
+        assert_eq!(tail, expected);
+        assert_eq!(prime_sieve(2_000_000).count(), 148_933);
+    }
+
+    #[test]
+    fn test_is_prime_deterministic() {
+        for n in 0..100_000u64 {
+            assert_eq!(is_prime_deterministic(n), is_prime(n), "{}", n);
+        }
+        for n in 999_990_000u64..1_000_010_000 {
+            assert_eq!(is_prime_deterministic(n), is_prime(n), "{}", n);
+        }
+
+        // Strong pseudoprimes to all prime bases up to 7, 17 and 23 respectively
+        for n in [3_215_031_751u64, 341_550_071_728_321, 3_825_123_056_546_413_051] {
+            assert!(!is_prime_deterministic(n), "{}", n);
+        }
+        assert!(is_prime_deterministic(18_446_744_073_709_551_557));
+        assert!(is_prime_deterministic(407_521));
+        assert!(!is_prime_deterministic(407_521 * 407_521));
+    }
+
+    #[test]
+    fn test_prescreen_reports_factor() {
+        assert_eq!(prescreen(0), PrescreenResult::ExponentTooSmall);
+        assert_eq!(prescreen(1), PrescreenResult::ExponentTooSmall);
+        assert_eq!(prescreen(2), PrescreenResult::ExponentPrime);
+        assert_eq!(prescreen(127), PrescreenResult::ExponentPrime);
+        assert_eq!(prescreen(32), PrescreenResult::ExponentComposite { factor: 2 });
+        assert_eq!(prescreen(49), PrescreenResult::ExponentComposite { factor: 7 });
+        // Large semiprime exponent that goes through the Miller-Rabin path
+        assert_eq!(
+            prescreen(1_000_003 * 1_000_033),
+            PrescreenResult::ExponentComposite { factor: 1_000_003 }
+        );
+
+        let results = check_mersenne_candidate(49, CheckLevel::PreScreen);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].message, "Exponent is not prime (divisible by 7)");
+    }
+
+    #[test]
+    fn test_prime_cache_shared_across_batch() {
+        let cache = Arc::new(PrimeCache::new());
+        let config = CheckConfig {
+            prime_cache: Some(Arc::clone(&cache)),
+            ..CheckConfig::default()
+        };
+
+        for p in [31, 61, 89, 107, 127] {
+            let cached = check_mersenne_candidate_with_config(p, CheckLevel::TrialFactoring, &config);
+            let uncached = check_mersenne_candidate(p, CheckLevel::TrialFactoring);
+            let passed = |results: &[CheckResult]| results.iter().map(|r| r.passed).collect::<Vec<_>>();
+            assert_eq!(passed(&cached), passed(&uncached));
+        }
+
+        // Each exponent is looked up by both the pre-screen and trial factoring
+        assert!(cache.hits() >= 5);
+        assert!(!cache.is_empty());
+
+        let misses = cache.misses();
+        check_mersenne_candidate_with_config(127, CheckLevel::TrialFactoring, &config);
+        assert_eq!(cache.misses(), misses, "a repeated candidate should be served from the cache");
+    }
+
+    #[test]
+    fn test_iterations_remaining() {
+        assert_eq!(lucas_lehmer_iterations(2), 0);
+        assert_eq!(lucas_lehmer_iterations(31), 29);
+        assert_eq!(iterations_remaining(31, 0), 29);
+        assert_eq!(iterations_remaining(31, 29), 0);
+        assert_eq!(iterations_remaining(31, 40), 0);
+
+        assert!(is_valid_checkpoint_progress(31, 0));
+        assert!(is_valid_checkpoint_progress(31, 29));
+        assert!(!is_valid_checkpoint_progress(31, 30));
+        assert!(!is_valid_checkpoint_progress(2, 1));
+    }
+
+    #[test]
+    fn test_residues_equal_mod_mp() {
+        let p = 13;
+        let mp = (BigUint::one() << p) - BigUint::one();
+
+        assert!(residues_equal_mod_mp(&mp, &BigUint::zero(), p));
+        assert!(residues_equal_mod_mp(&(&mp + 5u32), &BigUint::from(5u32), p));
+        assert!(residues_equal_mod_mp(&(&mp * &mp), &BigUint::zero(), p));
+        assert!(!residues_equal_mod_mp(&BigUint::one(), &BigUint::zero(), p));
+    }
+
+    #[test]
+    fn test_cross_check_backend() {
+        /// Returns M_p + `offset`, which is wrong unless `offset` is 0
+        #[derive(Debug)]
+        struct Offset(u32);
+        impl PrimalityBackend for Offset {
+            fn name(&self) -> &str {
+                "offset"
+            }
+            fn lucas_lehmer_residue(&self, p: u64) -> Result<BigUint, PrimalityError> {
+                let s = SchoolbookBackend.lucas_lehmer_residue(p)?;
+                Ok(s + (BigUint::one() << p) - 1u32 + self.0)
+            }
+        }
+
+        // An unreduced but correct residue agrees
+        let config = CheckConfig::new().cross_check_backend(Arc::new(Offset(0)));
+        let results = check_mersenne_candidate_with_config(127, CheckLevel::LucasLehmer, &config);
+        let last = results.last().unwrap();
+        assert!(last.passed && !last.inconclusive);
+        assert!(last.message.contains("confirmed by the offset backend"), "{}", last.message);
+
+        let config = CheckConfig::new().cross_check_backend(Arc::new(Offset(1)));
+        let results = check_mersenne_candidate_with_config(127, CheckLevel::LucasLehmer, &config);
+        let last = results.last().unwrap();
+        assert_eq!(last.kind, CheckKind::LucasLehmer);
+        assert!(!last.passed && last.inconclusive);
+        assert!(last.message.contains("schoolbook gives res64 0000000000000000, offset gives 0000000000000001"), "{}", last.message);
+
+        let backend = CrossChecked { primary: SchoolbookBackend, secondary: Offset(2) };
+        assert!(matches!(
+            backend.lucas_lehmer_residue(11),
+            Err(PrimalityError::BackendsDisagree { exponent: 11, primary_res64: 1736, secondary_res64: 1738, .. })
+        ));
+        assert!(matches!(backend.lucas_lehmer_residue(1), Err(PrimalityError::InvalidExponent(1))));
+    }
+
+    #[test]
+    fn test_trial_factor_result_carries_factor() {
+        let results = check_mersenne_candidate(29, CheckLevel::TrialFactoring);
+        let last = results.last().unwrap();
+        assert_eq!(last.kind, CheckKind::TrialFactor);
+        assert_eq!(last.factor, Some(BigUint::from(233u32)));
+
+        let results = check_mersenne_candidate(31, CheckLevel::TrialFactoring);
+        assert!(results.iter().all(|r| r.factor.is_none()));
+    }
+
+    #[test]
+    fn test_check_small_factors_sieved() {
+        // The smallest prime q = 2kp + 1 up to the limit with 2^p ≡ 1 (mod q)
+        let smallest_factor = |p: u64, limit: u64| {
+            (1..)
+                .map(|k| 2 * k * p + 1)
+                .take_while(|&q| q <= limit)
+                .find(|&q| is_prime(q) && mod_pow_u64(2, p, q) == 1 && (p >= 64 || q != (1u64 << p) - 1))
+        };
+        for p in primes_up_to(400) {
+            assert_eq!(check_small_factors_sieved(p, 200_000), smallest_factor(p, 200_000), "M{}", p);
+        }
+        for p in [11u64, 29, 43, 89, 113, 127, 1277] {
+            assert_eq!(
+                check_small_factors_sieved(p, 1_000_000).is_some(),
+                check_small_factors(p, 1_000_000).is_some(),
+                "M{}",
+                p
+            );
+        }
+
+        // M_p itself is not a factor, and sieving must not cross off q = r
+        assert_eq!(check_small_factors_sieved(7, 1000), None);
+        assert_eq!(check_small_factors_sieved(3, 100), None);
+        assert_eq!(check_small_factors_sieved(23, 100), Some(47));
+
+        // Factors beyond the sieving primes, and exponents that are not prime
+        assert_eq!(check_small_factors_sieved(47, 10_000_000), Some(2351));
+        assert_eq!(check_small_factors_sieved(1_000_133, 1 << 36), Some(8_007_064_799));
+        assert_eq!(check_small_factors_sieved(1_000_081, u64::MAX >> 20), Some(26_154_118_313));
+        assert_eq!(check_small_factors_sieved(15, 1_000_000), None);
+        assert_eq!(check_small_factors_sieved(0, u64::MAX), None);
+        assert_eq!(check_small_factors_sieved((1 << 61) - 1, u64::MAX), None);
+    }
+
+    #[test]
+    fn test_trial_factor_to_bits() {
+        // Consecutive bit levels cover exactly what one search to the top does
+        for p in [11u64, 23, 29, 37, 43, 47, 53, 59, 71, 73, 79, 113, 1_000_081] {
+            let whole = check_small_factors_sieved(p, (1 << 36) - 1);
+            let by_level = (0..36).find_map(|bits| trial_factor_to_bits(p, bits, bits + 1));
+            assert_eq!(by_level, whole, "M{}", p);
+            if let Some(q) = whole {
+                let level = u64::BITS - q.leading_zeros();
+                assert_eq!(trial_factor_to_bits(p, level - 1, level), Some(q), "M{}", p);
+                assert_eq!(trial_factor_to_bits(p, 0, level - 1), None, "M{}", p);
+            }
+        }
+
+        // M37 = 223 * 616318177: a range above 223 finds the second factor
+        assert_eq!(trial_factor_to_bits(37, 8, 30), Some(616_318_177));
+
+        assert_eq!(trial_factor_to_bits(11, 5, 5), None);
+        assert_eq!(trial_factor_to_bits(11, 6, 4), None);
+        assert_eq!(trial_factor_to_bits(15, 0, 20), None);
+        assert_eq!(trial_factor_to_bits((1 << 61) - 1, 0, 200), None);
+    }
+
+    #[test]
+    fn test_algebraic_factor_for_composite_exponent() {
+        assert_eq!(mersenne_algebraic_factor(1), None);
+        assert_eq!(mersenne_algebraic_factor(31), None);
+        assert_eq!(mersenne_algebraic_factor(22), Some((2, BigUint::from(3u32))));
+
+        // PreScreen alone only rejects the exponent
+        let results = check_mersenne_candidate(121, CheckLevel::PreScreen);
+        assert_eq!(results.len(), 1);
+
+        // M11 = 23 * 89 and 11 divides 121, so 23 divides M121
+        let results = check_mersenne_candidate(121, CheckLevel::TrialFactoring);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1].kind, CheckKind::TrialFactor);
+        assert!(!results[1].passed);
+        assert_eq!(results[1].factor, Some(BigUint::from(23u32)));
+
+        // M2 = 3 has no factor of the form 2k*2 + 1, so M2 itself is reported
+        let results = check_mersenne_candidate(22, CheckLevel::LucasLehmer);
+        assert_eq!(results[1].factor, Some(BigUint::from(3u32)));
+    }
+
+    #[test]
+    fn test_sample_candidates() {
+        let sample = sample_candidates(100, 1_000, 20, 7);
+        assert_eq!(sample.len(), 20);
+        assert!(sample.windows(2).all(|w| w[0] < w[1]));
+        assert!(sample.iter().all(|&p| (100..1_000).contains(&p) && is_prime(p)));
+        assert_eq!(sample, sample_candidates(100, 1_000, 20, 7));
+        assert_ne!(sample, sample_candidates(100, 1_000, 20, 8));
+
+        // Fewer primes than requested: take them all
+        assert_eq!(sample_candidates(10, 30, 100, 1), vec![11, 13, 17, 19, 23, 29]);
+        assert!(sample_candidates(30, 10, 5, 1).is_empty());
+
+        // Large range goes through rejection sampling
+        let large = sample_candidates(1 << 40, 1 << 41, 5, 3);
+        assert_eq!(large.len(), 5);
+        assert!(large.iter().all(|&p| is_prime(p)));
+        assert_eq!(large, sample_candidates(1 << 40, 1 << 41, 5, 3));
+    }
+
+    #[test]
+    fn test_known_mersenne_prime_table() {
+        assert_eq!(KNOWN_MERSENNE_PRIME_COUNT, 52);
+        assert_eq!(largest_known_mersenne_exponent(), 136_279_841);
+        assert!(KNOWN_MERSENNE_PRIME_EXPONENTS.windows(2).all(|w| w[0] < w[1]));
+        assert!(KNOWN_MERSENNE_PRIME_EXPONENTS.iter().all(|&p| is_prime(p)));
+        for &p in KNOWN_MERSENNE_PRIME_EXPONENTS.iter().take_while(|&&p| p <= 607) {
+            assert!(lucas_lehmer_test(p), "M{} should be prime", p);
+        }
+        assert_eq!(known_mersenne_primes(), KNOWN_MERSENNE_PRIME_EXPONENTS);
+
+        // Every prime exponent below 1000 is in exactly one of the two lists
+        let composites = known_composite_exponents();
+        assert!(composites.windows(2).all(|w| w[0] < w[1]));
+        for p in primes_up_to(999) {
+            assert_ne!(composites.contains(&p), known_mersenne_primes().contains(&p), "p = {}", p);
+        }
+        for &p in composites {
+            assert!(!lucas_lehmer_test(p), "M{} should be composite", p);
+        }
+    }
+
+    #[test]
+    fn test_verify_against_known() {
+        let report = verify_against_known(3000);
+        assert!(report.passed(), "{:?}", report.mismatches);
+        assert_eq!(report.max_exponent, 3000);
+        assert_eq!(report.primes_checked, 17);
+        // The 154 listed composites, plus samples from the primes in 1000..=3000
+        assert_eq!(report.composites_checked, 154 + VERIFICATION_COMPOSITE_SAMPLES);
+
+        let report = verify_against_known(1);
+        assert!(report.passed());
+        assert_eq!(report.primes_checked + report.composites_checked, 0);
+    }
+
+    #[test]
+    fn test_load_known_primes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("primes.json");
+        fs::write(&path, "[\n  2, 3, 5,\n  7\n]\n").unwrap();
+        assert_eq!(load_known_primes(&path).unwrap(), vec![2, 3, 5, 7]);
+
+        fs::write(&path, "[]").unwrap();
+        assert!(load_known_primes(&path).unwrap().is_empty());
+        for bad in ["2, 3", "[2, three]", "[2,]", "{\"primes\": [2]}"] {
+            fs::write(&path, bad).unwrap();
+            let err = load_known_primes(&path).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", bad);
+        }
+        assert!(load_known_primes(dir.path().join("missing.json")).is_err());
+    }
+
+    #[test]
+    fn test_lucas_lehmer_with_fallback() {
+        let timeout = Duration::from_secs(60);
+        let cancel = AtomicBool::new(false);
+        let result = lucas_lehmer_with_fallback(89, &cancel, timeout);
+        assert_eq!(result.kind, CheckKind::LucasLehmer);
+        assert!(result.passed);
+
+        cancel.store(true, Ordering::Relaxed);
+        let result = lucas_lehmer_with_fallback(127, &cancel, timeout);
+        assert_eq!(result.kind, CheckKind::MillerRabin);
+        assert!(result.passed);
+        assert_eq!(
+            result.message,
+            "Lucas-Lehmer cancelled after 0/125 iterations; probably prime by a base-3 Fermat test"
+        );
+
+        let result = lucas_lehmer_with_fallback(67, &cancel, timeout);
+        assert_eq!(result.kind, CheckKind::MillerRabin);
+        assert!(!result.passed);
+
+        // Out of time before the Fermat test starts
+        let result = lucas_lehmer_with_fallback(127, &cancel, Duration::ZERO);
+        assert_eq!(result.kind, CheckKind::LucasLehmer);
+        assert!(result.inconclusive);
+        assert!(!result.passed);
+        assert_eq!(
+            result.message,
+            "Lucas-Lehmer cancelled after 0/125 iterations; the Fermat test ran out of time (inconclusive)"
+        );
+    }
+
+    #[test]
+    fn test_is_mersenne_number() {
+        assert_eq!(is_mersenne_number(0), None);
+        assert_eq!(is_mersenne_number(1), Some(1));
+        assert_eq!(is_mersenne_number(127), Some(7));
+        assert_eq!(is_mersenne_number(128), None);
+        assert_eq!(is_mersenne_number(126), None);
+        assert_eq!(is_mersenne_number(u64::MAX), Some(64));
+    }
+
+    #[test]
+    fn test_stage_bitmask() {
+        assert_eq!(stage_bitmask(&[]), 0);
+        assert_eq!(stage_bitmask(&check_mersenne_candidate(127, CheckLevel::Probabilistic)), 0b10111);
+        assert_eq!(stage_bitmask(&check_mersenne_candidate(127, CheckLevel::PMinusOne)), 0b10011);
+        assert_eq!(stage_bitmask(&check_mersenne_candidate(29, CheckLevel::TrialFactoring)), 0b0001);
+        assert_eq!(stage_bitmask(&check_mersenne_candidate(22, CheckLevel::TrialFactoring)), 0);
+    }
+
+    #[test]
+    fn test_summarize() {
+        let start = Instant::now();
+        let results = check_mersenne_candidate(2203, CheckLevel::LucasLehmer);
+        let wall = start.elapsed();
+        let summary = summarize(2203, &results);
+
+        // The stages account for nearly all of the run
+        assert_eq!(summary.stage_times.len(), results.len());
+        assert_eq!(summary.stage_times.iter().map(|&(_, t)| t).sum::<Duration>(), summary.total_time);
+        assert!(summary.total_time <= wall);
+        assert!(summary.total_time.as_secs_f64() >= 0.8 * wall.as_secs_f64(), "{:?} of {:?}", summary.total_time, wall);
+        assert_eq!(summary.stage_times.last().unwrap().0, CheckKind::LucasLehmer);
+        assert_eq!(summary.eliminated_by, None);
+        assert_eq!(summary.peak_memory_bytes, estimate_resources(2203, CheckLevel::LucasLehmer).bytes_required);
+
+        let summary = summarize(2205, &check_mersenne_candidate(2205, CheckLevel::LucasLehmer));
+        assert_eq!(summary.eliminated_by, Some(CheckKind::ExponentPrime));
+        assert_eq!(summarize(0, &[]).total_time, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_miller_rabin_resumable() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoint = dir.path().join("mr.ckpt");
+
+        // Resume M127 after three recorded rounds
+        MillerRabinCheckpoint { exponent: 127, rounds_passed: 3 }.save(&checkpoint).unwrap();
+        assert_eq!(
+            MillerRabinCheckpoint::load(&checkpoint).unwrap(),
+            MillerRabinCheckpoint { exponent: 127, rounds_passed: 3 }
+        );
+        assert!(miller_rabin_test_resumable(127, 6, &checkpoint).unwrap());
+        assert!(!checkpoint.exists());
+
+        assert!(!miller_rabin_test_resumable(67, 4, &checkpoint).unwrap());
+        for p in [3, 5, 7, 13] {
+            assert!(miller_rabin_test_resumable(p, 8, &checkpoint).unwrap(), "M{} is prime", p);
+        }
+
+        // A checkpoint for another exponent is rejected and left alone
+        MillerRabinCheckpoint { exponent: 61, rounds_passed: 1 }.save(&checkpoint).unwrap();
+        assert!(miller_rabin_test_resumable(127, 6, &checkpoint).is_err());
+        assert!(checkpoint.exists());
+    }
+
+    #[test]
+    fn test_lucas_lehmer_test_with_progress() {
+        let mut updates = Vec::new();
+        assert!(lucas_lehmer_test_with_progress(4423, |current, total| updates.push((current, total))));
+        assert!(updates.len() >= 200 && updates.len() <= 201, "{} updates", updates.len());
+        assert!(updates.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(updates.last(), Some(&(4421, 4421)));
+
+        updates.clear();
+        assert!(!lucas_lehmer_test_with_progress(11, |current, total| updates.push((current, total))));
+        assert_eq!(updates, (1..=9).map(|i| (i, 9)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_lucas_lehmer_test_with_timeout() {
+        assert_eq!(lucas_lehmer_test_with_timeout(521, Duration::from_secs(60)), Some(true));
+        assert_eq!(lucas_lehmer_test_with_timeout(523, Duration::from_secs(60)), Some(false));
+        assert_eq!(lucas_lehmer_test_with_timeout(86243, Duration::from_millis(50)), None);
+
+        let config = CheckConfig {
+            lucas_lehmer_timeout: Some(Duration::ZERO),
+            ..CheckConfig::default()
+        };
+        let results = check_mersenne_candidate_with_config(521, CheckLevel::LucasLehmer, &config);
+        let last = results.last().unwrap();
+        assert_eq!(last.kind, CheckKind::LucasLehmer);
+        assert!(last.inconclusive && !last.passed);
+        assert!(last.message.contains("timed out after 0/519"), "{}", last.message);
+
+        // A budget that runs out part way stops the loop there, not at the end
+        let start = Instant::now();
+        let stopped = lucas_lehmer_until(86243, &NoProgress, Some(start + Duration::from_millis(500)));
+        let elapsed = start.elapsed();
+        match stopped {
+            Err(LucasLehmerStop::Stopped(completed)) => {
+                assert!(completed > 0 && completed < lucas_lehmer_iterations(86243), "{}", completed);
+            }
+            other => panic!("expected a timeout, got {:?}", other),
+        }
+        assert!(elapsed < Duration::from_secs(30), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn test_mersenne_digit_count() {
+        for p in 0..3000u64 {
+            let m = (BigUint::one() << p) - BigUint::one();
+            assert_eq!(mersenne_digit_count(p), m.to_string().len() as u64, "p = {}", p);
+        }
+        assert_eq!(mersenne_digit_count(127), 39);
+        assert_eq!(mersenne_digit_count(521), 157);
+        assert_eq!(mersenne_digit_count(136_279_841), 41_024_320);
+
+        // The last six convergent denominators of log10 2 below 2^64, where
+        // p log10 2 comes closest to an integer, and the top of the range
+        assert_eq!(mersenne_digit_count(100_946_106_243_339_069), 30_387_805_924_728_145);
+        assert_eq!(mersenne_digit_count(112_404_439_328_411_815), 33_837_107_883_644_047);
+        assert_eq!(mersenne_digit_count(550_563_863_556_986_329), 165_736_237_459_304_329);
+        assert_eq!(mersenne_digit_count(662_968_302_885_398_144), 199_573_345_342_948_376);
+        assert_eq!(mersenne_digit_count(1_876_500_469_327_782_617), 564_882_928_145_201_079);
+        assert_eq!(mersenne_digit_count(4_415_969_241_540_963_378), 1_329_339_201_633_350_534);
+        assert_eq!(mersenne_digit_count(u64::MAX), 5_553_023_288_523_357_132);
+    }
+
+    #[test]
+    fn test_wagstaff_prp_test() {
+        // Exponents of the Wagstaff primes below 400 (OEIS A000978)
+        let wagstaff = [3, 5, 7, 11, 13, 17, 19, 23, 31, 43, 61, 79, 101, 127, 167, 191, 199, 313, 347];
+        for p in 0..400u64 {
+            assert_eq!(wagstaff_prp_test(p), wagstaff.contains(&p), "p = {}", p);
+        }
+    }
+
+    #[test]
+    fn test_check_level_full() {
+        let kinds = |results: &[CheckResult]| results.iter().map(|r| r.kind).collect::<Vec<_>>();
+        let every_stage =
+            [CheckKind::ExponentPrime, CheckKind::TrialFactor, CheckKind::PMinusOne, CheckKind::MillerRabin, CheckKind::LucasLehmer];
+
+        let results = check_mersenne_candidate(127, CheckLevel::Full);
+        assert_eq!(kinds(&results), every_stage);
+        assert!(results.iter().all(|r| r.passed), "{:?}", results);
+        assert_eq!(results[1].message, "No small factors found up to 2^32");
+        assert!(results[2].message.contains("B2 = 20000"), "{}", results[2].message);
+        assert_eq!(results[4].message, "Passed Lucas-Lehmer test (definitive), res64 0000000000000000, 0 error(s) corrected");
+
+        // No composite M_p below 2^64 needs factors past half its bits
+        let results = check_mersenne_candidate(31, CheckLevel::Full);
+        assert_eq!(kinds(&results), every_stage);
+        assert_eq!(results[1].message, "No small factors found up to 1000000");
+
+        // 193707721 divides M67, beyond the default limit but below 2^32
+        assert_eq!(check_mersenne_candidate(67, CheckLevel::TrialFactoring)[1].factor, None);
+        let results = check_mersenne_candidate(67, CheckLevel::Full);
+        let last = results.last().unwrap();
+        assert_eq!(last.kind, CheckKind::TrialFactor);
+        assert_eq!(last.factor, Some(BigUint::from(193_707_721u32)));
+
+        // The other levels keep their messages
+        let results = check_mersenne_candidate(127, CheckLevel::LucasLehmer);
+        assert_eq!(results[2].message, "No factor found by P-1 (B1 = 1000)");
+        assert_eq!(results[4].message, "Passed Lucas-Lehmer test (definitive)");
+
+        let full = estimate_resources(86_243, CheckLevel::Full);
+        let lucas_lehmer = estimate_resources(86_243, CheckLevel::LucasLehmer);
+        assert!(full.estimated_duration > lucas_lehmer.estimated_duration);
+        assert_eq!(full.bytes_required, lucas_lehmer.bytes_required);
+    }
+
+    #[test]
+    fn test_max_memory_bytes() {
+        // M521 works on 576-byte values
+        let tight = CheckConfig::new().max_memory_bytes(575);
+        assert!(CheckConfig::new().max_memory_bytes(576).check_memory(521, CheckLevel::LucasLehmer).is_ok());
+        assert!(tight.check_memory(521, CheckLevel::TrialFactoring).is_ok());
+        let error = tight.check_memory(521, CheckLevel::LucasLehmer).unwrap_err();
+        assert!(matches!(
+            error,
+            PrimalityError::WouldExceedMemory { exponent: 521, required_bytes: 576, max_bytes: 575 }
+        ));
+        assert_eq!(io::Error::from(error).kind(), io::ErrorKind::OutOfMemory);
+
+        // The pipeline runs the cheap stages and stops before P-1
+        let results = check_mersenne_candidate_with_config(521, CheckLevel::LucasLehmer, &tight);
+        let kinds: Vec<CheckKind> = results.iter().map(|r| r.kind).collect();
+        assert_eq!(kinds, [CheckKind::ExponentPrime, CheckKind::TrialFactor, CheckKind::PMinusOne]);
+        let last = results.last().unwrap();
+        assert!(last.inconclusive && !last.passed);
+        assert!(last.message.contains("limit of 575"), "{}", last.message);
+
+        let results = check_mersenne_candidate_with_config(521, CheckLevel::TrialFactoring, &tight);
+        assert!(results.iter().all(|r| r.passed));
+    }
+
+    #[test]
+    fn test_lucas_lehmer_resumable() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoint = dir.path().join("ll.ckpt");
+
+        // Stop M2203 and M2207 part way through, then resume from the saved state
+        for p in [2203u64, 2207] {
+            let mut s = BigUint::from(4u32);
+            for _ in 0..1500 {
+                s = square_and_subtract_two_mod_mp(&s, p);
+            }
+            let state = LucasLehmerCheckpoint { exponent: p, iteration: 1500, value: s };
+            state.save(&checkpoint).unwrap();
+            assert_eq!(LucasLehmerCheckpoint::load(&checkpoint).unwrap(), state);
+
+            assert_eq!(lucas_lehmer_test_resumable(p, &checkpoint, 100).unwrap(), lucas_lehmer_test(p));
+            assert!(!checkpoint.exists());
+        }
+
+        // Fresh runs reach the same verdict and leave no checkpoint behind
+        for p in [2u64, 3, 11, 127, 521] {
+            assert_eq!(lucas_lehmer_test_resumable(p, &checkpoint, 7).unwrap(), lucas_lehmer_test(p), "M{}", p);
+            assert!(!checkpoint.exists());
+        }
+
+        // Checkpoints for another exponent or past the last iteration are rejected
+        LucasLehmerCheckpoint { exponent: 61, iteration: 10, value: BigUint::from(4u32) }
+            .save(&checkpoint)
+            .unwrap();
+        assert!(lucas_lehmer_test_resumable(127, &checkpoint, 10).is_err());
+        assert!(checkpoint.exists());
+        LucasLehmerCheckpoint { exponent: 127, iteration: 126, value: BigUint::from(4u32) }
+            .save(&checkpoint)
+            .unwrap();
+        assert!(lucas_lehmer_test_resumable(127, &checkpoint, 10).is_err());
+    }
+
+    #[test]
+    fn test_partition_work() {
+        assert!(partition_work(10, 10, 3).is_empty());
+        assert!(partition_work(10, 20, 0).is_empty());
+        assert_eq!(partition_work(10, 13, 8), vec![(10, 11), (11, 12), (12, 13)]);
+
+        let units = partition_work(1_000_000, 100_000_000, 16);
+        assert_eq!(units.len(), 16);
+        assert_eq!(units[0].0, 1_000_000);
+        assert_eq!(units[15].1, 100_000_000);
+        assert!(units.windows(2).all(|w| w[0].1 == w[1].0));
+
+        // Each unit carries about the same modelled cost
+        let cost = |(a, b): (u64, u64)| {
+            (b as f64).powf(LL_COST_EXPONENT + 1.0) - (a as f64).powf(LL_COST_EXPONENT + 1.0)
+        };
+        let costs: Vec<f64> = units.iter().map(|&u| cost(u)).collect();
+        let mean = costs.iter().sum::<f64>() / costs.len() as f64;
+        assert!(costs.iter().all(|c| (c / mean - 1.0).abs() < 1e-3));
+    }
+
+    #[test]
+    fn test_known_factors() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "# factors of small Mersenne numbers").unwrap();
+        writeln!(file, "M11: 23, 89").unwrap();
+        writeln!(file).unwrap();
+        writeln!(file, "67: 2, 761838257287").unwrap();
+        writeln!(file, "M67: 193707721").unwrap();
+        let known_factors = load_known_factors(file.path()).unwrap();
+        assert_eq!(known_factors[&11], vec![BigUint::from(23u32), BigUint::from(89u32)]);
+        assert_eq!(known_factors[&67].len(), 3);
+
+        // 2 does not divide M67 and is skipped; the next factor short-circuits LL
+        let config = CheckConfig { known_factors, ..CheckConfig::default() };
+        let results = check_mersenne_candidate_with_config(67, CheckLevel::LucasLehmer, &config);
+        let last = results.last().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(last.kind, CheckKind::TrialFactor);
+        assert_eq!(last.factor, Some(BigUint::from(761_838_257_287u64)));
+
+        writeln!(file, "M13 23").unwrap();
+        let err = load_known_factors(file.path()).unwrap_err();
+        assert!(err.to_string().starts_with("line 6:"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_worktodo() {
+        // Each supported work type, with and without an assignment ID
+        assert_eq!(parse_worktodo_line("Test=127"), Some(127));
+        assert_eq!(parse_worktodo_line("Test=N/A,521,68,1"), Some(521));
+        assert_eq!(parse_worktodo_line("Test=0123456789ABCDEF0123456789abcdef,607,69,0"), Some(607));
+        assert_eq!(parse_worktodo_line("DoubleCheck=N/A,1279,70,1"), Some(1279));
+        assert_eq!(parse_worktodo_line("DoubleCheck=2203,70,1"), Some(2203));
+        assert_eq!(parse_worktodo_line("PRP=N/A,1,2,2281,-1,75,0"), Some(2281));
+        assert_eq!(parse_worktodo_line("PRP=1,2,3217,-1"), Some(3217));
+        assert_eq!(parse_worktodo_line("PRPDC=N/A,1,2,4253,-1,76,0,3,5"), Some(4253));
+        assert_eq!(parse_worktodo_line("  Test = N/A , 4423 , 70 , 1  "), Some(4423));
+
+        // Malformed or unsupported entries
+        for line in [
+            "",
+            "[Worker #1]",
+            "; comment",
+            "Test=",
+            "Test=N/A",
+            "Test=N/A,abc,68,1",
+            "Test=N/A,1,68,1",
+            "PRP=N/A,3,2,2281,-1",
+            "PRP=N/A,1,2,2281,1",
+            "PRP=N/A,1,2,2281",
+            "Factor=N/A,127,60,61",
+            "Pminus1=N/A,1,2,127,-1,100000,0",
+            "127",
+        ] {
+            assert_eq!(parse_worktodo_line(line), None, "{:?}", line);
+        }
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "[Worker #1]").unwrap();
+        writeln!(file, "Test=N/A,127,68,1").unwrap();
+        writeln!(file, "Factor=N/A,130,60,61").unwrap();
+        writeln!(file, "PRP=N/A,1,2,521,-1,68,0").unwrap();
+        writeln!(file, "DoubleCheck=garbage").unwrap();
+        writeln!(file).unwrap();
+        writeln!(file, "[Worker #2]").unwrap();
+        writeln!(file, "DoubleCheck=N/A,89,64,1").unwrap();
+        assert_eq!(parse_worktodo(file.path()).unwrap(), vec![127, 521, 89]);
+
+        assert!(parse_worktodo("no/such/worktodo.txt").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_check_result_serde_round_trip() {
+        let results = check_mersenne_candidate(37, CheckLevel::TrialFactoring);
+        let json = serde_json::to_string(&results).unwrap();
+        assert!(json.contains("\"kind\":\"TrialFactor\""), "{}", json);
+
+        let decoded: Vec<CheckResult> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.len(), results.len());
+        for (a, b) in decoded.iter().zip(&results) {
+            assert_eq!((a.kind, a.passed, a.inconclusive), (b.kind, b.passed, b.inconclusive));
+            assert_eq!((&a.message, a.time_taken, &a.factor), (&b.message, b.time_taken, &b.factor));
+        }
+        assert_eq!(decoded[1].factor, Some(BigUint::from(223u32)));
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let caps = capabilities();
+        assert_eq!(caps.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(caps.has("python"), cfg!(feature = "pyo3"));
+        assert_eq!(caps.has("fft"), cfg!(feature = "fft"));
+        assert_eq!(caps.has("serde"), cfg!(feature = "serde"));
+        assert_eq!(caps.has("ffi"), cfg!(feature = "ffi"));
+        assert_eq!(caps.has("wasm"), cfg!(feature = "wasm"));
+        assert_eq!(caps.has("primenet"), cfg!(feature = "primenet"));
+        assert!(!caps.has("no-such-feature"));
+    }
+
+    #[test]
+    fn test_jacobi_symbol() {
+        let jacobi = |a: u32, n: u32| super::jacobi(&BigUint::from(a), &BigUint::from(n));
+        assert_eq!(jacobi(0, 1), 1);
+        assert_eq!(jacobi(0, 9), 0);
+        assert_eq!(jacobi(1, 9), 1);
+        assert_eq!(jacobi(1, 7), 1);
+        assert_eq!(jacobi(3, 7), -1);
+        assert_eq!(jacobi(2, 7), 1);
+        assert_eq!(jacobi(5, 21), 1);
+        assert_eq!(jacobi(2, 15), 1);
+        assert_eq!(jacobi(7, 21), 0);
+        assert_eq!(jacobi(1001, 9907), -1);
+
+        // Legendre symbols agree with Euler's criterion a^((q-1)/2) mod q
+        for q in [3u32, 5, 7, 11, 13, 17, 19, 23, 29, 31] {
+            for a in 0..2 * q {
+                let euler = BigUint::from(a).modpow(&BigUint::from((q - 1) / 2), &BigUint::from(q));
+                let expected = if euler.is_zero() { 0 } else if euler.is_one() { 1 } else { -1 };
+                assert_eq!(jacobi(a, q), expected, "({} / {})", a, q);
+            }
+        }
+
+        // Even n gives the Kronecker symbol
+        assert_eq!(jacobi(3, 2), -1);
+        assert_eq!(jacobi(7, 2), 1);
+        assert_eq!(jacobi(4, 6), 0);
+        assert_eq!(jacobi(5, 12), -1); // (5 / 4) (5 / 3) = 1 * -1
+        assert_eq!(jacobi(1, 0), 1);
+        assert_eq!(jacobi(2, 0), 0);
+    }
+
+    #[test]
+    fn test_lucas_lehmer_check_holds_for_every_iteration() {
+        for p in [7, 11, 13, 23, 31, 61, 67] {
+            let mp = (BigUint::one() << p) - BigUint::one();
+            let mut s = BigUint::from(4u32);
+            for _ in 0..p - 2 {
+                s = square_and_subtract_two_mod_mp(&s, p);
+                assert!(lucas_lehmer_value_is_consistent(&s, &mp), "M{}", p);
+            }
+        }
+    }
+
+    #[test]
+    fn test_lucas_lehmer_check_interval() {
+        assert_eq!(lucas_lehmer_check_interval(3), 1);
+        assert_eq!(lucas_lehmer_check_interval(86_243), 86_241);
+        assert_eq!(lucas_lehmer_check_interval(110_503), 13_813);
+    }
+
+    #[test]
+    fn test_lucas_lehmer_recovers_from_injected_error() {
+        use std::cell::Cell;
+
+        let run = |p: u64, fault: &dyn Fn(u64, &mut BigUint)| {
+            let start = LucasLehmerCheckpoint::start(p);
+            let checks = LucasLehmerChecks::recovering(1000);
+            lucas_lehmer_core(p, start, checks, &mut |_, _| {}, &|| false, fault, &mut |_| Ok(()))
+                .unwrap()
+                .0
+        };
+
+        // s = 3 gives s - 2 = 1, whose Jacobi symbol is +1, so a check made right
+        // after the corruption must fire (iteration 1999 is the last before one)
+        let injected = Cell::new(false);
+        let corrupt_once = |i: u64, s: &mut BigUint| {
+            if i == 1999 && !injected.replace(true) {
+                *s = BigUint::from(3u32);
+            }
+        };
+        let result = run(2203, &corrupt_once);
+        assert!(result.is_prime);
+        assert_eq!(result.errors_corrected, 1);
+
+        // An error in the final partial block is caught by the final check
+        let injected = Cell::new(false);
+        let corrupt_last = |i: u64, s: &mut BigUint| {
+            if i == 2200 && !injected.replace(true) {
+                *s = BigUint::from(3u32);
+            }
+        };
+        let result = run(2203, &corrupt_last);
+        assert!(result.is_prime);
+        assert_eq!(result.errors_corrected, 1);
+
+        let result = run(2207, &|_, _| {});
+        assert!(!result.is_prime);
+        assert_eq!(result.errors_corrected, 0);
+
+        // A fault that recurs on every attempt is reported, not retried forever
+        let start = LucasLehmerCheckpoint::start(2203);
+        let corrupt_always = |i: u64, s: &mut BigUint| {
+            if i == 1999 {
+                *s = BigUint::from(3u32);
+            }
+        };
+        let checks = LucasLehmerChecks::recovering(1000);
+        match lucas_lehmer_core(2203, start, checks, &mut |_, _| {}, &|| false, &corrupt_always, &mut |_| Ok(())) {
+            Err(LucasLehmerStop::Inconsistent(PrimalityError::ConsistencyCheckFailed { exponent, iteration })) => {
+                assert_eq!((exponent, iteration), (2203, 2000));
+            }
+            other => panic!("expected a consistency failure, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_lucas_lehmer_residue_is_low_bits_of_final_value() {
+        for p in [3u64, 11, 23, 29, 67, 101, 127, 2207] {
+            let mp = (BigUint::one() << p) - BigUint::one();
+            let mut s = BigUint::from(4u32);
+            for _ in 0..p - 2 {
+                s = (&s * &s + &mp - 2u32) % &mp;
+            }
+            let expected = s.iter_u64_digits().next().unwrap_or(0);
+            assert_eq!(lucas_lehmer_residue(p), (s.is_zero(), expected), "M{}", p);
+        }
+        assert_eq!(format_res64(0xabc), "0000000000000ABC");
+    }
+
+    #[test]
+    fn test_lucas_lehmer_test_u128_matches_biguint() {
+        for p in 0..=LUCAS_LEHMER_U128_MAX_EXPONENT {
+            let expected = lucas_lehmer_test_checked(p).unwrap().is_prime;
+            assert_eq!(lucas_lehmer_test_u128(p), Some(expected), "M{}", p);
+            assert_eq!(lucas_lehmer_test(p), expected, "M{}", p);
+            if p >= 2 {
+                assert_eq!(expected, lucas_lehmer_reference(p), "M{}", p);
+            }
+        }
+        let small: Vec<u64> = known_mersenne_primes().iter().copied().filter(|&p| p <= 63).collect();
+        let found: Vec<u64> = (0..=63).filter(|&p| lucas_lehmer_test_u128(p) == Some(true)).collect();
+        assert_eq!(found, small);
+        assert_eq!(lucas_lehmer_test_u128(LUCAS_LEHMER_U128_MAX_EXPONENT + 1), None);
+    }
+
+    #[test]
+    fn test_lucas_lehmer_test_jacobi_checked() {
+        // A correct run never trips the check, however often it runs
+        let runs = prime_sieve(256).map(|p| (p, [0, 1, 7, 100])).chain([521, 607, 1279, 2203].map(|p| (p, [0, 25, 100, 1000])));
+        for (p, intervals) in runs {
+            for check_every in intervals {
+                assert_eq!(
+                    lucas_lehmer_test_jacobi_checked(p, check_every).unwrap(),
+                    lucas_lehmer_test(p),
+                    "M{} checked every {}",
+                    p,
+                    check_every
+                );
+            }
+        }
+        assert!(matches!(lucas_lehmer_test_jacobi_checked(1, 10), Err(PrimalityError::InvalidExponent(1))));
+
+        // s = 3 gives s - 2 = 1, whose symbol is +1, so a check made right after
+        // the corruption must fire (fault index 49 is the 50th iteration)
+        let corrupt = |i: u64, s: &mut BigUint| {
+            if i == 49 {
+                *s = BigUint::from(3u32);
+            }
+        };
+        let error = lucas_lehmer_jacobi_checked_with_fault(127, 10, &corrupt).unwrap_err();
+        assert!(matches!(error, PrimalityError::ConsistencyCheckFailed { exponent: 127, iteration: 50 }));
+        assert!(error.to_string().contains("after 50 iterations"));
+        // Only checking the end catches a corruption of the final value
+        let corrupt_last = |i: u64, s: &mut BigUint| {
+            if i == 124 {
+                *s = BigUint::from(3u32);
+            }
+        };
+        let error = lucas_lehmer_jacobi_checked_with_fault(127, 0, &corrupt_last).unwrap_err();
+        assert!(matches!(error, PrimalityError::ConsistencyCheckFailed { iteration: 125, .. }));
+    }
+
+    #[test]
+    fn test_lucas_lehmer_test_verbose() {
+        let (is_prime, residue) = lucas_lehmer_test_verbose(7);
+        assert!(is_prime);
+        assert!(residue.is_zero());
+
+        let (is_prime, residue) = lucas_lehmer_test_verbose(11);
+        assert!(!is_prime);
+        assert!(!residue.is_zero());
+        assert_eq!(residue, BigUint::from(1736u32));
+
+        // The full residue is reduced below M_p and agrees with res64
+        for p in [2u64, 3, 13, 23, 29, 521, 523] {
+            let (is_prime, residue) = lucas_lehmer_test_verbose(p);
+            assert!(residue < (BigUint::one() << p) - BigUint::one(), "M{}", p);
+            assert_eq!((is_prime, residue.iter_u64_digits().next().unwrap_or(0)), lucas_lehmer_residue(p), "M{}", p);
+        }
+    }
+
+    #[test]
+    fn test_export_gimps_result() {
+        let prime = export_gimps_result(127).unwrap();
+        assert_eq!((prime.exponent, prime.status.as_str()), (127, "P"));
+        assert_eq!(prime.res64, "0000000000000000");
+
+        let composite = export_gimps_result(11).unwrap();
+        assert_eq!(composite.worktype, "LL");
+        assert_eq!(composite.status, "C");
+        assert_eq!(composite.res64, format_res64(lucas_lehmer_residue(11).1));
+        assert_eq!(composite.program.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(composite.timestamp.len(), "2025-01-01 12:00:00".len());
+        assert!(composite.to_json().contains(r#""res64":"00000000000006C8","shift-count":0,"#));
+
+        #[cfg(feature = "serde")]
+        {
+            assert_eq!(serde_json::to_string(&composite).unwrap(), composite.to_json());
+            let parsed: GimpsResult = serde_json::from_str(&composite.to_json()).unwrap();
+            assert_eq!(parsed, composite);
+        }
+    }
+
+    #[test]
+    fn test_square_and_subtract_two_small_values() {
+        for p in 1..=7u64 {
+            let mp = (1i64 << p) - 1;
+            for s in 0..=2i64 {
+                let expected = (s * s - 2).rem_euclid(mp);
+                assert_eq!(
+                    square_and_subtract_two_mod_mp(&BigUint::from(s as u64), p),
+                    BigUint::from(expected as u64),
+                    "s = {}, p = {}", s, p
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_solovay_strassen() {
+        // Primes always pass; with 40 rounds a composite slips through with
+        // probability at most 2^-40
+        for n in 0..1000u64 {
+            assert_eq!(solovay_strassen(&BigUint::from(n), 40), is_prime(n), "{}", n);
+        }
+        // Carmichael numbers and strong pseudoprimes to base 2
+        for n in [561u64, 1105, 1729, 2465, 2821, 6601, 2047, 3277, 4033, 8321] {
+            assert!(!solovay_strassen(&BigUint::from(n), 40), "{}", n);
+        }
+
+        for p in [31u64, 61, 89, 127] {
+            assert!(solovay_strassen(&Exponent::new(p).unwrap().mersenne_number(), 10), "M{}", p);
+        }
+        for p in [11u64, 29, 67] {
+            assert!(!solovay_strassen(&Exponent::new(p).unwrap().mersenne_number(), 40), "M{}", p);
+        }
+    }
+
+    #[test]
+    fn test_baillie_psw() {
+        for n in 0..2000u64 {
+            assert_eq!(baillie_psw(&BigUint::from(n)), is_prime(n), "{}", n);
+        }
+
+        // Strong pseudoprimes to base 2, strong Lucas pseudoprimes, and Carmichael numbers
+        for n in [2047u64, 3277, 4033, 4681, 8321, 5459, 5777, 10877, 16109, 18971, 561, 1105, 1729] {
+            assert!(!baillie_psw(&BigUint::from(n)), "{}", n);
+        }
+        assert!(baillie_psw(&BigUint::from(18446744073709551557u64)));
+
+        // The Lucas part alone: it is fooled by strong Lucas pseudoprimes, but
+        // catches 8321, a strong pseudoprime to base 2
+        for n in [5459u64, 5777, 10877, 16109, 18971] {
+            assert!(strong_lucas_test(&BigUint::from(n)), "{}", n);
+        }
+        assert!(!strong_lucas_test(&BigUint::from(8321u32)));
+
+        for p in (2..1300).filter(|&p| is_prime(p)) {
+            let mp = Exponent::new(p).unwrap().mersenne_number();
+            assert_eq!(baillie_psw(&mp), KNOWN_MERSENNE_PRIME_EXPONENTS.contains(&p), "M{}", p);
+        }
+    }
+
+    #[test]
+    fn test_strong_lucas_prp() {
+        // The first strong Lucas pseudoprimes with Selfridge's parameters
+        let pseudoprimes = [5459u64, 5777, 10877, 16109, 18971, 22499, 24569, 25199, 40309, 58519];
+        for n in 0..60_000u64 {
+            let expected = is_prime(n) || pseudoprimes.contains(&n);
+            assert_eq!(strong_lucas_prp(&BigUint::from(n)), expected, "{}", n);
+        }
+
+        // Fermat base-2 pseudoprimes, Carmichael numbers among them, and plain
+        // Lucas pseudoprimes such as 323 and 377 are all caught
+        let two = BigUint::from(2u32);
+        for n in [341u64, 561, 645, 1105, 1387, 1729, 1905, 2047, 2465, 2701, 323, 377, 1159, 1829] {
+            let n = BigUint::from(n);
+            assert!(!strong_lucas_prp(&n), "{}", n);
+        }
+        for n in [341u64, 561, 645, 1105, 1387, 1729, 1905, 2047, 2465, 2701] {
+            let n = BigUint::from(n);
+            assert!(two.modpow(&(&n - 1u32), &n).is_one(), "{} fools Fermat base 2", n);
+        }
+        // ...and the strong Lucas pseudoprimes are caught by Fermat base 2
+        for n in pseudoprimes {
+            let n = BigUint::from(n);
+            assert!(!two.modpow(&(&n - 1u32), &n).is_one(), "{}", n);
+        }
+
+        for p in (2..700).filter(|&p| is_prime(p)) {
+            let mp = Exponent::new(p).unwrap().mersenne_number();
+            assert_eq!(strong_lucas_prp(&mp), KNOWN_MERSENNE_PRIME_EXPONENTS.contains(&p), "M{}", p);
+        }
+    }
+
+    #[test]
+    fn test_check_outcome() {
+        let start = Instant::now();
+        let timeout = Duration::from_secs(30);
+        assert_eq!(miller_rabin_outcome(61, 5, start, timeout).unwrap(), CheckOutcome::ProbablyPrime);
+        assert_eq!(miller_rabin_outcome(67, 5, start, timeout).unwrap(), CheckOutcome::Composite);
+        assert!(matches!(miller_rabin_outcome(61, 5, start, Duration::ZERO), Err(PrimalityError::TimedOut)));
+        assert_eq!(lucas_lehmer_outcome(61).unwrap(), CheckOutcome::DefinitelyPrime);
+        assert_eq!(lucas_lehmer_outcome(67).unwrap(), CheckOutcome::DefinitelyComposite);
+        assert_eq!(lucas_lehmer_outcome_with_timeout(61, timeout).unwrap(), CheckOutcome::DefinitelyPrime);
+
+        // M_0 and M_1 are errors here, where the bool wrappers just say false
+        for p in [0u64, 1] {
+            assert!(matches!(lucas_lehmer_outcome(p), Err(PrimalityError::InvalidExponent(q)) if q == p));
+            assert!(matches!(miller_rabin_outcome(p, 5, start, timeout), Err(PrimalityError::InvalidExponent(q)) if q == p));
+            assert!(!lucas_lehmer_test(p));
+        }
+        let io_error = PrimalityError::from(io::Error::new(io::ErrorKind::NotFound, "gone"));
+        assert!(std::error::Error::source(&io_error).is_some());
+        assert_eq!(io::Error::from(io_error).kind(), io::ErrorKind::NotFound);
+        assert_eq!(io::Error::from(PrimalityError::TimedOut).kind(), io::ErrorKind::TimedOut);
+
+        assert!(CheckOutcome::Composite.is_composite());
+        assert!(CheckOutcome::DefinitelyComposite.is_composite());
+        assert!(!CheckOutcome::Inconclusive.is_composite());
+        assert!(!CheckOutcome::ProbablyPrime.is_composite());
+    }
+
+    #[test]
+    fn test_primality_test_trait() {
+        // The standard tests reach the pipeline's verdict, and for prime
+        // exponents stop at the same stage (the pipeline adds one to name the
+        // algebraic factor of a composite exponent)
+        for p in [4u64, 11, 31, 37, 61, 67, 89, 127] {
+            let pipeline = check_mersenne_candidate(p, CheckLevel::LucasLehmer);
+            let reports = run_primality_tests(p, &standard_tests(CheckLevel::LucasLehmer));
+            let last = reports.last().unwrap().outcome.as_ref().unwrap();
+            assert_eq!(!last.is_composite(), pipeline.iter().all(|r| r.passed), "p = {p}");
+            if is_prime(p) {
+                assert_eq!(reports.len(), pipeline.len(), "p = {p}");
+            }
+        }
+        assert_eq!(standard_tests(CheckLevel::PreScreen).len(), 1);
+        assert_eq!(standard_tests(CheckLevel::Probabilistic).len(), 4);
+
+        // At Full they search as deep as the pipeline: M103's smallest factor,
+        // 2550183799, is beyond the default trial factoring limit but below 2^32
+        for p in [31u64, 67, 103] {
+            let pipeline = check_mersenne_candidate(p, CheckLevel::Full);
+            let reports = run_primality_tests(p, &standard_tests(CheckLevel::Full));
+            let last = reports.last().unwrap().outcome.as_ref().unwrap();
+            assert_eq!(!last.is_composite(), pipeline.iter().all(|r| r.passed), "p = {p}");
+            assert_eq!(reports.len(), pipeline.len(), "p = {p}");
+        }
+        assert_eq!(TrialFactoring::default().test(103).unwrap(), CheckOutcome::ProbablyPrime);
+
+        assert_eq!(TrialFactoring { limit: 100, to_bits: None }.test(11).unwrap(), CheckOutcome::Composite);
+        assert_eq!(PMinusOne { b1: Some(3000), b2_multiplier: 0 }.test(67).unwrap(), CheckOutcome::Composite);
+        assert_eq!(MillerRabin { rounds: 3 }.test(61).unwrap(), CheckOutcome::ProbablyPrime);
+        assert_eq!(LucasLehmer.name(), "Lucas-Lehmer");
+
+        // An invalid exponent ends the run at the first test
+        let reports = run_primality_tests(1, &standard_tests(CheckLevel::LucasLehmer));
+        assert_eq!(reports.len(), 1);
+        assert!(matches!(reports[0].outcome, Err(PrimalityError::InvalidExponent(1))));
+    }
+
+    #[test]
+    fn test_validate_exponent() {
+        assert!(validate_exponent(2).is_ok());
+        assert!(validate_exponent(4).is_ok()); // M_4 = 15 is composite, not invalid
+        assert!(matches!(validate_exponent(0), Err(PrimalityError::InvalidExponent(0))));
+        assert!(matches!(validate_exponent(1), Err(PrimalityError::InvalidExponent(1))));
+        assert_eq!(
+            validate_exponent(0).unwrap_err().to_string(),
+            "M_0 = 0 is neither prime nor composite"
+        );
+        assert_eq!(
+            validate_exponent(1).unwrap_err().to_string(),
+            "M_1 = 1 is neither prime nor composite"
+        );
+
+        // The pipeline stops at once and calls neither M_0 nor M_1 composite,
+        // while M_4 fails the pre-screen as an ordinary composite
+        for p in [0u64, 1] {
+            let results = check_mersenne_candidate(p, CheckLevel::LucasLehmer);
+            assert_eq!(results.len(), 1, "M{}", p);
+            assert!(!results[0].passed && results[0].inconclusive, "M{}", p);
+            assert!(results[0].message.contains("neither prime nor composite"), "M{}", p);
+        }
+        let results = check_mersenne_candidate(4, CheckLevel::PreScreen);
+        assert!(!results[0].passed && !results[0].inconclusive);
+
+        let checkpoint = std::env::temp_dir().join("primality_jones_test_validate_1.ckpt");
+        assert!(matches!(
+            lucas_lehmer_outcome_resumable(1, &checkpoint, 10),
+            Err(PrimalityError::InvalidExponent(1))
+        ));
+        assert!(matches!(
+            lucas_lehmer_outcome_with_timeout(0, Duration::from_secs(1)),
+            Err(PrimalityError::InvalidExponent(0))
+        ));
+    }
+
+    #[test]
+    fn test_check_config_limits() {
+        let defaults = CheckConfig::default();
+        assert_eq!(defaults.trial_factor_limit, 1_000_000);
+        assert_eq!(defaults.miller_rabin_rounds, 5);
+        assert_eq!(defaults.probabilistic_timeout, Duration::from_secs(300));
+
+        // M47 = 2351 * 4513 * 13264529: a low limit misses 2351
+        let low_limit = CheckConfig { trial_factor_limit: 2000, ..CheckConfig::default() };
+        let results = check_mersenne_candidate_with_config(47, CheckLevel::TrialFactoring, &low_limit);
+        assert!(results.iter().all(|r| r.passed));
+        assert_eq!(results[1].message, "No small factors found up to 2000");
+        let results = check_mersenne_candidate(47, CheckLevel::TrialFactoring);
+        assert_eq!(results[1].factor, Some(BigUint::from(2351u32)));
+
+        // A limit of 0 searches nothing rather than underflowing
+        let zero_limit = CheckConfig::new().trial_limit(0);
+        let results = check_mersenne_candidate_with_config(31, CheckLevel::TrialFactoring, &zero_limit);
+        assert_eq!(results[1].message, "No small factors found up to 0");
+        assert_eq!(check_small_factors(31, 0), None);
+        assert_eq!(check_small_factors(31, 2), None);
+        // The algebraic factor M3 of M33 is reported whole
+        let results = check_mersenne_candidate_with_config(33, CheckLevel::TrialFactoring, &zero_limit);
+        assert_eq!(results[1].factor, Some(BigUint::from(7u32)));
+
+        let no_time = CheckConfig { probabilistic_timeout: Duration::ZERO, ..CheckConfig::default() };
+        let results = check_mersenne_candidate_with_config(127, CheckLevel::Probabilistic, &no_time);
+        assert!(results.last().unwrap().inconclusive);
+    }
+
+    #[test]
+    fn test_check_config_builder() {
+        let config = CheckConfig::new()
+            .miller_rabin_rounds(20)
+            .trial_limit(10_000_000)
+            .probabilistic_timeout(Duration::from_secs(600))
+            .lucas_lehmer_timeout(Duration::from_secs(60));
+        assert_eq!(config.miller_rabin_rounds, 20);
+        assert_eq!(config.trial_factor_limit, 10_000_000);
+        assert_eq!(config.probabilistic_timeout, Duration::from_secs(600));
+        assert_eq!(config.lucas_lehmer_timeout, Some(Duration::from_secs(60)));
+
+        // More rounds must not turn a prime into a composite
+        let results = check_mersenne_candidate_with_config(31, CheckLevel::LucasLehmer, &config);
+        assert!(results.iter().all(|r| r.passed));
+        let miller_rabin = results.iter().find(|r| r.kind == CheckKind::MillerRabin).unwrap();
+        assert_eq!(miller_rabin.message, "Passed Miller-Rabin test");
+    }
+
+    #[test]
+    fn test_pollard_p_minus_1() {
+        // 2349023 - 1 = 2 * 13 * 167 * 541, beyond the trial factoring limit
+        assert_eq!(pollard_p_minus_1(167, 600), Some(BigUint::from(2349023u32)));
+        assert_eq!(pollard_p_minus_1(167, 500), None);
+        assert_eq!(pollard_p_minus_1(127, 10_000), None);
+        assert_eq!(pollard_p_minus_1_with_stage2(127, 1000, 100_000), None);
+
+        // Stage 2 reaches factors with one prime between B1 and B2 in q - 1:
+        // 852133200 = 2^4 * 3 * 5^2 * 157 * 4523
+        assert_eq!(pollard_p_minus_1(157, 1000), None);
+        assert_eq!(pollard_p_minus_1_with_stage2(157, 1000, 4522), None);
+        assert_eq!(pollard_p_minus_1_with_stage2(157, 1000, 4523), Some(BigUint::from(852133201u32)));
+        // A factor stage 1 finds is returned without running stage 2
+        assert_eq!(pollard_p_minus_1_with_stage2(167, 600, 1_000_000), Some(BigUint::from(2349023u32)));
+        // B2 no greater than B1 is stage 1 alone, and the first prime above B1 counts
+        assert_eq!(pollard_p_minus_1_with_stage2(157, 1000, 1000), None);
+        assert_eq!(pollard_p_minus_1_with_stage2(157, 4522, 4523), Some(BigUint::from(852133201u32)));
+
+        let results = check_mersenne_candidate(167, CheckLevel::PMinusOne);
+        let last = results.last().unwrap();
+        assert_eq!(last.kind, CheckKind::PMinusOne);
+        assert!(!last.passed);
+        assert_eq!(last.factor, Some(BigUint::from(2349023u32)));
+
+        // Trial factoring still gets there first when it can
+        let results = check_mersenne_candidate(29, CheckLevel::LucasLehmer);
+        assert!(results.iter().all(|r| r.kind != CheckKind::PMinusOne));
+    }
+
+    #[test]
+    fn test_parallel_processing() {
+        // Test parallel processing of multiple candidates
+        let candidates = vec![31, 61, 89, 107, 127];
+        let results = process_candidates_parallel(candidates.clone(), CheckLevel::LucasLehmer);
+        
+        assert_eq!(results.len(), candidates.len());
+        
+        // Verify that known primes are correctly identified
+        for (p, candidate_results) in results {
+            if p == 31 || p == 61 || p == 89 || p == 107 || p == 127 {
+                assert!(candidate_results.iter().all(|r| r.passed), 
+                    "M{} should be identified as prime", p);
+            }
+        }
+    }
+
+    #[test]
+    fn test_check_candidates_parallel_matches_sequential() {
+        let summary = |r: &CheckResult| (r.kind, r.passed, r.factor.clone(), r.message.clone());
+        let ps = [127u64, 11, 1, 37, 89, 4, 61, 23, 107, 67];
+        let sequential: Vec<_> = ps
+            .iter()
+            .map(|&p| (p, check_mersenne_candidate(p, CheckLevel::LucasLehmer).iter().map(summary).collect::<Vec<_>>()))
+            .collect();
+
+        for results in [
+            check_candidates_parallel(&ps, CheckLevel::LucasLehmer),
+            check_candidates_parallel_with_threads(&ps, CheckLevel::LucasLehmer, 1),
+            check_candidates_parallel_with_threads(&ps, CheckLevel::LucasLehmer, 3),
+        ] {
+            let parallel: Vec<_> = results
+                .iter()
+                .map(|(p, stages)| (*p, stages.iter().map(summary).collect::<Vec<_>>()))
+                .collect();
+            assert_eq!(parallel, sequential);
+        }
+    }
+}
+
+/// Python module for Mersenne number primality testing
+#[cfg(feature = "pyo3")]
+#[pymodule]
+fn primality_jones(_py: Python, m: &PyModule) -> PyResult<()> {
+    // Expose CheckLevel enum to Python
+    #[pyclass]
+    #[derive(Clone, Copy)]
+    enum PyCheckLevel {
+        PreScreen = 0,
+        TrialFactoring = 1,
+        Probabilistic = 2,
+        LucasLehmer = 3,
+        PMinusOne = 4,
+        Full = 5,
+    }
+
+    #[pymethods]
+    impl PyCheckLevel {
+        fn description(&self) -> String {
+            match self {
+                PyCheckLevel::PreScreen => {
+                    "Pre-screen: Check if exponent is prime (instant)".to_string()
+                }
+                PyCheckLevel::TrialFactoring => {
+                    "Trial factoring: Check for small factors (~1 second)".to_string()
+                }
+                PyCheckLevel::PMinusOne => {
+                    "P-1 factoring: Check for factors with smooth q - 1 (seconds)".to_string()
+                }
+                PyCheckLevel::Probabilistic => {
+                    "Probabilistic: Miller-Rabin test (seconds to minutes)".to_string()
+                }
+                PyCheckLevel::LucasLehmer => {
+                    "Lucas-Lehmer: Definitive test (minutes to hours)".to_string()
+                }
+                PyCheckLevel::Full => {
+                    "Full: Deeper factoring, then Lucas-Lehmer with res64 (minutes to hours)".to_string()
+                }
+            }
+        }
+    }
+
+    impl From<PyCheckLevel> for CheckLevel {
+        fn from(level: PyCheckLevel) -> Self {
+            match level {
+                PyCheckLevel::PreScreen => CheckLevel::PreScreen,
+                PyCheckLevel::TrialFactoring => CheckLevel::TrialFactoring,
+                PyCheckLevel::PMinusOne => CheckLevel::PMinusOne,
+                PyCheckLevel::Probabilistic => CheckLevel::Probabilistic,
+                PyCheckLevel::LucasLehmer => CheckLevel::LucasLehmer,
+                PyCheckLevel::Full => CheckLevel::Full,
+            }
+        }
+    }
+
+    /// A `BigUint` as a Python int of any size
+    fn biguint_to_py(py: Python, n: &BigUint) -> PyResult<PyObject> {
+        Ok(py.get_type::<pyo3::types::PyLong>().call1((n.to_string(),))?.into())
+    }
+
+    /// A non-negative Python int as a `BigUint`
+    fn py_to_biguint(n: &pyo3::types::PyLong) -> PyResult<BigUint> {
+        n.str()?
+            .to_str()?
+            .parse()
+            .map_err(|_| pyo3::exceptions::PyValueError::new_err("expected a non-negative integer"))
+    }
+
+    /// M_0 = 0 has no residues, and reducing by it would never finish
+    fn check_mersenne_modulus(p: u64) -> PyResult<()> {
+        if p == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err("p must be at least 1"));
+        }
+        Ok(())
+    }
+
+    /// A factor as a Python int, or None
+    fn factor_to_py(py: Python, factor: &Option<BigUint>) -> PyResult<PyObject> {
+        match factor {
+            Some(factor) => biguint_to_py(py, factor),
+            None => Ok(py.None()),
+        }
+    }
+
+    /// Passes progress to a Python callable as `(stage, current, total)`
+    ///
+    /// The first exception the callable raises is kept and stops the check,
+    /// so `check_mersenne` can raise it once the pipeline has unwound.
+    struct PyProgress {
+        callback: PyObject,
+        error: Mutex<Option<PyErr>>,
+    }
+
+    impl PyProgress {
+        fn call(&self, kind: CheckKind, current: u64, total: u64) {
+            let mut error = self.error.lock().unwrap();
+            if error.is_none() {
+                let stage = format!("{:?}", kind);
+                *error = Python::with_gil(|py| self.callback.call1(py, (stage, current, total)).err());
+            }
+        }
+    }
+
+    impl ProgressReporter for PyProgress {
+        fn on_progress(&self, kind: CheckKind, current: u64, total: u64) {
+            self.call(kind, current, total);
+        }
+
+        fn on_stage_complete(&self, result: &CheckResult) {
+            self.call(result.kind, 1, 1);
+        }
+
+        fn should_stop(&self) -> bool {
+            self.error.lock().unwrap().is_some()
+        }
+    }
+
+    /// Check a Mersenne number for primality
+    ///
+    /// If `progress` is given, it is called as `progress(stage, current, total)`
+    /// during the Lucas-Lehmer test and with `current == total == 1` as each
+    /// stage completes. An exception raised by it stops the check and is
+    /// raised from `check_mersenne`.
+    #[pyfunction]
+    #[pyo3(signature = (p, level, progress = None))]
+    fn check_mersenne(py: Python, p: u64, level: PyCheckLevel, progress: Option<PyObject>) -> PyResult<Vec<PyObject>> {
+        let check_level = level.into();
+
+        let results = match progress {
+            None => check_mersenne_candidate(p, check_level),
+            Some(callback) => {
+                let reporter = PyProgress { callback, error: Mutex::new(None) };
+                // Release the GIL so the callback can take it from whichever
+                // thread reports
+                let results = py.allow_threads(|| {
+                    check_mersenne_candidate_with_config_and_reporter(
+                        p,
+                        check_level,
+                        &CheckConfig::default(),
+                        &reporter,
+                    )
+                });
+                if let Some(e) = reporter.error.into_inner().unwrap() {
+                    return Err(e);
+                }
+                results
+            }
+        };
+
+        Python::with_gil(|py| {
+            results
+                .into_iter()
+                .map(|r| {
+                    let dict = PyDict::new(py);
                     dict.set_item("passed", r.passed)?;
                     dict.set_item("message", r.message)?;
+                    dict.set_item("inconclusive", r.inconclusive)?;
+                    dict.set_item("factor", factor_to_py(py, &r.factor)?)?;
                     dict.set_item("time_taken_ns", r.time_taken.as_nanos())?;
                     Ok(dict.into())
                 })
@@ -910,20 +2445,70 @@ fn primality_jones(_py: Python, m: &PyModule) -> PyResult<()> {
     }
 
     /// Perform Lucas-Lehmer test
+    ///
+    /// Raises RuntimeError if the error check keeps failing, a sign of
+    /// faulty hardware.
+    #[pyfunction]
+    #[pyo3(name = "lucas_lehmer")]
+    fn lucas_lehmer_py(p: u64) -> PyResult<bool> {
+        lucas_lehmer_test_checked(p)
+            .map(|result| result.is_prime)
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+    }
+
+    /// Reduce k modulo M_p = 2^p - 1 with shifts and adds instead of division
+    ///
+    /// >>> mod_mp(200, 7)
+    /// 73
+    /// >>> mod_mp(2**521 + 5, 521)
+    /// 6
+    /// >>> mod_mp(200, 0)
+    /// Traceback (most recent call last):
+    ///     ...
+    /// ValueError: p must be at least 1
+    #[pyfunction]
+    #[pyo3(name = "mod_mp")]
+    fn mod_mp_py(py: Python, k: &pyo3::types::PyLong, p: u64) -> PyResult<PyObject> {
+        check_mersenne_modulus(p)?;
+        let k = py_to_biguint(k)?;
+        biguint_to_py(py, &mod_mp(&k, p))
+    }
+
+    /// One Lucas-Lehmer step: (s * s - 2) mod M_p
+    ///
+    /// >>> square_and_subtract_two_mod_mp(4, 7)
+    /// 14
+    /// >>> square_and_subtract_two_mod_mp(4, 0)
+    /// Traceback (most recent call last):
+    ///     ...
+    /// ValueError: p must be at least 1
     #[pyfunction]
-    fn lucas_lehmer(p: u64) -> bool {
-        lucas_lehmer_test(p)
+    #[pyo3(name = "square_and_subtract_two_mod_mp")]
+    fn square_and_subtract_two_mod_mp_py(py: Python, s: &pyo3::types::PyLong, p: u64) -> PyResult<PyObject> {
+        check_mersenne_modulus(p)?;
+        let s = py_to_biguint(s)?;
+        biguint_to_py(py, &square_and_subtract_two_mod_mp(&s, p))
+    }
+
+    /// Estimate digits, memory in bytes and seconds needed to check M_p
+    #[pyfunction]
+    fn estimate_resources_py(p: u64, level: PyCheckLevel) -> PyResult<PyObject> {
+        let check_level = level.into();
+        let estimate = estimate_resources(p, check_level);
+
+        Python::with_gil(|py| {
+            let dict = PyDict::new(py);
+            dict.set_item("decimal_digits", estimate.decimal_digits)?;
+            dict.set_item("bytes_required", estimate.bytes_required)?;
+            dict.set_item("estimated_seconds", estimate.estimated_duration.as_secs_f64())?;
+            Ok(dict.into())
+        })
     }
 
     /// Process multiple candidates in parallel
     #[pyfunction]
     fn process_candidates_parallel_py(candidates: Vec<u64>, level: PyCheckLevel) -> PyResult<Vec<PyObject>> {
-        let check_level = match level {
-            PyCheckLevel::PreScreen => CheckLevel::PreScreen,
-            PyCheckLevel::TrialFactoring => CheckLevel::TrialFactoring,
-            PyCheckLevel::Probabilistic => CheckLevel::Probabilistic,
-            PyCheckLevel::LucasLehmer => CheckLevel::LucasLehmer,
-        };
+        let check_level = level.into();
 
         let results = process_candidates_parallel(candidates, check_level);
 
@@ -940,6 +2525,8 @@ fn primality_jones(_py: Python, m: &PyModule) -> PyResult<()> {
                             let result_dict = PyDict::new(py);
                             result_dict.set_item("passed", r.passed)?;
                             result_dict.set_item("message", r.message)?;
+                            result_dict.set_item("inconclusive", r.inconclusive)?;
+                            result_dict.set_item("factor", factor_to_py(py, &r.factor)?)?;
                             result_dict.set_item("time_taken_ns", r.time_taken.as_nanos())?;
                             Ok(result_dict.into())
                         })
@@ -957,8 +2544,11 @@ fn primality_jones(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(check_mersenne, m)?)?;
     m.add_function(wrap_pyfunction!(is_prime_py, m)?)?;
     m.add_function(wrap_pyfunction!(find_small_factors, m)?)?;
-    m.add_function(wrap_pyfunction!(lucas_lehmer, m)?)?;
+    m.add_function(wrap_pyfunction!(lucas_lehmer_py, m)?)?;
     m.add_function(wrap_pyfunction!(process_candidates_parallel_py, m)?)?;
+    m.add_function(wrap_pyfunction!(estimate_resources_py, m)?)?;
+    m.add_function(wrap_pyfunction!(mod_mp_py, m)?)?;
+    m.add_function(wrap_pyfunction!(square_and_subtract_two_mod_mp_py, m)?)?;
 
     Ok(())
 }
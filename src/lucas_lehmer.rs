@@ -0,0 +1,1238 @@
+/*!
+The Lucas-Lehmer test with progress reports, timeouts, error checking and
+checkpoints, the GIMPS result format, and the backends a run can be
+cross-checked against.
+
+The small-exponent fast path `lucas_lehmer_test_u128` does not need `std` and
+lives in the crate root.
+*/
+
+use crate::{
+    fermat_test_stoppable, lucas_lehmer_test_u128, residues_equal_mod_mp, validate_exponent, CheckKind, CheckOutcome,
+    CheckResult, Exponent, Instant, ModMpScratch, NoProgress, PrimalityError, ProgressReporter,
+};
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Perform the Lucas-Lehmer test for Mersenne number primality
+///
+/// This is the definitive test for Mersenne primes. For a Mersenne number M_p = 2^p - 1:
+/// 1. Start with s = 4
+/// 2. For p-2 iterations, compute s = (s^2 - 2) mod M_p
+/// 3. M_p is prime if and only if the final result is s = 0
+///
+/// # Arguments
+///
+/// * `p` - The Mersenne exponent to test (testing 2^p - 1)
+///
+/// # Returns
+///
+/// * `true` if M_p is prime
+/// * `false` if M_p is composite, and also for p = 0 or 1; use
+///   `lucas_lehmer_outcome` to have those rejected by `validate_exponent`
+///
+/// # Panics
+///
+/// Panics on a persistent hardware fault, as described for
+/// `lucas_lehmer_test_checked`, which returns it as an error instead.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::lucas_lehmer_test;
+///
+/// assert!(lucas_lehmer_test(7));   // M7 = 127 is prime
+/// assert!(!lucas_lehmer_test(11)); // M11 = 2047 is composite
+/// ```
+pub fn lucas_lehmer_test(p: impl Into<Exponent>) -> bool {
+    let p = p.into().get();
+    if let Some(is_prime) = lucas_lehmer_test_u128(p) {
+        return is_prime;
+    }
+    lucas_lehmer_test_checked(p).unwrap_or_else(|e| panic!("{e}")).is_prime
+}
+
+/// Lucas-Lehmer test on M_p that returns a `CheckOutcome`
+///
+/// The answer is always `DefinitelyPrime` or `DefinitelyComposite`.
+///
+/// # Errors
+///
+/// `InvalidExponent` if p < 2, where `lucas_lehmer_test` just says `false`.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::{lucas_lehmer_outcome, CheckOutcome, PrimalityError};
+///
+/// assert_eq!(lucas_lehmer_outcome(7).unwrap(), CheckOutcome::DefinitelyPrime);
+/// assert_eq!(lucas_lehmer_outcome(11).unwrap(), CheckOutcome::DefinitelyComposite);
+/// assert!(matches!(lucas_lehmer_outcome(1), Err(PrimalityError::InvalidExponent(1))));
+/// ```
+pub fn lucas_lehmer_outcome(p: impl Into<Exponent>) -> Result<CheckOutcome, PrimalityError> {
+    let p = p.into().get();
+    validate_exponent(p)?;
+    let is_prime = match lucas_lehmer_test_u128(p) {
+        Some(is_prime) => is_prime,
+        None => lucas_lehmer_test_checked(p)?.is_prime,
+    };
+    Ok(lucas_lehmer_verdict(is_prime))
+}
+
+/// Lucas-Lehmer test on M_p that gives up with `TimedOut` after `timeout`
+///
+/// # Errors
+///
+/// `InvalidExponent` if p < 2, and `TimedOut` if the test did not finish in
+/// time.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::{lucas_lehmer_outcome_with_timeout, CheckOutcome};
+/// use std::time::Duration;
+///
+/// let outcome = lucas_lehmer_outcome_with_timeout(127, Duration::from_secs(30));
+/// assert_eq!(outcome.unwrap(), CheckOutcome::DefinitelyPrime);
+/// ```
+pub fn lucas_lehmer_outcome_with_timeout(p: impl Into<Exponent>, timeout: Duration) -> Result<CheckOutcome, PrimalityError> {
+    let p = p.into().get();
+    validate_exponent(p)?;
+    match lucas_lehmer_until(p, &NoProgress, Instant::now().checked_add(timeout)) {
+        Ok((result, _)) => Ok(lucas_lehmer_verdict(result.is_prime)),
+        Err(LucasLehmerStop::Stopped(_)) => Err(PrimalityError::TimedOut),
+        Err(LucasLehmerStop::Inconsistent(e)) => Err(e),
+        Err(LucasLehmerStop::Checkpoint(_)) => unreachable!("nothing is saved"),
+    }
+}
+
+fn lucas_lehmer_verdict(is_prime: bool) -> CheckOutcome {
+    if is_prime {
+        CheckOutcome::DefinitelyPrime
+    } else {
+        CheckOutcome::DefinitelyComposite
+    }
+}
+
+/// Reference Lucas-Lehmer test using only textbook arithmetic
+///
+/// Every step is `(s * s + M_p - 2) % M_p` with plain `BigUint` operations, and
+/// none of the `mod_mp` shortcuts. It is far slower than `lucas_lehmer_test`
+/// and exists only to validate the optimized path on small exponents.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::{lucas_lehmer_reference, lucas_lehmer_test};
+///
+/// for p in 2..100 {
+///     assert_eq!(lucas_lehmer_reference(p), lucas_lehmer_test(p));
+/// }
+/// ```
+pub fn lucas_lehmer_reference(p: impl Into<Exponent>) -> bool {
+    let p = p.into().get();
+    if p < 2 {
+        return false;
+    }
+    if p == 2 {
+        return true;
+    }
+
+    let mp = (BigUint::one() << p) - BigUint::one();
+    let mut s = BigUint::from(4u32);
+    for _ in 0..p - 2 {
+        s = (&s * &s + &mp - 2u32) % &mp;
+    }
+    s.is_zero()
+}
+
+/// Lucas-Lehmer test on M_p with every squaring done in Montgomery form
+///
+/// The sequence value is kept as s·R mod M_p with R = 2^r, r being p rounded
+/// up to whole 64-bit limbs, so each step is one squaring followed by a
+/// Montgomery reduction (REDC) in place of `mod_mp`. The value is converted
+/// back only once, after the last iteration.
+///
+/// This is an alternative for benchmarking, not a faster default. REDC needs
+/// two extra multiplications per step, while `mod_mp` reduces with a shift
+/// and an add, so on the `benchmarks` "Montgomery vs mod_mp" group this path
+/// is slower for every exponent from M607 through M9941 and there is no
+/// crossover in that range. Montgomery form pays off for moduli with no
+/// special shape; M_p is the best case for the direct fold.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::{lucas_lehmer_test, lucas_lehmer_test_montgomery};
+///
+/// for p in 2..200 {
+///     assert_eq!(lucas_lehmer_test_montgomery(p), lucas_lehmer_test(p));
+/// }
+/// ```
+pub fn lucas_lehmer_test_montgomery(p: impl Into<Exponent>) -> bool {
+    let p = p.into().get();
+    if p < 2 {
+        return false;
+    }
+    if p == 2 {
+        return true;
+    }
+
+    let mp = (BigUint::one() << p) - BigUint::one();
+    let r_bits = p.div_ceil(64) * 64;
+    let r_mask = (BigUint::one() << r_bits) - BigUint::one();
+    // -M_p^-1 mod R, so that t + ((t * n_prime) mod R) * M_p is divisible by R
+    let n_prime = &r_mask + BigUint::one()
+        - mp.modinv(&(&r_mask + BigUint::one())).expect("M_p is odd, so it is invertible mod 2^r");
+
+    // REDC(t) = t / R mod M_p, for t < M_p * R
+    let redc = |t: &BigUint| {
+        let m = ((t & &r_mask) * &n_prime) & &r_mask;
+        let u = (t + m * &mp) >> r_bits;
+        if u >= mp { u - &mp } else { u }
+    };
+
+    let two = (BigUint::from(2u32) << r_bits) % &mp;
+    let mut s = (BigUint::from(4u32) << r_bits) % &mp;
+    for _ in 0..p - 2 {
+        let x = redc(&(&s * &s));
+        s = if x >= two { x - &two } else { x + &mp - &two };
+    }
+    redc(&s).is_zero()
+}
+
+/// Total number of squaring iterations the Lucas-Lehmer test runs for M_p
+///
+/// This is p - 2 (zero for p < 3, which need no iterations).
+pub fn lucas_lehmer_iterations(p: impl Into<Exponent>) -> u64 {
+    let p = p.into().get();
+    p.saturating_sub(2)
+}
+
+/// Number of Lucas-Lehmer iterations left after `completed` have been run
+///
+/// Intended for displaying progress when resuming from a checkpoint. The
+/// result saturates at zero; use `is_valid_checkpoint_progress` to reject a
+/// checkpoint whose iteration count is impossible for `p`.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::iterations_remaining;
+///
+/// assert_eq!(iterations_remaining(127, 0), 125);
+/// assert_eq!(iterations_remaining(127, 100), 25);
+/// ```
+pub fn iterations_remaining(p: impl Into<Exponent>, completed: u64) -> u64 {
+    let p = p.into().get();
+    lucas_lehmer_iterations(p).saturating_sub(completed)
+}
+
+/// Whether `completed` is a possible iteration count for a Lucas-Lehmer run on M_p
+///
+/// A checkpoint claiming more than p - 2 completed iterations is corrupt (or
+/// belongs to a different exponent) and must not be resumed from.
+pub fn is_valid_checkpoint_progress(p: impl Into<Exponent>, completed: u64) -> bool {
+    let p = p.into().get();
+    completed <= lucas_lehmer_iterations(p)
+}
+
+/// Number of progress updates the Lucas-Lehmer loop sends over a full run
+const LUCAS_LEHMER_PROGRESS_STEPS: u64 = 200;
+
+/// Lucas-Lehmer test that reports its progress to a callback
+///
+/// Behaves exactly like `lucas_lehmer_test`, and also calls `callback` with
+/// `(current_iteration, total_iterations)` about 200 times over the run (every
+/// iteration when there are fewer than 200), ending with `(total, total)`.
+/// That is often enough to drive a progress bar or a log while costing
+/// nothing measurable. Nothing is reported for p < 3, which need no
+/// iterations. Like `lucas_lehmer_test`, it panics on a persistent hardware
+/// fault.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::lucas_lehmer_test_with_progress;
+///
+/// let mut last = (0, 0);
+/// assert!(lucas_lehmer_test_with_progress(127, |current, total| last = (current, total)));
+/// assert_eq!(last, (125, 125));
+/// ```
+pub fn lucas_lehmer_test_with_progress(p: impl Into<Exponent>, mut callback: impl FnMut(u64, u64)) -> bool {
+    let p = p.into().get();
+    let result = lucas_lehmer_core(
+        p,
+        LucasLehmerCheckpoint::start(p),
+        LucasLehmerChecks::recovering(lucas_lehmer_check_interval(p)),
+        &mut callback,
+        &|| false,
+        &|_, _| {},
+        &mut |_| Ok(()),
+    );
+    lucas_lehmer_or_panic(result).expect("the test is never stopped").0.is_prime
+}
+
+/// Lucas-Lehmer test that gives up after `timeout`
+///
+/// Returns `Some(verdict)` if the test finished in time and `None` if the
+/// timeout elapsed first. The clock is read every 16 iterations, so the test
+/// overruns `timeout` by at most that many iterations. Like
+/// `lucas_lehmer_test`, it panics on a persistent hardware fault;
+/// `lucas_lehmer_outcome_with_timeout` returns that as an error.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::lucas_lehmer_test_with_timeout;
+/// use std::time::Duration;
+///
+/// assert_eq!(lucas_lehmer_test_with_timeout(127, Duration::from_secs(60)), Some(true));
+/// assert_eq!(lucas_lehmer_test_with_timeout(44497, Duration::ZERO), None);
+/// ```
+pub fn lucas_lehmer_test_with_timeout(p: impl Into<Exponent>, timeout: Duration) -> Option<bool> {
+    let p = p.into().get();
+    lucas_lehmer_or_panic(lucas_lehmer_until(p, &NoProgress, Instant::now().checked_add(timeout)))
+        .map(|(result, _)| result.is_prime)
+}
+
+/// Lucas-Lehmer test that stops early once `stop` returns true
+///
+/// `stop` is polled every `LUCAS_LEHMER_STOP_POLL_INTERVAL` iterations.
+/// Returns the verdict and the final value, or `LucasLehmerStop::Stopped`
+/// with the number of iterations completed if the test was stopped before
+/// finishing.
+fn lucas_lehmer_stoppable(
+    p: u64,
+    reporter: &dyn ProgressReporter,
+    stop: &dyn Fn() -> bool,
+) -> Result<(CheckedLucasLehmer, BigUint), LucasLehmerStop> {
+    lucas_lehmer_core(
+        p,
+        LucasLehmerCheckpoint::start(p),
+        LucasLehmerChecks::recovering(lucas_lehmer_check_interval(p)),
+        &mut |i, total| reporter.on_progress(CheckKind::LucasLehmer, i, total),
+        stop,
+        &|_, _| {},
+        &mut |_| Ok(()),
+    )
+}
+
+/// Lucas-Lehmer test that gives up once `deadline` has passed or `reporter`
+/// asks it to stop
+pub(crate) fn lucas_lehmer_until(
+    p: u64,
+    reporter: &dyn ProgressReporter,
+    deadline: Option<Instant>,
+) -> Result<(CheckedLucasLehmer, BigUint), LucasLehmerStop> {
+    match deadline {
+        Some(deadline) => {
+            lucas_lehmer_stoppable(p, reporter, &|| reporter.should_stop() || Instant::now() >= deadline)
+        }
+        None => lucas_lehmer_stoppable(p, reporter, &|| reporter.should_stop()),
+    }
+}
+
+/// Iterations between polls of the stop condition in the Lucas-Lehmer loop
+///
+/// Polling is cheap but not free next to an iteration for small p, so it
+/// happens only at multiples of this count.
+const LUCAS_LEHMER_STOP_POLL_INTERVAL: u64 = 16;
+
+/// Why the Lucas-Lehmer loop stopped before reaching a verdict
+#[derive(Debug)]
+pub(crate) enum LucasLehmerStop {
+    /// The stop condition was met after this many iterations
+    Stopped(u64),
+    /// A verified value could not be saved
+    Checkpoint(io::Error),
+    /// A block kept failing its error check, always
+    /// `PrimalityError::ConsistencyCheckFailed`
+    Inconsistent(PrimalityError),
+}
+
+/// The result of a Lucas-Lehmer run for the entry points with no error to
+/// return: `None` if it was stopped
+///
+/// # Panics
+///
+/// Panics on a persistent hardware fault, `LucasLehmerStop::Inconsistent`.
+fn lucas_lehmer_or_panic<T>(result: Result<T, LucasLehmerStop>) -> Option<T> {
+    match result {
+        Ok(value) => Some(value),
+        Err(LucasLehmerStop::Stopped(_)) => None,
+        Err(LucasLehmerStop::Inconsistent(e)) => panic!("{e}"),
+        Err(LucasLehmerStop::Checkpoint(_)) => unreachable!("nothing is saved"),
+    }
+}
+
+/// Exponents from which the Lucas-Lehmer loop is checked part way through;
+/// smaller ones are only checked once, after the last iteration
+const LUCAS_LEHMER_BLOCK_CHECK_MIN_EXPONENT: u64 = 100_000;
+
+/// Number of blocks a checked Lucas-Lehmer run is split into for large exponents
+const LUCAS_LEHMER_CHECK_BLOCKS: u64 = 8;
+
+/// Iterations between error checks for M_p
+///
+/// A check costs a few hundred iterations' worth of time at these sizes, so
+/// checks are kept rare: only a final one for small exponents, where rerunning
+/// everything is cheap, and one per eighth of the run for large ones.
+pub(crate) fn lucas_lehmer_check_interval(p: u64) -> u64 {
+    let total = lucas_lehmer_iterations(p).max(1);
+    if p < LUCAS_LEHMER_BLOCK_CHECK_MIN_EXPONENT {
+        total
+    } else {
+        total.div_ceil(LUCAS_LEHMER_CHECK_BLOCKS)
+    }
+}
+
+/// Times one block may fail its error check in a row before giving up
+const MAX_BLOCK_RETRIES: u32 = 3;
+
+/// How often `lucas_lehmer_core` checks its value, and what it does when a
+/// check fails
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LucasLehmerChecks {
+    /// Iterations between checks; 0 checks only after the last one
+    every: u64,
+    /// Times one block may be recomputed after failing its check before the
+    /// run gives up; 0 gives up at the first failure
+    max_retries: u32,
+}
+
+impl LucasLehmerChecks {
+    /// Check every `every` iterations and recompute failed blocks
+    pub(crate) fn recovering(every: u64) -> Self {
+        LucasLehmerChecks { every, max_retries: MAX_BLOCK_RETRIES }
+    }
+}
+
+/// The Lucas-Lehmer loop shared by every entry point
+///
+/// Runs from the iteration and value in `start`. Every `checks.every`
+/// iterations, and after the last one, the value is checked with
+/// `lucas_lehmer_value_is_consistent`. A failed check rolls back to the last
+/// value that passed and recomputes the block; a value that passes before the
+/// last iteration is handed to `on_verified`. `progress` receives the
+/// iteration count about `LUCAS_LEHMER_PROGRESS_STEPS` times per run, and
+/// `stop` is polled every `LUCAS_LEHMER_STOP_POLL_INTERVAL` iterations. `fault`
+/// is called after every iteration so tests can inject errors. The final
+/// value s_(p-2) is returned alongside the verdict. A block that fails more
+/// than `checks.max_retries` times in a row ends the run with
+/// `LucasLehmerStop::Inconsistent`.
+pub(crate) fn lucas_lehmer_core(
+    p: u64,
+    start: LucasLehmerCheckpoint,
+    checks: LucasLehmerChecks,
+    progress: &mut dyn FnMut(u64, u64),
+    stop: &dyn Fn() -> bool,
+    fault: &dyn Fn(u64, &mut BigUint),
+    on_verified: &mut dyn FnMut(&LucasLehmerCheckpoint) -> io::Result<()>,
+) -> Result<(CheckedLucasLehmer, BigUint), LucasLehmerStop> {
+    if p < 2 {
+        return Ok((CheckedLucasLehmer { is_prime: false, errors_corrected: 0, res64: 0 }, BigUint::zero()));
+    }
+    
+    // Special case: M2 = 3 is prime
+    if p == 2 {
+        return Ok((CheckedLucasLehmer { is_prime: true, errors_corrected: 0, res64: 0 }, BigUint::zero()));
+    }
+
+    let mp = (BigUint::one() << p) - BigUint::one();
+    let mut scratch = ModMpScratch::new(p);
+    let mut s = start.value;
+    let total = lucas_lehmer_iterations(p);
+    let report_every = (total / LUCAS_LEHMER_PROGRESS_STEPS).max(1);
+
+    // Last iteration count and value that passed the check
+    let mut verified = (start.iteration, s.clone());
+    let mut errors_corrected = 0;
+    let mut retries = 0;
+
+    // Perform the remaining iterations of the Lucas-Lehmer sequence
+    let mut i = start.iteration;
+    while i < total {
+        if i.is_multiple_of(LUCAS_LEHMER_STOP_POLL_INTERVAL) && stop() {
+            return Err(LucasLehmerStop::Stopped(i));
+        }
+        scratch.square_and_subtract_two(&mut s);
+        fault(i, &mut s);
+        i += 1;
+        if i.is_multiple_of(report_every) || i == total {
+            progress(i, total);
+        }
+
+        if (checks.every > 0 && i.is_multiple_of(checks.every)) || i == total {
+            if lucas_lehmer_value_is_consistent(&s, &mp) {
+                retries = 0;
+                verified = (i, s.clone());
+                if i < total {
+                    let checkpoint = LucasLehmerCheckpoint { exponent: p, iteration: i, value: s.clone() };
+                    on_verified(&checkpoint).map_err(LucasLehmerStop::Checkpoint)?;
+                }
+            } else {
+                retries += 1;
+                errors_corrected += 1;
+                if retries > checks.max_retries {
+                    let error = PrimalityError::ConsistencyCheckFailed { exponent: p, iteration: i };
+                    return Err(LucasLehmerStop::Inconsistent(error));
+                }
+                (i, s) = verified.clone();
+            }
+        }
+    }
+
+    // M_p is prime if and only if s = 0
+    let result = CheckedLucasLehmer {
+        is_prime: s.is_zero(),
+        errors_corrected,
+        res64: s.iter_u64_digits().next().unwrap_or(0),
+    };
+    Ok((result, s))
+}
+
+/// Error check for a Lucas-Lehmer value s_i (i >= 1) modulo M_p, p odd
+///
+/// Writing ω = 2 + √3, s_i = ω^(2^i) + ω^(-2^i), so s_i - 2 =
+/// (ω^(2^(i-1)) - ω^(-2^(i-1)))² = 3t² for an integer t, and the Jacobi symbol
+/// ((s_i - 2) / M_p) = (3 / M_p) (t / M_p)² is -1, or 0 in the rare case that
+/// t shares a factor with M_p. A value corrupted by an error has symbol +1
+/// about half the time, so each check catches a given error with probability
+/// about 1/2, for one Jacobi symbol (a few hundred iterations' worth of time)
+/// and no extra state. This is the check GIMPS uses for Lucas-Lehmer runs:
+/// the stronger Gerbicz-Li check needs the iteration to be a pure powering
+/// x -> x², which s -> s² - 2 is not.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::lucas_lehmer_value_is_consistent;
+///
+/// let m7 = BigUint::from(127u32);
+/// assert!(lucas_lehmer_value_is_consistent(&BigUint::from(14u32), &m7)); // s_1
+/// assert!(!lucas_lehmer_value_is_consistent(&BigUint::from(3u32), &m7)); // 3 - 2 = 1²
+/// ```
+pub fn lucas_lehmer_value_is_consistent(s: &BigUint, mp: &BigUint) -> bool {
+    jacobi(&((s + mp - 2u32) % mp), mp) != 1
+}
+
+/// Jacobi symbol (a / n): -1, 0 or 1
+///
+/// Computed iteratively with quadratic reciprocity, never factoring `n`. For
+/// an odd prime `n` it is the Legendre symbol, telling whether `a` is a
+/// quadratic residue modulo `n`. The Jacobi symbol is only defined for odd
+/// `n`; for even `n` (including 0) this returns the Kronecker symbol, its
+/// standard extension, so every input has an answer.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::jacobi;
+///
+/// assert_eq!(jacobi(&BigUint::from(1001u32), &BigUint::from(9907u32)), -1);
+/// assert_eq!(jacobi(&BigUint::from(2u32), &BigUint::from(7u32)), 1); // 3² ≡ 2 (mod 7)
+/// assert_eq!(jacobi(&BigUint::from(7u32), &BigUint::from(21u32)), 0);
+/// ```
+pub fn jacobi(a: &BigUint, n: &BigUint) -> i8 {
+    let low_bits = |x: &BigUint| x.iter_u64_digits().next().unwrap_or(0);
+    if n.is_zero() {
+        return if a.is_one() { 1 } else { 0 };
+    }
+
+    // Kronecker extension: (a / 2) is 0 for even a, else -1 when a ≡ 3 or 5 (mod 8)
+    let twos = n.trailing_zeros().unwrap_or(0);
+    let mut result = 1;
+    if twos > 0 {
+        if !a.bit(0) {
+            return 0;
+        }
+        if twos % 2 == 1 && matches!(low_bits(a) % 8, 3 | 5) {
+            result = -1;
+        }
+    }
+    let n = n >> twos;
+
+    let mut a = a % &n;
+    let mut n = n;
+
+    while !a.is_zero() {
+        let twos = a.trailing_zeros().unwrap_or(0);
+        a >>= twos;
+        // (2 / n) = -1 exactly when n ≡ 3 or 5 (mod 8)
+        if twos % 2 == 1 && matches!(low_bits(&n) % 8, 3 | 5) {
+            result = -result;
+        }
+        // Quadratic reciprocity: flip when both are ≡ 3 (mod 4)
+        std::mem::swap(&mut a, &mut n);
+        if low_bits(&a) % 4 == 3 && low_bits(&n) % 4 == 3 {
+            result = -result;
+        }
+        a %= &n;
+    }
+
+    if n.is_one() {
+        result
+    } else {
+        0
+    }
+}
+
+/// Lucas-Lehmer test that checks its value every `check_every` iterations
+/// and stops at the first failure
+///
+/// The check is `lucas_lehmer_value_is_consistent`, where its theory is
+/// described, and runs after every `check_every` iterations and after the
+/// last one; a `check_every` of 0 checks only the final value. Where
+/// `lucas_lehmer_test_checked` recovers from a failed check by recomputing,
+/// this reports it.
+///
+/// # Errors
+///
+/// * `InvalidExponent` if p < 2
+/// * `ConsistencyCheckFailed` with the iteration count if a check fails
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::lucas_lehmer_test_jacobi_checked;
+///
+/// assert_eq!(lucas_lehmer_test_jacobi_checked(521, 50).unwrap(), true);
+/// assert_eq!(lucas_lehmer_test_jacobi_checked(523, 50).unwrap(), false);
+/// ```
+pub fn lucas_lehmer_test_jacobi_checked(p: impl Into<Exponent>, check_every: u64) -> Result<bool, PrimalityError> {
+    let p = p.into().get();
+    lucas_lehmer_jacobi_checked_with_fault(p, check_every, &|_, _| {})
+}
+
+/// `lucas_lehmer_test_jacobi_checked` with `fault` called after every
+/// iteration, so tests can inject errors
+pub(crate) fn lucas_lehmer_jacobi_checked_with_fault(
+    p: u64,
+    check_every: u64,
+    fault: &dyn Fn(u64, &mut BigUint),
+) -> Result<bool, PrimalityError> {
+    validate_exponent(p)?;
+    let checks = LucasLehmerChecks { every: check_every, max_retries: 0 };
+    let result = lucas_lehmer_core(
+        p,
+        LucasLehmerCheckpoint::start(p),
+        checks,
+        &mut |_, _| {},
+        &|| false,
+        fault,
+        &mut |_| Ok(()),
+    );
+    match result {
+        Ok((result, _)) => Ok(result.is_prime),
+        Err(LucasLehmerStop::Inconsistent(e)) => Err(e),
+        Err(_) => unreachable!("the test is never stopped and nothing is saved"),
+    }
+}
+
+/// Result of a Lucas-Lehmer test run with error checking
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CheckedLucasLehmer {
+    /// Whether M_p is prime
+    pub is_prime: bool,
+    /// How many blocks of iterations failed the error check and were recomputed
+    pub errors_corrected: u32,
+    /// Low 64 bits of the final sequence value, as in `lucas_lehmer_residue`
+    pub res64: u64,
+}
+
+/// Lucas-Lehmer test that detects and recovers from computation errors
+///
+/// The current value is checked against an invariant of the Lucas-Lehmer
+/// sequence, the Jacobi check used by GIMPS. For exponents of 100,000 and up
+/// this happens after every eighth of the run; below that, where a rerun is
+/// cheap, only after the last iteration. If a check fails, the block since
+/// the most recent value that passed is recomputed, and `errors_corrected`
+/// counts how often that happened. A check costs a few hundred iterations,
+/// which keeps the overhead well under 1% for small exponents and a few
+/// percent at most for large ones.
+///
+/// A single error is caught with probability about 1/2 per check; see
+/// `lucas_lehmer_value_is_consistent` for why.
+///
+/// # Errors
+///
+/// `ConsistencyCheckFailed` if the same block fails the check more than
+/// three times in a row, which points to a persistent hardware fault rather
+/// than a one-off error.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::lucas_lehmer_test_checked;
+///
+/// let result = lucas_lehmer_test_checked(127).unwrap();
+/// assert!(result.is_prime);
+/// assert_eq!(result.errors_corrected, 0);
+/// ```
+pub fn lucas_lehmer_test_checked(p: impl Into<Exponent>) -> Result<CheckedLucasLehmer, PrimalityError> {
+    let p = p.into().get();
+    match lucas_lehmer_stoppable(p, &NoProgress, &|| false) {
+        Ok((result, _)) => Ok(result),
+        Err(LucasLehmerStop::Inconsistent(e)) => Err(e),
+        Err(_) => unreachable!("the test is never stopped and nothing is saved"),
+    }
+}
+
+/// Lucas-Lehmer test that also returns the GIMPS-style 64-bit residue
+///
+/// The residue ("res64") is the low 64 bits of s_(p-2), the value left after
+/// the last of the p - 2 iterations (counting s_0 = 4 as the start) and the
+/// one that is tested for zero. It is fully reduced modulo M_p, so it is 0
+/// exactly when M_p is prime. GIMPS publishes this value for every composite
+/// it tests, which lets a run be checked against theirs without repeating it;
+/// use `format_res64` to print it in their notation. For p < 3 no iterations
+/// are run and the residue is 0. Like `lucas_lehmer_test`, it panics on a
+/// persistent hardware fault.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::{format_res64, lucas_lehmer_residue};
+///
+/// assert_eq!(lucas_lehmer_residue(127), (true, 0));
+///
+/// // M11 = 2047: s_9 = 1736
+/// let (is_prime, res64) = lucas_lehmer_residue(11);
+/// assert!(!is_prime);
+/// assert_eq!(format_res64(res64), "00000000000006C8");
+/// ```
+pub fn lucas_lehmer_residue(p: impl Into<Exponent>) -> (bool, u64) {
+    let p = p.into().get();
+    let result = lucas_lehmer_test_checked(p).unwrap_or_else(|e| panic!("{e}"));
+    (result.is_prime, result.res64)
+}
+
+/// Lucas-Lehmer test that also returns the whole final value s_(p-2)
+///
+/// Where `lucas_lehmer_residue` keeps only the low 64 bits, this returns all
+/// of s_(p-2), for debugging and for callers that want to inspect it. The
+/// value is fully reduced into 0..M_p: the reduction maps M_p itself to 0,
+/// so a zero residue cannot be an unreduced M_p in disguise, and the verdict
+/// is exactly `residue == 0`. It runs with the same error checking as
+/// `lucas_lehmer_test_checked`, and like `lucas_lehmer_test` panics on a
+/// persistent hardware fault. For p < 3 no iterations are run and the
+/// residue is 0.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::lucas_lehmer_test_verbose;
+///
+/// assert_eq!(lucas_lehmer_test_verbose(7), (true, BigUint::from(0u32)));
+///
+/// // M11 = 2047: s_9 = 1736
+/// assert_eq!(lucas_lehmer_test_verbose(11), (false, BigUint::from(1736u32)));
+/// ```
+pub fn lucas_lehmer_test_verbose(p: impl Into<Exponent>) -> (bool, BigUint) {
+    let p = p.into().get();
+    let result = lucas_lehmer_core(
+        p,
+        LucasLehmerCheckpoint::start(p),
+        LucasLehmerChecks::recovering(lucas_lehmer_check_interval(p)),
+        &mut |_, _| {},
+        &|| false,
+        &|_, _| {},
+        &mut |_| Ok(()),
+    );
+    let (result, s) = lucas_lehmer_or_panic(result).expect("the test is never stopped");
+    (result.is_prime, s)
+}
+
+/// A way of running the Lucas-Lehmer test, for comparing one against another
+///
+/// Implementations differ only in how they square modulo M_p, so two of them
+/// given the same p must arrive at the same final value s_(p-2). A bug or a
+/// hardware fault that affects one squaring method but not the other shows
+/// up as residues that disagree; `CrossChecked` and
+/// `CheckConfig::cross_check_backend` use this to catch what a single
+/// backend cannot. `SchoolbookBackend` uses `BigUint` arithmetic, and
+/// `dwt::DwtBackend` (feature `fft`) uses FFT squaring.
+pub trait PrimalityBackend: Send + Sync + fmt::Debug {
+    /// Short name of the backend, for reports
+    fn name(&self) -> &str;
+
+    /// The final Lucas-Lehmer value s_(p-2) for M_p
+    ///
+    /// The value only has to be correct modulo M_p, so M_p itself may stand
+    /// for 0; compare results with `residues_equal_mod_mp`. For p = 2, where
+    /// no iterations are run, it is 0.
+    ///
+    /// # Errors
+    ///
+    /// `InvalidExponent` if p < 2, or whatever error the backend hits on the
+    /// way, such as `ConsistencyCheckFailed`.
+    fn lucas_lehmer_residue(&self, p: u64) -> Result<BigUint, PrimalityError>;
+}
+
+/// The Lucas-Lehmer test with `BigUint` squaring and the error checking of
+/// `lucas_lehmer_test_checked`, as a `PrimalityBackend`
+///
+/// This is the arithmetic the pipeline itself uses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SchoolbookBackend;
+
+impl PrimalityBackend for SchoolbookBackend {
+    fn name(&self) -> &str {
+        "schoolbook"
+    }
+
+    fn lucas_lehmer_residue(&self, p: u64) -> Result<BigUint, PrimalityError> {
+        validate_exponent(p)?;
+        match lucas_lehmer_stoppable(p, &NoProgress, &|| false) {
+            Ok((_, s)) => Ok(s),
+            Err(LucasLehmerStop::Inconsistent(e)) => Err(e),
+            Err(_) => unreachable!("the test is never stopped and nothing is saved"),
+        }
+    }
+}
+
+/// Runs `primary` and then `secondary`, and only returns the residue if the
+/// two agree
+///
+/// Costs the sum of both backends, so about twice a single run when they are
+/// equally fast.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::{CrossChecked, PrimalityBackend, SchoolbookBackend};
+///
+/// let backend = CrossChecked { primary: SchoolbookBackend, secondary: SchoolbookBackend };
+/// // M11 = 2047: s_9 = 1736
+/// assert_eq!(backend.lucas_lehmer_residue(11).unwrap(), BigUint::from(1736u32));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CrossChecked<P, S> {
+    /// The backend whose residue is returned
+    pub primary: P,
+    /// The backend that has to agree with it
+    pub secondary: S,
+}
+
+impl<P: PrimalityBackend, S: PrimalityBackend> PrimalityBackend for CrossChecked<P, S> {
+    fn name(&self) -> &str {
+        "cross-checked"
+    }
+
+    fn lucas_lehmer_residue(&self, p: u64) -> Result<BigUint, PrimalityError> {
+        let residue = self.primary.lucas_lehmer_residue(p)?;
+        cross_check_residue(p, self.primary.name(), &residue, &self.secondary)?;
+        Ok(residue)
+    }
+}
+
+/// Check that `secondary` arrives at the same residue for M_p as the backend
+/// named `primary_name`, which computed `residue`
+///
+/// # Errors
+///
+/// `BackendsDisagree` if the residues differ modulo M_p, and any error from
+/// `secondary`.
+pub(crate) fn cross_check_residue(
+    p: u64,
+    primary_name: &str,
+    residue: &BigUint,
+    secondary: &dyn PrimalityBackend,
+) -> Result<(), PrimalityError> {
+    let other = secondary.lucas_lehmer_residue(p)?;
+    if residues_equal_mod_mp(residue, &other, p) {
+        return Ok(());
+    }
+    let mp = (BigUint::one() << p) - BigUint::one();
+    let res64 = |s: &BigUint| (s % &mp).iter_u64_digits().next().unwrap_or(0);
+    Err(PrimalityError::BackendsDisagree {
+        exponent: p,
+        primary: primary_name.to_string(),
+        primary_res64: res64(residue),
+        secondary: secondary.name().to_string(),
+        secondary_res64: res64(&other),
+    })
+}
+
+/// Format a res64 the way GIMPS reports it: 16 uppercase hex digits
+pub fn format_res64(res64: u64) -> String {
+    format!("{:016X}", res64)
+}
+
+/// The program that produced a `GimpsResult`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GimpsProgram {
+    /// Always "primality_jones"
+    pub name: String,
+    /// Version of this crate
+    pub version: String,
+}
+
+/// A Lucas-Lehmer result in the JSON format of GIMPS's `results.json.txt`
+///
+/// Serializes (with the `serde` feature, or with `to_json` without it) to one
+/// line of the form PrimeNet accepts for manual results:
+///
+/// ```text
+/// {"exponent":11,"worktype":"LL","status":"C","res64":"00000000000006C8","shift-count":0,
+///  "program":{"name":"primality_jones","version":"0.2.0"},"timestamp":"2025-01-01 12:00:00"}
+/// ```
+///
+/// # Shift count
+///
+/// Prime95 and gpuOwl start the sequence from 4 · 2^shift mod M_p for a
+/// random shift, so that a first test and its double-check by the same
+/// program do not make the same mistakes at the same bits; the final value
+/// is rotated back before the res64 is taken, so it does not depend on the
+/// shift. This crate always starts from plain 4, which is shift 0, and says
+/// so in `shift_count`. A double-check of someone else's run is still
+/// independent, since theirs almost certainly used a nonzero shift; a result
+/// with shift 0 does not make a valid double-check of another shift-0 run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GimpsResult {
+    /// The exponent p of M_p
+    pub exponent: u64,
+    /// Always "LL"
+    pub worktype: String,
+    /// "P" if M_p is prime, "C" if composite
+    pub status: String,
+    /// The 64-bit residue as 16 uppercase hex digits, as `format_res64` prints it
+    pub res64: String,
+    /// The shift the sequence started from, always 0 here
+    #[cfg_attr(feature = "serde", serde(rename = "shift-count"))]
+    pub shift_count: u64,
+    /// What produced the result
+    pub program: GimpsProgram,
+    /// When the test finished, UTC, as `YYYY-MM-DD HH:MM:SS`
+    pub timestamp: String,
+}
+
+impl GimpsResult {
+    /// The result as one line of JSON, the same as serde_json would write
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"exponent\":{},\"worktype\":\"{}\",\"status\":\"{}\",\"res64\":\"{}\",\"shift-count\":{},\
+             \"program\":{{\"name\":\"{}\",\"version\":\"{}\"}},\"timestamp\":\"{}\"}}",
+            self.exponent,
+            self.worktype,
+            self.status,
+            self.res64,
+            self.shift_count,
+            self.program.name,
+            self.program.version,
+            self.timestamp
+        )
+    }
+}
+
+/// Run the Lucas-Lehmer test on M_p and record it as a GIMPS result
+///
+/// Uses `lucas_lehmer_test_checked`, so the residue has been through the
+/// Jacobi error check. Append `to_json` (or the serde serialization) to a
+/// results file to compare runs against GIMPS or submit them by hand.
+///
+/// # Errors
+///
+/// `ConsistencyCheckFailed` on a persistent hardware fault, as for
+/// `lucas_lehmer_test_checked`; no result is worth reporting then.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::export_gimps_result;
+///
+/// let result = export_gimps_result(11).unwrap();
+/// assert_eq!(result.status, "C");
+/// assert_eq!(result.res64, "00000000000006C8");
+/// assert_eq!(result.shift_count, 0);
+/// assert!(result.to_json().starts_with(r#"{"exponent":11,"worktype":"LL","status":"C""#));
+/// ```
+pub fn export_gimps_result(p: impl Into<Exponent>) -> Result<GimpsResult, PrimalityError> {
+    let p = p.into().get();
+    let result = lucas_lehmer_test_checked(p)?;
+    Ok(GimpsResult {
+        exponent: p,
+        worktype: "LL".to_string(),
+        status: if result.is_prime { "P" } else { "C" }.to_string(),
+        res64: format_res64(result.res64),
+        shift_count: 0,
+        program: GimpsProgram {
+            name: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        },
+        timestamp: chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    })
+}
+
+/// Progress of a resumable Lucas-Lehmer test on M_p
+///
+/// Stored as `key=value` lines like `MillerRabinCheckpoint`, with the sequence
+/// value written out in full as hexadecimal so a resumed run continues from
+/// exactly the same state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LucasLehmerCheckpoint {
+    /// The exponent p of the Mersenne number under test
+    pub exponent: u64,
+    /// How many iterations have been completed
+    pub iteration: u64,
+    /// The sequence value s after `iteration` iterations, reduced modulo M_p
+    pub value: BigUint,
+}
+
+impl LucasLehmerCheckpoint {
+    /// The state before the first iteration, s = 4
+    pub(crate) fn start(p: u64) -> Self {
+        Self { exponent: p, iteration: 0, value: BigUint::from(4u32) }
+    }
+
+    /// Read a checkpoint written by `save`
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let field = |key: &str| {
+            content
+                .lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("checkpoint is missing '{}'", key))
+                })
+        };
+        let invalid = |key: &str| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("checkpoint has an invalid '{}'", key))
+        };
+        Ok(Self {
+            exponent: field("exponent")?.parse().map_err(|_| invalid("exponent"))?,
+            iteration: field("iteration")?.parse().map_err(|_| invalid("iteration"))?,
+            value: BigUint::parse_bytes(field("value")?.as_bytes(), 16).ok_or_else(|| invalid("value"))?,
+        })
+    }
+
+    /// Write the checkpoint, replacing any previous one
+    ///
+    /// The new contents go to a temporary file that is then renamed over
+    /// `path`, so an interruption never leaves a half-written checkpoint.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(
+            &tmp,
+            format!(
+                "exponent={}\niteration={}\nvalue={:x}\n",
+                self.exponent, self.iteration, self.value
+            ),
+        )?;
+        fs::rename(&tmp, path)
+    }
+}
+
+/// Lucas-Lehmer test on M_p that saves its progress to a checkpoint file
+///
+/// Every `interval` iterations the current value passes the same error check
+/// as `lucas_lehmer_test_checked` and is then written to `checkpoint`, so a
+/// value that failed the check never reaches the disk. If `checkpoint` exists
+/// when the test starts, it resumes from there. The file is removed once the
+/// test reaches a verdict.
+///
+/// Each checkpoint costs about as much as a few hundred iterations, so
+/// `interval` should be in the tens of thousands for large exponents.
+///
+/// # Errors
+///
+/// Fails if the checkpoint cannot be read or written, if it belongs to a
+/// different exponent, or if its iteration count or value is impossible for
+/// M_p.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::lucas_lehmer_test_resumable;
+///
+/// let checkpoint = std::env::temp_dir().join("primality_jones_doc_ll_127.ckpt");
+/// assert!(lucas_lehmer_test_resumable(127, &checkpoint, 50).unwrap());
+/// assert!(!checkpoint.exists());
+/// ```
+pub fn lucas_lehmer_test_resumable<P: AsRef<Path>>(
+    p: impl Into<Exponent>,
+    checkpoint: P,
+    interval: u64,
+) -> io::Result<bool> {
+    let p = p.into().get();
+    Ok(resumable_lucas_lehmer(p, checkpoint.as_ref(), interval)?)
+}
+
+/// Resumable Lucas-Lehmer test on M_p that returns a `CheckOutcome`
+///
+/// Runs exactly like `lucas_lehmer_test_resumable`, but tells a damaged
+/// checkpoint apart from a failure to read or write it.
+///
+/// # Errors
+///
+/// `InvalidExponent` if p < 2, `CheckpointCorrupt` if the checkpoint does not
+/// parse, belongs to a different exponent or holds an impossible state, and
+/// `Io` if it cannot be read or written.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::{lucas_lehmer_outcome_resumable, CheckOutcome, PrimalityError};
+///
+/// let checkpoint = std::env::temp_dir().join("primality_jones_doc_ll_outcome_61.ckpt");
+/// std::fs::write(&checkpoint, "exponent=61\niteration=1000\nvalue=e\n").unwrap();
+/// let outcome = lucas_lehmer_outcome_resumable(61, &checkpoint, 10);
+/// assert!(matches!(outcome, Err(PrimalityError::CheckpointCorrupt(_))));
+///
+/// std::fs::remove_file(&checkpoint).unwrap();
+/// let outcome = lucas_lehmer_outcome_resumable(61, &checkpoint, 10);
+/// assert_eq!(outcome.unwrap(), CheckOutcome::DefinitelyPrime);
+/// ```
+pub fn lucas_lehmer_outcome_resumable<P: AsRef<Path>>(
+    p: impl Into<Exponent>,
+    checkpoint: P,
+    interval: u64,
+) -> Result<CheckOutcome, PrimalityError> {
+    let p = p.into().get();
+    validate_exponent(p)?;
+    resumable_lucas_lehmer(p, checkpoint.as_ref(), interval).map(lucas_lehmer_verdict)
+}
+
+fn resumable_lucas_lehmer(p: u64, checkpoint: &Path, interval: u64) -> Result<bool, PrimalityError> {
+    let start = if p > 2 && checkpoint.exists() {
+        let state = LucasLehmerCheckpoint::load(checkpoint).map_err(|e| match e.kind() {
+            io::ErrorKind::InvalidData => PrimalityError::CheckpointCorrupt(e.to_string()),
+            _ => PrimalityError::Io(e),
+        })?;
+        let mp = (BigUint::one() << p) - BigUint::one();
+        let invalid = |reason: String| Err(PrimalityError::CheckpointCorrupt(reason));
+        if state.exponent != p {
+            return invalid(format!("checkpoint is for M{}, not M{}", state.exponent, p));
+        }
+        if !is_valid_checkpoint_progress(p, state.iteration) {
+            return invalid(format!("checkpoint claims {} iterations for M{}", state.iteration, p));
+        }
+        if state.value >= mp || (state.iteration > 0 && !lucas_lehmer_value_is_consistent(&state.value, &mp)) {
+            return invalid(format!("checkpoint value is not a Lucas-Lehmer value for M{}", p));
+        }
+        state
+    } else {
+        LucasLehmerCheckpoint::start(p)
+    };
+
+    let result = lucas_lehmer_core(
+        p,
+        start,
+        LucasLehmerChecks::recovering(interval.max(1)),
+        &mut |_, _| {},
+        &|| false,
+        &|_, _| {},
+        &mut |state| state.save(checkpoint),
+    );
+    let is_prime = match result {
+        Ok((result, _)) => result.is_prime,
+        Err(LucasLehmerStop::Checkpoint(e)) => return Err(e.into()),
+        Err(LucasLehmerStop::Inconsistent(e)) => return Err(e),
+        Err(LucasLehmerStop::Stopped(_)) => unreachable!("the test is never stopped"),
+    };
+
+    if checkpoint.exists() {
+        fs::remove_file(checkpoint)?;
+    }
+    Ok(is_prime)
+}
+
+/// Lucas-Lehmer test that settles for a probable verdict if cancelled
+///
+/// Runs the Lucas-Lehmer test, checking `cancel` every 16 iterations. If it
+/// finishes, the result is definitive and has kind `CheckKind::LucasLehmer`. If
+/// `cancel` is set first, the test stops and a base-3 Fermat test is given
+/// `fallback_timeout` to reach a verdict instead; that result has kind
+/// `CheckKind::MillerRabin` and its message records how far the Lucas-Lehmer
+/// test got. A "composite" answer from the fallback is certain, but "prime"
+/// only means probably prime.
+///
+/// The Fermat test costs p squarings, as many as a whole Lucas-Lehmer test, so
+/// `fallback_timeout` is what bounds the call once cancelled: if the Fermat test
+/// has not finished by then, the result is an inconclusive
+/// `CheckKind::LucasLehmer` one. A zero timeout skips the fallback entirely.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::{lucas_lehmer_with_fallback, CheckKind};
+/// use std::sync::atomic::AtomicBool;
+/// use std::time::Duration;
+///
+/// let cancel = AtomicBool::new(false);
+/// let result = lucas_lehmer_with_fallback(127, &cancel, Duration::from_secs(1));
+/// assert!(result.passed);
+/// assert_eq!(result.kind, CheckKind::LucasLehmer);
+/// ```
+pub fn lucas_lehmer_with_fallback(p: impl Into<Exponent>, cancel: &AtomicBool, fallback_timeout: Duration) -> CheckResult {
+    let p = p.into().get();
+    let check_start = Instant::now();
+    match lucas_lehmer_stoppable(p, &NoProgress, &|| cancel.load(Ordering::Relaxed)) {
+        Ok((CheckedLucasLehmer { is_prime: passed, .. }, _)) => CheckResult {
+            passed,
+            message: if passed {
+                "Passed Lucas-Lehmer test (definitive)".to_string()
+            } else {
+                "Failed Lucas-Lehmer test (definitive)".to_string()
+            },
+            time_taken: check_start.elapsed(),
+            kind: CheckKind::LucasLehmer,
+            factor: None,
+            inconclusive: false,
+        },
+        Err(LucasLehmerStop::Inconsistent(e)) => CheckResult {
+            passed: false,
+            message: format!("{e} (inconclusive)"),
+            time_taken: check_start.elapsed(),
+            kind: CheckKind::LucasLehmer,
+            factor: None,
+            inconclusive: true,
+        },
+        Err(LucasLehmerStop::Checkpoint(_)) => unreachable!("nothing is saved"),
+        Err(LucasLehmerStop::Stopped(completed)) => {
+            let cancelled = format!("Lucas-Lehmer cancelled after {}/{} iterations", completed, lucas_lehmer_iterations(p));
+            let three = BigUint::from(3u32);
+            let fermat = match Instant::now().checked_add(fallback_timeout) {
+                Some(deadline) => fermat_test_stoppable(p, &three, &|| Instant::now() >= deadline),
+                None => fermat_test_stoppable(p, &three, &|| false),
+            };
+            match fermat {
+                Some(passed) => CheckResult {
+                    passed,
+                    message: format!(
+                        "{}; {} by a base-3 Fermat test",
+                        cancelled,
+                        if passed { "probably prime" } else { "composite" }
+                    ),
+                    time_taken: check_start.elapsed(),
+                    kind: CheckKind::MillerRabin,
+                    factor: None,
+                    inconclusive: false,
+                },
+                None => CheckResult {
+                    passed: false,
+                    message: format!("{}; the Fermat test ran out of time (inconclusive)", cancelled),
+                    time_taken: check_start.elapsed(),
+                    kind: CheckKind::LucasLehmer,
+                    factor: None,
+                    inconclusive: true,
+                },
+            }
+        }
+    }
+}
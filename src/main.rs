@@ -1,79 +1,775 @@
-use chrono::Local;
-use primality_jones::{check_mersenne_candidate, CheckLevel, process_candidates_parallel};
+use clap::Parser;
+use indicatif::{ProgressBar, ProgressStyle};
+use num_bigint::BigUint;
+use num_traits::One;
+use primality_jones::{
+    capabilities, check_mersenne_candidate_with_config, check_mersenne_candidate_with_config_and_reporter,
+    estimate_resources, is_mersenne_number, load_known_factors, lucas_lehmer_residue, miller_rabin_test_quiet, mod_mp,
+    mul_mod_mp, parse_worktodo_line, prime_sieve, process_candidates_parallel_with_config,
+    square_and_subtract_two_mod_mp, test_cofactor, validate_exponent, verify_against_known, CheckConfig, CheckKind,
+    CheckLevel, CheckResult, CofactorStatus, ProgressReporter,
+};
+use rayon::prelude::*;
+use std::env;
 use std::fs::File;
 use std::io::{self, BufRead, BufReader, Write};
+use std::ops::RangeInclusive;
 use std::path::Path;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 use std::time::{Duration, Instant};
-use indicatif::{ProgressBar, ProgressStyle};
 
+/// Prints each stage as it finishes and draws a bar for the Lucas-Lehmer stage
+#[derive(Default)]
+struct CliReporter {
+    stage_count: AtomicUsize,
+    bar: Mutex<Option<ProgressBar>>,
+}
 
+impl ProgressReporter for CliReporter {
+    fn on_progress(&self, kind: CheckKind, current: u64, total: u64) {
+        if kind != CheckKind::LucasLehmer {
+            return;
+        }
+        let mut bar = self.bar.lock().unwrap();
+        let bar = bar.get_or_insert_with(|| {
+            let pb = ProgressBar::new(total);
+            pb.set_style(ProgressStyle::default_bar()
+                .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} iterations ({eta})")
+                .unwrap()
+                .progress_chars("#>-"));
+            pb
+        });
+        bar.set_position(current);
+    }
 
+    fn on_stage_complete(&self, result: &CheckResult) {
+        if let Some(bar) = self.bar.lock().unwrap().take() {
+            bar.finish_and_clear();
+        }
+        let index = self.stage_count.fetch_add(1, Ordering::SeqCst) + 1;
+        let status = if result.passed {
+            "✅"
+        } else if result.inconclusive {
+            "❓"
+        } else {
+            "❌"
+        };
+        println!("{}. {} {}", index, status, result.message);
+        println!("   Time: {:?}", result.time_taken);
+        let _ = io::stdout().flush();
+    }
+}
 
+/// An exponent to test, tagged with the file it was read from
+struct Candidate {
+    exponent: u64,
+    source: String,
+}
 
+impl Candidate {
+    /// `M<p>`, followed by the source file when `show_source` is set
+    fn label(&self, show_source: bool) -> String {
+        if show_source {
+            format!("M{} (from {})", self.exponent, self.source)
+        } else {
+            format!("M{}", self.exponent)
+        }
+    }
+}
 
+/// Exit codes for `--quiet` mode, one per verdict
+const EXIT_PRIME: i32 = 0;
+const EXIT_COMPOSITE: i32 = 1;
+const EXIT_PROBABLE_PRIME: i32 = 2;
+const EXIT_INCONCLUSIVE: i32 = 3;
+const EXIT_USAGE: i32 = 64;
 
-fn main() -> io::Result<()> {
-    println!("🔍 Primality Jones - Mersenne Number Primality Tester");
-    println!("=====================================================");
+/// Exit code of `--self-test` when any check fails
+const EXIT_SELF_TEST_FAILED: i32 = 1;
+
+/// How far `--self-test` checks the known results; a second or two in a release build
+const SELF_TEST_MAX_EXPONENT: u64 = 9941;
+
+/// Exponents above this are far beyond anything that can be tested, so such a
+/// value is more likely a Mersenne number typed in place of its exponent
+const SUSPICIOUS_EXPONENT: u64 = 1_000_000_000;
+
+/// Mersenne number primality tester
+///
+/// With no arguments, reads candidates.txt and asks which check level to run.
+/// Any argument selects batch mode, which never prompts and reports through
+/// its exit code: 0 PRIME, 1 COMPOSITE, 2 PROBABLE_PRIME, 3 INCONCLUSIVE and
+/// 64 for bad arguments. With several candidates, the code is that of the
+/// first one that is not PRIME.
+#[derive(Parser, Debug)]
+#[command(version)]
+struct Cli {
+    /// Exponents to test, as 127 or M127
+    #[arg(value_name = "EXPONENT", value_parser = parse_valid_exponent)]
+    exponents: Vec<u64>,
+
+    /// Another exponent to test; may be repeated
+    #[arg(short, long = "exponent", value_name = "EXPONENT", value_parser = parse_valid_exponent)]
+    exponent_flags: Vec<u64>,
+
+    /// Read exponents from FILE (one per line, or a GIMPS worktodo.txt); may be repeated
+    #[arg(short, long = "file", visible_alias = "candidates", value_name = "FILE")]
+    files: Vec<String>,
+
+    /// Read exponents from standard input, one per line
+    #[arg(long)]
+    stdin: bool,
+
+    /// Test every prime exponent in START..END (END excluded) or START..=END
+    #[arg(short, long, value_name = "START..END", value_parser = parse_range)]
+    range: Option<RangeInclusive<u64>>,
+
+    /// How far to test: 1-6, or pre-screen, trial-factoring, p-minus-one,
+    /// probabilistic, lucas-lehmer or full [default: lucas-lehmer]
+    #[arg(short, long, value_parser = parse_check_level)]
+    level: Option<CheckLevel>,
 
-    // Check if candidates.txt exists
-    if !Path::new("candidates.txt").exists() {
-        println!("❌ candidates.txt not found. Creating sample file...");
-        create_sample_candidates_file()?;
-        println!("✅ Created candidates.txt with sample data");
-        println!("   Edit this file to add your own Mersenne exponents to test");
-        println!("   Each line should contain one exponent (e.g., 31, 61, 89, 107, 127)");
-        return Ok(());
+    /// Print one JSON object per candidate
+    #[arg(long, group = "format")]
+    json: bool,
+
+    /// Print a CSV table with one row per stage of each candidate
+    #[arg(long, group = "format")]
+    csv: bool,
+
+    /// Print only one verdict line per candidate
+    #[arg(short, long, group = "format")]
+    quiet: bool,
+
+    /// Write the --json, --csv or --quiet output to FILE instead of stdout
+    #[arg(short, long, value_name = "FILE", requires = "format")]
+    output: Option<String>,
+
+    /// Run on at most N threads
+    #[arg(short = 'j', long, value_name = "N")]
+    threads: Option<usize>,
+
+    /// Exponents with known factors, one `M<exp>: <factor>, ...` per line
+    #[arg(long, value_name = "FILE")]
+    known_factors: Option<String>,
+
+    /// Give up on the Lucas-Lehmer stage after this long (reported inconclusive)
+    #[arg(long, value_name = "SECONDS")]
+    ll_timeout: Option<u64>,
+
+    /// Skip the stages that compute modulo M_p when they would need more memory than this (reported inconclusive)
+    #[arg(long, value_name = "MB")]
+    max_memory: Option<u64>,
+
+    /// Test the cofactor of M<EXPONENT> left after dividing out --factors
+    #[arg(long, value_name = "EXPONENT", value_parser = parse_valid_exponent, conflicts_with_all = ["json", "csv"])]
+    cofactor: Option<u64>,
+
+    /// Known factors for --cofactor, comma separated
+    #[arg(long, value_name = "Q1,Q2,...", requires = "cofactor")]
+    factors: Option<String>,
+
+    /// Print the version and optional features as JSON
+    #[arg(long)]
+    capabilities: bool,
+
+    /// Check this build against known results and exit non-zero if anything is wrong
+    #[arg(long)]
+    self_test: bool,
+}
+
+fn main() {
+    let interactive = env::args_os().len() <= 1;
+    let cli = Cli::try_parse().unwrap_or_else(|e| {
+        if e.use_stderr() {
+            let _ = e.print();
+            process::exit(EXIT_USAGE);
+        }
+        e.exit()
+    });
+
+    match run(&cli, interactive) {
+        Ok(code) => process::exit(code),
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            process::exit(EXIT_USAGE);
+        }
+    }
+}
+
+/// Carry out whatever the command line asks for, returning the exit code
+fn run(cli: &Cli, interactive: bool) -> io::Result<i32> {
+    if let Some(threads) = cli.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .map_err(|e| invalid_input(format!("Could not start {} threads: {}", threads, e)))?;
     }
 
-    // Read candidates from file
-    let candidates = read_candidates_file()?;
+    // Machine-readable description of this build, for orchestration tools
+    if cli.capabilities {
+        print_capabilities();
+        return Ok(EXIT_PRIME);
+    }
+
+    if cli.self_test {
+        return Ok(run_self_test());
+    }
+
+    let config = load_config(cli)?;
+    let level = cli.level.unwrap_or(CheckLevel::LucasLehmer);
+
+    // Cofactor mode: primality_jones --cofactor M<exp> --factors q1,q2
+    if let Some(p) = cli.cofactor {
+        let known_factors = parse_factors(cli.factors.as_deref().unwrap_or(""))?;
+        if cli.quiet {
+            let mut out = open_output(cli)?;
+            let code = write_cofactor_verdict(&mut out, p, &known_factors)?;
+            out.flush()?;
+            return Ok(code);
+        }
+        print_banner();
+        return run_cofactor(p, &known_factors);
+    }
+
+    if cli.json || cli.csv || cli.quiet {
+        let candidates = cli_candidates(cli)?;
+        let mut out = open_output(cli)?;
+        let exponents = candidates.iter().map(|c| c.exponent).collect();
+        let results = process_candidates_parallel_with_config(exponents, level, &config);
+        if cli.json {
+            write_json(&mut out, &results)?;
+        } else if cli.csv {
+            write_csv(&mut out, &results)?;
+        } else {
+            write_verdicts(&mut out, &results)?;
+        }
+        out.flush()?;
+        return Ok(batch_exit_code(&results));
+    }
+
+    print_banner();
+
+    let candidates = if interactive {
+        if !Path::new("candidates.txt").exists() {
+            println!("❌ candidates.txt not found. Creating sample file...");
+            create_sample_candidates_file()?;
+            println!("✅ Created candidates.txt with sample data");
+            println!("   Edit this file to add your own Mersenne exponents to test");
+            println!("   Each line should contain one exponent (e.g., 31, 61, 89, 107, 127)");
+            return Ok(EXIT_PRIME);
+        }
+        read_candidates_file("candidates.txt", true)?
+    } else {
+        cli_candidates(cli)?
+    };
+    // Only worth naming the source when there is more than one
+    let show_sources = candidates.iter().any(|c| c.source != candidates[0].source);
+
     if candidates.is_empty() {
-        println!("❌ No valid candidates found in candidates.txt");
-        return Ok(());
+        println!("❌ No valid candidates found");
+        return Ok(if interactive { EXIT_PRIME } else { EXIT_USAGE });
     }
 
     println!("📋 Found {} candidates to test", candidates.len());
-    println!("   Candidates: {:?}", candidates);
+    println!(
+        "   Candidates: {}",
+        candidates.iter().map(|c| c.label(show_sources)).collect::<Vec<_>>().join(", ")
+    );
 
     // Ask user for check level
-    let level = get_check_level()?;
+    let level = if interactive { get_check_level()? } else { level };
     println!("🔬 Using check level: {}", level.description());
 
     // Process candidates
     let start_time = Instant::now();
     
-    if candidates.len() > 1 {
+    let results = if candidates.len() > 1 {
         // Use parallel processing for multiple candidates
         println!("🚀 Using parallel processing for {} candidates", candidates.len());
-        let results = process_candidates_parallel(candidates, level);
+        let exponents: Vec<u64> = candidates.iter().map(|c| c.exponent).collect();
+        let results = check_with_progress(&exponents, level, &config);
         
         // Display results
-        display_parallel_results(results, start_time);
+        display_parallel_results(&candidates, &results, level, start_time, show_sources);
+        results
     } else {
         // Single candidate processing
-        let p = candidates[0];
+        let p = candidates[0].exponent;
         println!("🔍 Testing M{}...", p);
-        
-        let results = check_mersenne_candidate(p, level);
-        display_single_result(p, results, start_time);
+        warn_if_slow(p, level);
+        println!("\n📊 Results for M{}:", p);
+        println!("{}", "=".repeat(50));
+        io::stdout().flush()?;
+
+        // Stages are printed by the reporter as they complete
+        let results =
+            check_mersenne_candidate_with_config_and_reporter(p, level, &config, &CliReporter::default());
+        display_single_result(p, &results, start_time);
+        vec![(p, results)]
+    };
+
+    Ok(if interactive { EXIT_PRIME } else { batch_exit_code(&results) })
+}
+
+/// Tell the user up front when a single check will take more than a minute
+fn warn_if_slow(p: u64, level: CheckLevel) {
+    let estimate = estimate_resources(p, level);
+    let secs = estimate.estimated_duration.as_secs_f64();
+    if secs < 60.0 {
+        return;
+    }
+    let time = if secs < 3600.0 {
+        format!("{:.0} minutes", secs / 60.0)
+    } else if secs < 48.0 * 3600.0 {
+        format!("{:.1} hours", secs / 3600.0)
+    } else {
+        format!("{:.0} days", secs / 86400.0)
+    };
+    println!(
+        "⚠️  M{} has {} digits; expect roughly {} and {:.1} MB of memory",
+        p,
+        estimate.decimal_digits,
+        time,
+        estimate.bytes_required as f64 / 1e6
+    );
+}
+
+/// One `--self-test` check, returning what it verified or what went wrong
+type SelfTestCheck = fn() -> Result<String, String>;
+
+/// Run each self-test check, print a pass/fail line for it and a summary,
+/// and return the exit code
+fn run_self_test() -> i32 {
+    let checks: [(&str, SelfTestCheck); 4] = [
+        ("Known results", self_test_known_results),
+        ("Mersenne arithmetic", self_test_arithmetic),
+        ("Lucas-Lehmer residues", self_test_residues),
+        ("Miller-Rabin", self_test_miller_rabin),
+    ];
+
+    println!("🧪 Primality Jones self-test");
+    let start = Instant::now();
+    let mut passed = 0;
+    for (name, check) in checks {
+        let check_start = Instant::now();
+        match check() {
+            Ok(details) => {
+                passed += 1;
+                println!("  ✅ {}: {} ({:.2?})", name, details, check_start.elapsed());
+            }
+            Err(details) => println!("  ❌ {}: {} ({:.2?})", name, details, check_start.elapsed()),
+        }
+    }
+
+    let verdict = if passed == checks.len() { "passed" } else { "FAILED" };
+    println!("Self-test {}: {}/{} checks in {:.2?}", verdict, passed, checks.len(), start.elapsed());
+    if passed == checks.len() {
+        EXIT_PRIME
+    } else {
+        EXIT_SELF_TEST_FAILED
+    }
+}
+
+/// The Lucas-Lehmer test against every known answer up to `SELF_TEST_MAX_EXPONENT`
+fn self_test_known_results() -> Result<String, String> {
+    let report = verify_against_known(SELF_TEST_MAX_EXPONENT);
+    if report.passed() {
+        return Ok(format!(
+            "{} primes and {} composites up to M{}",
+            report.primes_checked, report.composites_checked, report.max_exponent
+        ));
+    }
+    let mismatches: Vec<String> = report
+        .mismatches
+        .iter()
+        .map(|m| format!("M{} ({})", m.exponent, if m.expected_prime { "prime" } else { "composite" }))
+        .collect();
+    Err(format!("wrong about {}", mismatches.join(", ")))
+}
+
+/// The fast reductions modulo M_p against plain `%`
+fn self_test_arithmetic() -> Result<String, String> {
+    let exponents = [7u64, 31, 61, 89, 127, 521, 4423];
+    let mut compared = 0;
+    for p in exponents {
+        let mp = (BigUint::one() << p) - BigUint::one();
+        // Edge cases around M_p and its square, and powers of 3 (about 1.585
+        // bits per power) spread over sizes up to 2p bits
+        let mut values = vec![BigUint::from(0u32), &mp - 1u32, mp.clone(), &mp + 1u32, &mp * &mp - 1u32];
+        values.extend((1..=8).map(|i| BigUint::from(3u32).pow((2 * p * i / 8 * 100 / 159) as u32)));
+        for k in &values {
+            let r = k % &mp;
+            if mod_mp(k, p) != r
+                || mul_mod_mp(&r, &r, p) != (&r * &r) % &mp
+                || square_and_subtract_two_mod_mp(&r, p) != (&r * &r + &mp * 2u32 - 2u32) % &mp
+            {
+                return Err(format!("wrong result modulo M{}", p));
+            }
+            compared += 1;
+        }
+    }
+    Ok(format!("{} values modulo {} Mersenne numbers match plain division", compared, exponents.len()))
+}
+
+/// Residues against a Lucas-Lehmer sequence computed with plain division
+fn self_test_residues() -> Result<String, String> {
+    let exponents = [11u64, 23, 29, 37, 41, 43, 47, 53];
+    for p in exponents {
+        let mp = (BigUint::one() << p) - BigUint::one();
+        let mut s = BigUint::from(4u32);
+        for _ in 0..p - 2 {
+            s = (&s * &s + &mp - 2u32) % &mp;
+        }
+        let expected = s.iter_u64_digits().next().unwrap_or(0);
+        if lucas_lehmer_residue(p) != (false, expected) {
+            return Err(format!("wrong res64 for M{}", p));
+        }
+    }
+    Ok(format!("res64 correct for {} composites", exponents.len()))
+}
+
+/// Miller-Rabin on a few primes and composites
+fn self_test_miller_rabin() -> Result<String, String> {
+    let timeout = Duration::from_secs(60);
+    for (p, prime) in [(31u64, true), (61, true), (127, true), (29, false), (67, false), (101, false)] {
+        if miller_rabin_test_quiet(p, 10, Instant::now(), timeout) != prime {
+            return Err(format!("wrong about M{}", p));
+        }
+    }
+    Ok("3 primes and 3 composites classified correctly".to_string())
+}
+
+/// Print the version and compiled-in features as a single JSON object
+fn print_capabilities() {
+    let caps = capabilities();
+    let features: Vec<String> = caps.features.iter().map(|f| format!("\"{}\"", f)).collect();
+    println!("{{\"version\":\"{}\",\"features\":[{}]}}", caps.version, features.join(","));
+}
+
+/// The exponents named on the command line, the primes in `--range`, those
+/// read from standard input, then those in each `--file`; candidates.txt
+/// stands in when there are none
+fn cli_candidates(cli: &Cli) -> io::Result<Vec<Candidate>> {
+    let mut candidates: Vec<Candidate> = cli
+        .exponents
+        .iter()
+        .chain(&cli.exponent_flags)
+        .map(|&exponent| Candidate { exponent, source: "command line".to_string() })
+        .collect();
+    if let Some(range) = &cli.range {
+        let primes = prime_sieve(*range.end()).skip_while(|p| p < range.start());
+        candidates.extend(primes.map(|exponent| Candidate { exponent, source: "range".to_string() }));
+    }
+    if cli.stdin {
+        candidates.extend(read_candidates(io::stdin().lock(), "stdin", false)?);
     }
+    let default_file = ["candidates.txt".to_string()];
+    let no_other_source = candidates.is_empty() && cli.range.is_none() && !cli.stdin;
+    let files = if no_other_source && cli.files.is_empty() { &default_file[..] } else { &cli.files[..] };
+    for path in files {
+        candidates.extend(read_candidates_file(path, false)?);
+    }
+    if candidates.is_empty() {
+        return Err(invalid_input("No candidates to test".to_string()));
+    }
+    Ok(candidates)
+}
+
+/// Parse a range of exponents given as `START..END` (END excluded) or `START..=END`
+fn parse_range(range: &str) -> io::Result<RangeInclusive<u64>> {
+    let invalid = || invalid_input(format!("Could not parse range '{}' (expected START..END or START..=END)", range));
+    let (start, end) = range.split_once("..").ok_or_else(invalid)?;
+    let start = start.trim().parse::<u64>().map_err(|_| invalid())?;
+    let (end, inclusive) = match end.strip_prefix('=') {
+        Some(end) => (end, true),
+        None => (end, false),
+    };
+    let end = end.trim().parse::<u64>().map_err(|_| invalid())?;
+    // START..0 becomes START..=0, which holds no primes either
+    Ok(start..=if inclusive { end } else { end.saturating_sub(1) })
+}
+
+/// Check candidates in parallel, with a progress bar over the whole batch
+fn check_with_progress(exponents: &[u64], level: CheckLevel, config: &CheckConfig) -> Vec<(u64, Vec<CheckResult>)> {
+    let bar = ProgressBar::new(exponents.len() as u64);
+    bar.set_style(ProgressStyle::default_bar()
+        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} candidates ({eta})")
+        .unwrap()
+        .progress_chars("#>-"));
+    let results = exponents
+        .par_iter()
+        .map(|&p| {
+            let results = check_mersenne_candidate_with_config(p, level, config);
+            bar.inc(1);
+            (p, results)
+        })
+        .collect();
+    bar.finish_and_clear();
+    results
+}
+
+/// Build the pipeline options from `--known-factors`, `--ll-timeout` and `--max-memory`
+fn load_config(cli: &Cli) -> io::Result<CheckConfig> {
+    let mut config = CheckConfig::default();
+    if let Some(path) = &cli.known_factors {
+        config.known_factors = load_known_factors(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+    }
+    config.lucas_lehmer_timeout = cli.ll_timeout.map(Duration::from_secs);
+    config.max_memory_bytes = cli.max_memory.map(|mb| mb.saturating_mul(1_000_000));
+    Ok(config)
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// Parse an exponent given on the command line, rejecting 0 and 1
+fn parse_valid_exponent(exponent: &str) -> io::Result<u64> {
+    let p = parse_exponent(exponent)?;
+    validate_exponent(p)?;
+    Ok(p)
+}
+
+/// Parse an exponent given as `127` or `M127`
+fn parse_exponent(exponent: &str) -> io::Result<u64> {
+    exponent
+        .trim_start_matches(['M', 'm'])
+        .parse::<u64>()
+        .map_err(|_| invalid_input(format!("Could not parse exponent '{}'", exponent)))
+}
+
+/// Map a level given on the command line, either the 1-6 of the interactive
+/// menu or its name
+fn parse_check_level(level: &str) -> io::Result<CheckLevel> {
+    match level.to_ascii_lowercase().as_str() {
+        "1" | "pre-screen" | "prescreen" => Ok(CheckLevel::PreScreen),
+        "2" | "trial-factoring" => Ok(CheckLevel::TrialFactoring),
+        "3" | "p-minus-one" | "p-1" => Ok(CheckLevel::PMinusOne),
+        "4" | "probabilistic" => Ok(CheckLevel::Probabilistic),
+        "5" | "lucas-lehmer" => Ok(CheckLevel::LucasLehmer),
+        "6" | "full" => Ok(CheckLevel::Full),
+        _ => Err(invalid_input(format!(
+            "Invalid check level '{}' (expected 1-6 or pre-screen, trial-factoring, p-minus-one, probabilistic, lucas-lehmer, full)",
+            level
+        ))),
+    }
+}
+
+/// Write nothing but a verdict line for each candidate
+fn write_verdicts(out: &mut dyn Write, results: &[(u64, Vec<CheckResult>)]) -> io::Result<()> {
+    for (p, results) in results {
+        let (verdict, code) = verdict(results);
+        match results.iter().find_map(|r| r.factor.as_ref()) {
+            Some(q) if code == EXIT_COMPOSITE => writeln!(out, "M{} {} factor={}", p, verdict, q)?,
+            _ => writeln!(out, "M{} {}", p, verdict)?,
+        }
+    }
+    Ok(())
+}
+
+/// The exit code for a batch: PRIME only if every candidate is, and otherwise
+/// the code of the first candidate that is not
+fn batch_exit_code(results: &[(u64, Vec<CheckResult>)]) -> i32 {
+    results
+        .iter()
+        .map(|(_, results)| verdict(results).1)
+        .find(|&code| code != EXIT_PRIME)
+        .unwrap_or(EXIT_PRIME)
+}
+
+/// The overall verdict on a candidate and the matching exit code
+///
+/// PROBABLE_PRIME means every check run passed, but Lucas-Lehmer was not
+/// among them; INCONCLUSIVE means a check timed out and nothing definitive
+/// followed.
+fn verdict(results: &[CheckResult]) -> (&'static str, i32) {
+    if is_composite(results) {
+        ("COMPOSITE", EXIT_COMPOSITE)
+    } else if is_inconclusive(results) {
+        ("INCONCLUSIVE", EXIT_INCONCLUSIVE)
+    } else if results.iter().any(|r| r.kind == CheckKind::LucasLehmer) {
+        ("PRIME", EXIT_PRIME)
+    } else {
+        ("PROBABLE_PRIME", EXIT_PROBABLE_PRIME)
+    }
+}
 
+/// Write one JSON object per candidate
+///
+/// Each line has the form `{"exponent":127,"verdict":"PRIME","results":[...]}`,
+/// with one entry per stage giving its kind, passed, inconclusive, message,
+/// time in seconds and factor (a decimal string, or null).
+fn write_json(out: &mut dyn Write, results: &[(u64, Vec<CheckResult>)]) -> io::Result<()> {
+    for (p, results) in results {
+        let stages: Vec<String> = results.iter().map(stage_json).collect();
+        writeln!(
+            out,
+            "{{\"exponent\":{},\"verdict\":\"{}\",\"results\":[{}]}}",
+            p,
+            verdict(results).0,
+            stages.join(",")
+        )?;
+    }
+    Ok(())
+}
+
+/// Write a CSV table with a header and one row per stage of each candidate
+///
+/// The columns are `exponent,level,passed,message,time_ns`, where `level` is
+/// the check level the stage belongs to, named as `--level` accepts it.
+fn write_csv(out: &mut dyn Write, results: &[(u64, Vec<CheckResult>)]) -> io::Result<()> {
+    writeln!(out, "exponent,level,passed,message,time_ns")?;
+    for (p, results) in results {
+        for result in results {
+            writeln!(
+                out,
+                "{},{},{},{},{}",
+                p,
+                level_name(CheckLevel::from(result.kind)),
+                result.passed,
+                csv_field(&result.message),
+                result.time_taken.as_nanos()
+            )?;
+        }
+    }
     Ok(())
 }
 
+/// The name `--level` accepts for `level`
+fn level_name(level: CheckLevel) -> &'static str {
+    match level {
+        CheckLevel::PreScreen => "pre-screen",
+        CheckLevel::TrialFactoring => "trial-factoring",
+        CheckLevel::PMinusOne => "p-minus-one",
+        CheckLevel::Probabilistic => "probabilistic",
+        CheckLevel::LucasLehmer => "lucas-lehmer",
+        CheckLevel::Full => "full",
+    }
+}
+
+/// Quote `s` for a CSV file when it holds a comma, quote or line break
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// One stage's result as a JSON object
+fn stage_json(result: &CheckResult) -> String {
+    let factor = result.factor.as_ref().map_or("null".to_string(), |q| format!("\"{}\"", q));
+    format!(
+        "{{\"kind\":\"{:?}\",\"passed\":{},\"inconclusive\":{},\"message\":{},\"time_secs\":{},\"factor\":{}}}",
+        result.kind,
+        result.passed,
+        result.inconclusive,
+        json_string(&result.message),
+        result.time_taken.as_secs_f64(),
+        factor
+    )
+}
+
+/// Quote `s` as a JSON string
+fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Parse the comma separated --factors list
+fn parse_factors(factors: &str) -> io::Result<Vec<BigUint>> {
+    factors
+        .split(',')
+        .map(str::trim)
+        .filter(|f| !f.is_empty())
+        .map(|f| f.parse::<BigUint>().map_err(|_| invalid_input(format!("Could not parse factor '{}'", f))))
+        .collect()
+}
+
+/// The `--quiet` verdict on the cofactor of M_p and its exit code; a factor
+/// that does not divide M_p is a usage error
+fn cofactor_verdict(p: u64, status: &CofactorStatus) -> io::Result<(&'static str, i32)> {
+    Ok(match status {
+        CofactorStatus::ProbablePrime => ("COFACTOR_PROBABLE_PRIME", EXIT_PROBABLE_PRIME),
+        CofactorStatus::Composite => ("COFACTOR_COMPOSITE", EXIT_COMPOSITE),
+        CofactorStatus::FullyFactored => ("FULLY_FACTORED", EXIT_COMPOSITE),
+        CofactorStatus::InvalidFactor(q) => return Err(invalid_input(format!("{} does not divide M{}", q, p))),
+    })
+}
+
+/// One `M<p> <verdict>` line for the cofactor, returning the exit code
+fn write_cofactor_verdict(out: &mut dyn Write, p: u64, known_factors: &[BigUint]) -> io::Result<i32> {
+    let result = test_cofactor(p, known_factors, 20);
+    let (verdict, code) = cofactor_verdict(p, &result.status)?;
+    writeln!(out, "M{} {}", p, verdict)?;
+    Ok(code)
+}
+
+fn run_cofactor(p: u64, known_factors: &[BigUint]) -> io::Result<i32> {
+    println!("🔍 Testing the cofactor of M{} after {} known factor(s)...", p, known_factors.len());
+    let start_time = Instant::now();
+    let result = test_cofactor(p, known_factors, 20);
+    let (_, code) = cofactor_verdict(p, &result.status)?;
+
+    match result.status {
+        CofactorStatus::ProbablePrime => {
+            println!("🎉 Cofactor of M{} is a PROBABLE PRIME ({} bits)", p, result.cofactor.bits());
+        }
+        CofactorStatus::Composite => {
+            println!("💔 Cofactor of M{} is COMPOSITE ({} bits)", p, result.cofactor.bits());
+        }
+        CofactorStatus::FullyFactored => {
+            println!("✅ The known factors fully factor M{}", p);
+        }
+        CofactorStatus::InvalidFactor(_) => unreachable!("cofactor_verdict rejects invalid factors"),
+    }
+    println!("⏱️  Total time: {:?}", start_time.elapsed());
+
+    Ok(code)
+}
+
+fn print_banner() {
+    println!("🔍 Primality Jones - Mersenne Number Primality Tester");
+    println!("=====================================================");
+}
+
+/// Where the `--json`, `--csv` and `--quiet` output goes
+fn open_output(cli: &Cli) -> io::Result<Box<dyn Write>> {
+    Ok(match &cli.output {
+        Some(path) => Box::new(io::BufWriter::new(
+            File::create(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?,
+        )),
+        None => Box::new(io::stdout().lock()),
+    })
+}
+
 fn create_sample_candidates_file() -> io::Result<()> {
     let mut file = File::create("candidates.txt")?;
     writeln!(file, "# Sample Mersenne exponents to test")?;
     writeln!(file, "# Each line should contain one exponent")?;
     writeln!(file, "# Lines starting with # are ignored")?;
-    writeln!(file, "")?;
+    writeln!(file)?;
     writeln!(file, "31")?;
     writeln!(file, "61")?;
     writeln!(file, "89")?;
     writeln!(file, "107")?;
     writeln!(file, "127")?;
-    writeln!(file, "")?;
+    writeln!(file)?;
     writeln!(file, "# Add your own exponents below:")?;
     writeln!(file, "# 521")?;
     writeln!(file, "# 607")?;
@@ -81,30 +777,40 @@ fn create_sample_candidates_file() -> io::Result<()> {
     Ok(())
 }
 
-fn read_candidates_file() -> io::Result<Vec<u64>> {
-    let file = File::open("candidates.txt")?;
-    let reader = BufReader::new(file);
+/// Read the exponents listed in `path`
+///
+/// With `confirm` set, a value that is really a Mersenne number 2^k - 1 is
+/// queried interactively; otherwise it is taken as written.
+fn read_candidates_file(path: &str, confirm: bool) -> io::Result<Vec<Candidate>> {
+    let file = File::open(path)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+    read_candidates(BufReader::new(file), path, confirm)
+}
+
+/// Read exponents, one per line as `127` or `M127` (or a worktodo.txt entry),
+/// warning about lines that cannot be parsed; `source` names the input
+fn read_candidates(reader: impl BufRead, source: &str, confirm: bool) -> io::Result<Vec<Candidate>> {
     let mut candidates = Vec::new();
 
     for (line_num, line) in reader.lines().enumerate() {
         let line = line?;
         let trimmed = line.trim();
         
-        // Skip empty lines and comments
-        if trimmed.is_empty() || trimmed.starts_with('#') {
+        // Skip empty lines, comments and worktodo.txt worker headers
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with('[') {
             continue;
         }
         
-        match trimmed.parse::<u64>() {
-            Ok(p) => {
-                if p > 0 {
-                    candidates.push(p);
-                } else {
-                    eprintln!("⚠️  Warning: Invalid exponent on line {}: {}", line_num + 1, p);
-                }
-            }
+        match parse_exponent(trimmed).or_else(|e| parse_worktodo_line(trimmed).ok_or(e)) {
+            Ok(p) => match validate_exponent(p) {
+                Ok(()) => candidates.push(Candidate {
+                    exponent: if confirm { confirm_exponent(p)? } else { p },
+                    source: source.to_string(),
+                }),
+                Err(e) => eprintln!("⚠️  Warning: Invalid exponent on {} line {}: {}", source, line_num + 1, e),
+            },
             Err(_) => {
-                eprintln!("⚠️  Warning: Could not parse line {}: '{}'", line_num + 1, trimmed);
+                eprintln!("⚠️  Warning: Could not parse {} line {}: '{}'", source, line_num + 1, trimmed);
             }
         }
     }
@@ -112,13 +818,32 @@ fn read_candidates_file() -> io::Result<Vec<u64>> {
     Ok(candidates)
 }
 
+/// Offer to replace a huge value that is really 2^k - 1 with the exponent k
+fn confirm_exponent(p: u64) -> io::Result<u64> {
+    let Some(k) = is_mersenne_number(p).filter(|_| p > SUSPICIOUS_EXPONENT) else {
+        return Ok(p);
+    };
+
+    print!("⚠️  {} is 2^{} - 1. Did you mean M{}? [Y/n]: ", p, k, k);
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    match input.trim() {
+        "" | "y" | "Y" | "yes" => Ok(k),
+        _ => Ok(p),
+    }
+}
+
 fn get_check_level() -> io::Result<CheckLevel> {
     println!("\n🔬 Choose check level:");
     println!("1. PreScreen (instant) - Check if exponent is prime");
     println!("2. TrialFactoring (~1s) - Check for small factors");
-    println!("3. Probabilistic (seconds-minutes) - Miller-Rabin test");
-    println!("4. LucasLehmer (minutes-hours) - Definitive test");
-    print!("Enter choice (1-4) [default: 4]: ");
+    println!("3. PMinusOne (seconds) - P-1 factoring");
+    println!("4. Probabilistic (seconds-minutes) - Miller-Rabin test");
+    println!("5. LucasLehmer (minutes-hours) - Definitive test");
+    println!("6. Full (minutes-hours) - Deeper factoring, then Lucas-Lehmer with res64");
+    print!("Enter choice (1-6) [default: 5]: ");
     io::stdout().flush()?;
 
     let mut input = String::new();
@@ -127,8 +852,10 @@ fn get_check_level() -> io::Result<CheckLevel> {
     match input.trim() {
         "1" => Ok(CheckLevel::PreScreen),
         "2" => Ok(CheckLevel::TrialFactoring),
-        "3" => Ok(CheckLevel::Probabilistic),
-        "4" | "" => Ok(CheckLevel::LucasLehmer),
+        "3" => Ok(CheckLevel::PMinusOne),
+        "4" => Ok(CheckLevel::Probabilistic),
+        "5" | "" => Ok(CheckLevel::LucasLehmer),
+        "6" => Ok(CheckLevel::Full),
         _ => {
             println!("Invalid choice, using LucasLehmer");
             Ok(CheckLevel::LucasLehmer)
@@ -136,63 +863,77 @@ fn get_check_level() -> io::Result<CheckLevel> {
     }
 }
 
-fn display_single_result(p: u64, results: Vec<primality_jones::CheckResult>, start_time: Instant) {
-    println!("\n📊 Results for M{}:", p);
-    println!("{}", "=".repeat(50));
-    
-    let mut all_passed = true;
-    for (i, result) in results.iter().enumerate() {
-        let status = if result.passed { "✅" } else { "❌" };
-        println!("{}. {} {}", i + 1, status, result.message);
-        println!("   Time: {:?}", result.time_taken);
-        
-        if !result.passed {
-            all_passed = false;
-        }
-    }
-    
+/// Whether some check proved M_p composite (an inconclusive check proves nothing)
+fn is_composite(results: &[CheckResult]) -> bool {
+    results.iter().any(|r| !r.passed && !r.inconclusive)
+}
+
+/// Whether a check was inconclusive and no definitive Lucas-Lehmer test followed
+fn is_inconclusive(results: &[CheckResult]) -> bool {
+    results.iter().any(|r| r.inconclusive)
+        && !results.iter().any(|r| r.kind == CheckKind::LucasLehmer && !r.inconclusive)
+}
+
+fn display_single_result(p: u64, results: &[CheckResult], start_time: Instant) {
     let total_time = start_time.elapsed();
     println!("\n⏱️  Total time: {:?}", total_time);
     
-    if all_passed {
-        println!("🎉 M{} is PRIME!", p);
-    } else {
+    if is_composite(results) {
         println!("💔 M{} is COMPOSITE", p);
+    } else if is_inconclusive(results) {
+        println!("❓ M{} is INCONCLUSIVE (a check timed out)", p);
+    } else {
+        println!("🎉 M{} is PRIME!", p);
     }
 }
 
-fn display_parallel_results(results: Vec<(u64, Vec<primality_jones::CheckResult>)>, start_time: Instant) {
+fn display_parallel_results(
+    candidates: &[Candidate],
+    results: &[(u64, Vec<CheckResult>)],
+    level: CheckLevel,
+    start_time: Instant,
+    show_sources: bool,
+) {
     println!("\n📊 Parallel Processing Results:");
     println!("{}", "=".repeat(60));
     
     let mut primes = Vec::new();
     let mut composites = Vec::new();
     
-    for (p, candidate_results) in results {
-        let all_passed = candidate_results.iter().all(|r| r.passed);
+    // Results come back in the same order as the candidates
+    for (candidate, (_, candidate_results)) in candidates.iter().zip(results) {
+        let label = candidate.label(show_sources);
         let total_time: std::time::Duration = candidate_results.iter()
             .map(|r| r.time_taken)
             .sum();
         
-        if all_passed {
-            primes.push((p, total_time));
-            println!("🎉 M{}: PRIME (took {:?})", p, total_time);
+        if is_composite(candidate_results) {
+            println!("💔 {}: COMPOSITE (took {:?})", label, total_time);
+            composites.push((label, total_time));
+        } else if is_inconclusive(candidate_results) {
+            println!("❓ {}: INCONCLUSIVE (took {:?})", label, total_time);
         } else {
-            composites.push((p, total_time));
-            println!("💔 M{}: COMPOSITE (took {:?})", p, total_time);
+            println!("🎉 {}: {} (took {:?})", label, verdict(candidate_results).0, total_time);
+            primes.push((label, total_time));
         }
     }
     
+    // Below Lucas-Lehmer, passing every check only means surviving them
+    let (found, heading) = if level >= CheckLevel::LucasLehmer {
+        ("Primes found", "🏆 Mersenne Primes Found:")
+    } else {
+        ("Survivors", "🏆 Survived every check:")
+    };
     let total_time = start_time.elapsed();
     println!("\n📈 Summary:");
     println!("   Total time: {:?}", total_time);
-    println!("   Primes found: {} ({:?})", primes.len(), primes.iter().map(|(p, _)| format!("M{}", p)).collect::<Vec<_>>().join(", "));
-    println!("   Composites: {} ({:?})", composites.len(), composites.iter().map(|(p, _)| format!("M{}", p)).collect::<Vec<_>>().join(", "));
+    println!("   {}: {} ({:?})", found, primes.len(), primes.iter().map(|(label, _)| label.as_str()).collect::<Vec<_>>().join(", "));
+    println!("   Composites: {} ({:?})", composites.len(), composites.iter().map(|(label, _)| label.as_str()).collect::<Vec<_>>().join(", "));
     
     if !primes.is_empty() {
-        println!("\n🏆 Mersenne Primes Found:");
-        for (p, time) in primes {
-            println!("   M{} (took {:?})", p, time);
+        println!("\n{}", heading);
+        for (label, time) in primes {
+            println!("   {} (took {:?})", label, time);
         }
     }
 }
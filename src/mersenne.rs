@@ -0,0 +1,585 @@
+/*!
+Arithmetic modulo a Mersenne number M_p = 2^p - 1.
+
+Everything here needs only `num-bigint` and an allocator, so it is available
+without the `std` feature; the functions are re-exported at the crate root.
+*/
+
+use alloc::vec::Vec;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+
+/// Optimized modulo operation for Mersenne numbers M_p = 2^p - 1
+///
+/// This function implements the bitwise trick for computing k mod (2^p - 1):
+/// repeatedly take the bits beyond position p, shift them down, and add them
+/// to the lower p bits until no bits remain above p.
+///
+/// This is much faster than general-purpose modulo for Mersenne numbers.
+///
+/// # Arguments
+///
+/// * `k` - The number to reduce modulo M_p
+/// * `p` - The Mersenne exponent (M_p = 2^p - 1)
+///
+/// # Returns
+///
+/// * k mod (2^p - 1)
+///
+/// # Algorithm
+///
+/// For a number k and M_p = 2^p - 1:
+/// 1. While k has bits beyond position p:
+///    a. Take the high bits (bits p and above)
+///    b. Shift them down by p positions
+///    c. Add them to the low bits (bits 0 to p-1)
+/// 2. The result is k mod M_p
+///
+/// This works because 2^p ≡ 1 (mod M_p), so shifting by p positions
+/// is equivalent to multiplying by 2^p ≡ 1.
+///
+/// # Convergence
+///
+/// The loop has no iteration cap because it cannot run long. A fold turns a
+/// b-bit value with b > p into at most max(b - p, p) + 1 bits, so large
+/// inputs lose p - 1 bits per fold. Any k < 2^(2p), which includes every
+/// product of two reduced residues, needs at most two folds: the first leaves
+/// at most (2^p - 1) + (2^p - 1) = 2^(p+1) - 2, and the second adds a high
+/// part of at most 1 to a low part of at most 2^p - 2, landing at or below
+/// M_p. Debug builds assert that bound.
+pub fn mod_mp(k: &BigUint, p: u64) -> BigUint {
+    // Only compute mp if needed (a p-bit value may still be M_p itself)
+    if k.bits() < p {
+        return k.clone();
+    }
+    mod_mp_with_modulus(k, &((BigUint::one() << p) - BigUint::one()), p)
+}
+
+/// `mod_mp` with M_p supplied by the caller
+///
+/// `mod_mp` builds M_p afresh on every call, an allocation and a p-bit
+/// subtraction; a loop reducing many values modulo the same M_p can build
+/// it once and call this instead. `mp` must be 2^p - 1.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::mod_mp_with_modulus;
+///
+/// let p = 89;
+/// let mp = (BigUint::from(1u32) << p) - 1u32;
+/// let mut x = BigUint::from(3u32);
+/// for _ in 0..10 {
+///     x = mod_mp_with_modulus(&(&x * &x), &mp, p);
+/// }
+/// assert_eq!(x, BigUint::from(3u32).modpow(&BigUint::from(1024u32), &mp));
+/// ```
+pub fn mod_mp_with_modulus(k: &BigUint, mp: &BigUint, p: u64) -> BigUint {
+    debug_assert!(mp.bits() == p && mp.count_ones() == p, "mp is not M_{}", p);
+    let (result, folds) = mod_mp_with_folds(k, mp, p);
+    debug_assert!(
+        k.bits() > 2 * p || folds <= 2,
+        "mod_mp took {} folds for a {}-bit value with p = {}",
+        folds,
+        k.bits(),
+        p
+    );
+    result
+}
+
+/// Reduce a value of at most 2p bits modulo M_p with a single fold
+///
+/// The general `mod_mp` loop clones `k`, then folds until the value fits,
+/// checking the size after every fold. When `k` has at most 2p bits, as any
+/// product of two reduced residues does, both halves are at most M_p, so one
+/// split into high and low halves and one conditional subtraction of M_p
+/// leave the residue. This skips the clone and the second fold. `mp` must be
+/// 2^p - 1; debug builds assert both preconditions.
+///
+/// The reduction itself is about a third faster than `mod_mp_with_modulus`
+/// at M1279, but the squaring costs several times more, so a whole
+/// Lucas-Lehmer step gains only a few percent. `ModMpScratch`, which the
+/// Lucas-Lehmer loop uses, already reduces in place and stays the faster
+/// choice there.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::{mod_mp, mod_mp_single_pass};
+///
+/// let p = 127;
+/// let mp = (BigUint::from(1u32) << p) - 1u32;
+/// let s = &mp - 5u32;
+/// let square = &s * &s;
+/// assert_eq!(mod_mp_single_pass(&square, &mp, p), mod_mp(&square, p));
+/// assert_eq!(mod_mp_single_pass(&square, &mp, p), BigUint::from(25u32));
+/// ```
+pub fn mod_mp_single_pass(k: &BigUint, mp: &BigUint, p: u64) -> BigUint {
+    debug_assert!(mp.bits() == p && mp.count_ones() == p, "mp is not M_{}", p);
+    debug_assert!(k.bits() <= 2 * p, "{}-bit value is too large to reduce modulo M_{} in one pass", k.bits(), p);
+    // high + low is at most 2 * M_p, reached only by k = 2^(2p) - 1
+    let mut result = k >> p;
+    result += k & mp;
+    if result >= *mp {
+        result -= mp;
+        if result == *mp {
+            result.set_zero();
+        }
+    }
+    result
+}
+
+/// `mod_mp_with_modulus`, also returning how many times the high bits were folded down
+pub(crate) fn mod_mp_with_folds(k: &BigUint, mp: &BigUint, p: u64) -> (BigUint, u32) {
+    // Handle edge cases first
+    if k.is_zero() {
+        return (BigUint::zero(), 0);
+    }
+    if k.bits() < p {
+        return (k.clone(), 0);
+    }
+
+    if k == mp {
+        return (BigUint::zero(), 0);
+    }
+    if k < mp {
+        return (k.clone(), 0);
+    }
+    
+    let mut result = k.clone();
+    let mut folds = 0;
+    
+    // Optimized reduction loop - unroll for better performance
+    loop {
+        // Check if result fits in p bits
+        if result.bits() <= p {
+            break;
+        }
+        
+        // Split result into high and low parts more efficiently
+        let high_bits = &result >> p;
+        let low_bits = &result & mp;
+        
+        // Add high bits to low bits
+        result = high_bits + low_bits;
+        folds += 1;
+        
+        // If result is small enough, we're done
+        if &result <= mp {
+            break;
+        }
+    }
+    
+    // Final check: if result equals mp, return 0
+    if &result == mp {
+        (BigUint::zero(), folds)
+    } else {
+        (result, folds)
+    }
+}
+
+/// Optimized square and subtract 2 modulo M_p for Lucas-Lehmer test
+///
+/// This function computes (s^2 - 2) mod M_p using the optimized modulo
+/// operation, which is much faster than general-purpose arithmetic.
+///
+/// # Arguments
+///
+/// * `s` - The current value in the Lucas-Lehmer sequence
+/// * `p` - The Mersenne exponent (M_p = 2^p - 1)
+///
+/// # Returns
+///
+/// * (s^2 - 2) mod M_p
+///
+/// Any `s` is accepted, including 0 and 1 where s^2 - 2 is negative; `p` must
+/// be at least 1.
+pub fn square_and_subtract_two_mod_mp(s: &BigUint, p: u64) -> BigUint {
+    let squared = s * s;
+    
+    // Direct optimization: subtract 2 before the modulo operation when possible
+    if squared >= BigUint::from(2u32) {
+        let minus_two = squared - BigUint::from(2u32);
+        mod_mp(&minus_two, p)
+    } else {
+        // Handle edge case where squared < 2 by adding a multiple of M_p
+        // first; 2 * M_p rather than M_p so that M_1 = 1 cannot underflow
+        let mp = (BigUint::one() << p) - BigUint::one();
+        let result = squared + (&mp << 1) - BigUint::from(2u32);
+        mod_mp_with_modulus(&result, &mp, p)
+    }
+}
+
+/// Multiply `a` and `b` modulo M_p, reducing with `mod_mp`
+///
+/// The inputs need not be reduced; the result is always below M_p.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::mul_mod_mp;
+///
+/// // 100 * 3 = 300 = 2 * 127 + 46
+/// assert_eq!(mul_mod_mp(&BigUint::from(100u32), &BigUint::from(3u32), 7), BigUint::from(46u32));
+/// ```
+pub fn mul_mod_mp(a: &BigUint, b: &BigUint, p: u64) -> BigUint {
+    mod_mp(&(a * b), p)
+}
+
+/// Add `a` and `b` modulo M_p, reducing with `mod_mp`
+///
+/// The inputs need not be reduced; the result is always below M_p.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::add_mod_mp;
+///
+/// // 100 + 27 = M_7 ≡ 0
+/// assert_eq!(add_mod_mp(&BigUint::from(100u32), &BigUint::from(27u32), 7), BigUint::from(0u32));
+/// ```
+pub fn add_mod_mp(a: &BigUint, b: &BigUint, p: u64) -> BigUint {
+    mod_mp(&(a + b), p)
+}
+
+/// Subtract `b` from `a` modulo M_p
+///
+/// Both inputs are reduced with `mod_mp` first, and M_p is added when `b`
+/// is the larger, so the result is always below M_p.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::sub_mod_mp;
+///
+/// // 3 - 5 ≡ -2 ≡ 125 (mod 127)
+/// assert_eq!(sub_mod_mp(&BigUint::from(3u32), &BigUint::from(5u32), 7), BigUint::from(125u32));
+/// ```
+pub fn sub_mod_mp(a: &BigUint, b: &BigUint, p: u64) -> BigUint {
+    let mp = (BigUint::one() << p) - BigUint::one();
+    let a = mod_mp_with_modulus(a, &mp, p);
+    let b = mod_mp_with_modulus(b, &mp, p);
+    if a >= b {
+        a - b
+    } else {
+        a + mp - b
+    }
+}
+
+/// Raise `base` to the power `exp` modulo M_p, reducing with `mod_mp`
+///
+/// Left-to-right square-and-multiply, so it agrees with `BigUint::modpow`
+/// but never divides by M_p. As with `modpow`, anything to the power 0 is 1
+/// (0 when p = 1, since M_1 = 1).
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::pow_mod_mp;
+///
+/// // 3^(M_7 - 1) ≡ 1 (mod M_7) because M_7 = 127 is prime
+/// assert_eq!(pow_mod_mp(&BigUint::from(3u32), &BigUint::from(126u32), 7), BigUint::from(1u32));
+/// ```
+pub fn pow_mod_mp(base: &BigUint, exp: &BigUint, p: u64) -> BigUint {
+    pow_mod_mp_stoppable(base, exp, p, &|| false).expect("never stopped")
+}
+
+/// How many bits of the exponent `pow_mod_mp_stoppable` handles between calls to `stop`
+const POW_MOD_MP_STOP_POLL_INTERVAL: usize = 16;
+
+/// `pow_mod_mp` that calls `stop` every few bits of the exponent and returns
+/// `None` as soon as it says yes
+pub(crate) fn pow_mod_mp_stoppable(base: &BigUint, exp: &BigUint, p: u64, stop: &dyn Fn() -> bool) -> Option<BigUint> {
+    let mp = (BigUint::one() << p) - BigUint::one();
+    let base = mod_mp_with_modulus(base, &mp, p);
+    let mut result = mod_mp_with_modulus(&BigUint::one(), &mp, p);
+    for (done, bit) in (0..exp.bits()).rev().enumerate() {
+        if done.is_multiple_of(POW_MOD_MP_STOP_POLL_INTERVAL) && stop() {
+            return None;
+        }
+        result = mod_mp_with_modulus(&(&result * &result), &mp, p);
+        if exp.bit(bit) {
+            result = mod_mp_with_modulus(&(&result * &base), &mp, p);
+        }
+    }
+    Some(result)
+}
+
+/// Reduce `k` modulo M_p in place
+///
+/// Gives the same result as `mod_mp`, but reuses `k`'s buffer instead of
+/// cloning it. Each call still builds M_p once; a loop reducing many values
+/// for the same p should hold a `ModMpScratch` and call its `reduce`.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::{mod_mp, mod_mp_assign};
+///
+/// let mut k = BigUint::from(1000u32);
+/// mod_mp_assign(&mut k, 7);
+/// assert_eq!(k, mod_mp(&BigUint::from(1000u32), 7));
+/// ```
+pub fn mod_mp_assign(k: &mut BigUint, p: u64) {
+    ModMpScratch::new(p).reduce(k);
+}
+
+/// Largest input, in 64-bit limbs, that `square_into` squares itself
+///
+/// 2048 bits, which covers the residues of every exponent up to 2048. Larger
+/// inputs go to num-bigint, whose Karatsuba and Toom-3 paths take over from
+/// schoolbook multiplication at about this size anyway.
+pub const SQUARE_INTO_MAX_LIMBS: usize = 32;
+
+/// Square `s` into `out`, reusing `out`'s buffer
+///
+/// `&s * &s` allocates a fresh product every call and multiplies every pair of
+/// limbs twice, once as a_i * a_j and once as a_j * a_i. For inputs of up to
+/// `SQUARE_INTO_MAX_LIMBS` limbs this computes each cross product once and
+/// doubles the sum, working in fixed-size stack buffers, and then copies the
+/// product into `out`; after the first call `out` has room for the product,
+/// so nothing is allocated. Larger inputs fall back to `s * s`.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::square_into;
+///
+/// let s = BigUint::from(3u32).pow(500);
+/// let mut out = BigUint::default();
+/// square_into(&s, &mut out);
+/// assert_eq!(out, &s * &s);
+/// ```
+pub fn square_into(s: &BigUint, out: &mut BigUint) {
+    let n = s.bits().div_ceil(64) as usize;
+    if n > SQUARE_INTO_MAX_LIMBS {
+        *out = s * s;
+        return;
+    }
+
+    let mut limbs = [0u64; SQUARE_INTO_MAX_LIMBS];
+    for (slot, digit) in limbs.iter_mut().zip(s.iter_u64_digits()) {
+        *slot = digit;
+    }
+    let a = &limbs[..n];
+
+    // Cross products a_i * a_j with i < j; row i ends at limb i + n, which no
+    // earlier row has reached
+    let mut product = [0u64; 2 * SQUARE_INTO_MAX_LIMBS];
+    for i in 0..n {
+        let mut carry = 0u128;
+        for j in i + 1..n {
+            let t = u128::from(a[i]) * u128::from(a[j]) + u128::from(product[i + j]) + carry;
+            product[i + j] = t as u64;
+            carry = t >> 64;
+        }
+        product[i + n] = carry as u64;
+    }
+
+    // Double them; the cross sum is below s^2 / 2, so no bit is lost
+    let product = &mut product[..2 * n];
+    let mut top = 0;
+    for limb in product.iter_mut() {
+        let next = *limb >> 63;
+        *limb = (*limb << 1) | top;
+        top = next;
+    }
+
+    // Add the squares a_i^2 on the diagonal
+    let mut carry = 0u128;
+    for (i, &limb) in a.iter().enumerate() {
+        let square = u128::from(limb) * u128::from(limb);
+        let low = u128::from(product[2 * i]) + (square as u64 as u128) + carry;
+        product[2 * i] = low as u64;
+        let high = u128::from(product[2 * i + 1]) + (square >> 64) + (low >> 64);
+        product[2 * i + 1] = high as u64;
+        carry = high >> 64;
+    }
+
+    // assign_from_slice takes 32-bit digits
+    let mut digits = [0u32; 4 * SQUARE_INTO_MAX_LIMBS];
+    for (pair, &limb) in digits.chunks_exact_mut(2).zip(product.iter()) {
+        pair[0] = limb as u32;
+        pair[1] = (limb >> 32) as u32;
+    }
+    out.assign_from_slice(&digits[..4 * n]);
+}
+
+/// Buffers for reducing modulo M_p repeatedly without allocating
+///
+/// `mod_mp` and `square_and_subtract_two_mod_mp` allocate fresh values for
+/// the high and low halves on every call. This keeps M_p and a buffer for the
+/// high half alive between calls, so a Lucas-Lehmer run allocates only the
+/// product of each squaring.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::{square_and_subtract_two_mod_mp, ModMpScratch};
+///
+/// let mut scratch = ModMpScratch::new(127);
+/// let mut s = BigUint::from(4u32);
+/// let mut expected = s.clone();
+/// for _ in 0..125 {
+///     scratch.square_and_subtract_two(&mut s);
+///     expected = square_and_subtract_two_mod_mp(&expected, 127);
+/// }
+/// assert_eq!(s, expected);
+/// assert_eq!(s, BigUint::from(0u32)); // M127 is prime
+/// ```
+#[derive(Debug, Clone)]
+pub struct ModMpScratch {
+    p: u64,
+    mp: BigUint,
+    high: BigUint,
+}
+
+impl ModMpScratch {
+    /// Scratch space for reductions modulo M_p; `p` must be at least 1
+    pub fn new(p: u64) -> Self {
+        Self { p, mp: (BigUint::one() << p) - BigUint::one(), high: BigUint::zero() }
+    }
+
+    /// Reduce `k` modulo M_p in place, as `mod_mp` does
+    pub fn reduce(&mut self, k: &mut BigUint) {
+        let p = self.p;
+        while k.bits() > p {
+            if k.bits() - p <= 64 {
+                // The high part fits in a u64, so fold it without a BigUint
+                let limb = (p / 64) as usize;
+                let shift = p % 64;
+                let mut digits = k.iter_u64_digits().skip(limb);
+                let low = digits.next().unwrap_or(0);
+                let next = digits.next().unwrap_or(0);
+                let high = if shift == 0 { low } else { (low >> shift) | (next << (64 - shift)) };
+                for bit in p..k.bits() {
+                    k.set_bit(bit, false);
+                }
+                *k += high;
+            } else {
+                self.high.clone_from(k);
+                self.high >>= p;
+                *k &= &self.mp;
+                *k += &self.high;
+            }
+        }
+        if k.bits() == p && *k == self.mp {
+            k.set_zero();
+        }
+    }
+
+    /// Replace `s` by (s^2 - 2) mod M_p, as `square_and_subtract_two_mod_mp` does
+    pub fn square_and_subtract_two(&mut self, s: &mut BigUint) {
+        let mut squared = &*s * &*s;
+        if squared < BigUint::from(2u32) {
+            // 2 * M_p rather than M_p so that M_1 = 1 cannot underflow
+            squared += &self.mp << 1;
+        }
+        squared -= 2u32;
+        self.reduce(&mut squared);
+        *s = squared;
+    }
+}
+
+/// Reduce a number modulo M_p directly on its 64-bit limbs
+///
+/// `limbs` holds the number little-endian, least significant limb first, the
+/// same layout as `BigUint::to_u64_digits`; trailing zero limbs are allowed.
+/// Each fold adds the bits from position p up into the low p bits in a single
+/// pass over the words, reading the high part just ahead of where the sum is
+/// written, so no second buffer is needed. On return `limbs` holds the
+/// residue, below M_p, with trailing zero limbs removed (empty for zero).
+///
+/// `p` must be at least 1.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::{mod_mp, mod_mp_limbs};
+///
+/// let k = BigUint::from(3u32).pow(200);
+/// let mut limbs = k.to_u64_digits();
+/// mod_mp_limbs(&mut limbs, 89);
+/// assert_eq!(limbs, mod_mp(&k, 89).to_u64_digits());
+/// ```
+pub fn mod_mp_limbs(limbs: &mut Vec<u64>, p: u64) {
+    let bits = |limbs: &[u64]| match limbs.last() {
+        Some(&top) => 64 * (limbs.len() as u64 - 1) + u64::from(64 - top.leading_zeros()),
+        None => 0,
+    };
+    let trim = |limbs: &mut Vec<u64>| {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+    };
+
+    let w = (p / 64) as usize;
+    let s = (p % 64) as u32;
+    let low_mask = if s == 0 { 0 } else { u64::MAX >> (64 - s) };
+
+    trim(limbs);
+    while bits(limbs) > p {
+        let len = limbs.len();
+        let count = (len - w).max(w + 1);
+        let mut carry = 0u128;
+        for i in 0..count {
+            // Bits p + 64i .. p + 64(i + 1), all at indices >= i, so not yet overwritten
+            let high = match limbs.get(w + i) {
+                Some(&word) if s == 0 => word,
+                Some(&word) => (word >> s) | (limbs.get(w + i + 1).map_or(0, |&next| next << (64 - s))),
+                None => 0,
+            };
+            let low = match i.cmp(&w) {
+                core::cmp::Ordering::Less => limbs[i],
+                core::cmp::Ordering::Equal => limbs[i] & low_mask,
+                core::cmp::Ordering::Greater => 0,
+            };
+            let sum = u128::from(low) + u128::from(high) + carry;
+            limbs[i] = sum as u64;
+            carry = sum >> 64;
+        }
+        limbs.truncate(count);
+        if carry > 0 {
+            limbs.push(carry as u64);
+        }
+        trim(limbs);
+    }
+
+    // A result of exactly M_p is zero
+    let is_mp = bits(limbs) == p
+        && limbs[..w].iter().all(|&word| word == u64::MAX)
+        && (s == 0 || limbs[w] == low_mask);
+    if is_mp {
+        limbs.clear();
+    }
+}
+
+/// Compare two residues modulo M_p, ignoring how each one is represented
+///
+/// An implementation may leave a residue as M_p where another reduces it to 0,
+/// or hand back a value that has not been fully reduced. Both sides are
+/// normalized with `mod_mp` before comparing, so such residues compare equal.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::residues_equal_mod_mp;
+///
+/// let m7 = BigUint::from(127u32);
+/// assert!(residues_equal_mod_mp(&m7, &BigUint::from(0u32), 7));
+/// assert!(residues_equal_mod_mp(&BigUint::from(130u32), &BigUint::from(3u32), 7));
+/// ```
+pub fn residues_equal_mod_mp(a: &BigUint, b: &BigUint, p: u64) -> bool {
+    mod_mp(a, p) == mod_mp(b, p)
+}
@@ -0,0 +1,873 @@
+/*!
+The checking pipeline: the stages a candidate goes through, from the
+pre-screen to the Lucas-Lehmer test, the reporter that follows it, and the
+`PrimalityTest` trait for running stages one at a time.
+*/
+
+use crate::{
+    check_small_factors, cross_check_residue, default_p_minus_1_bound, find_small_factor, format_res64, is_prime,
+    is_proper_mersenne_factor, lucas_lehmer_iterations, lucas_lehmer_outcome, lucas_lehmer_until, miller_rabin_rounds,
+    pollard_p_minus_1_with_stage2, prescreen, prescreen_with, trial_factor_to_bits, validate_exponent, CheckConfig,
+    CheckOutcome, CheckedLucasLehmer, Exponent, Instant, LucasLehmerStop, MillerRabinResult, PrescreenResult,
+    PrimalityBackend, PrimalityError, SchoolbookBackend, DEFAULT_MILLER_RABIN_ROUNDS, DEFAULT_TRIAL_FACTOR_LIMIT,
+    FULL_MILLER_RABIN_ROUNDS, FULL_P_MINUS_1_B2_MULTIPLIER, FULL_TRIAL_FACTOR_BITS,
+};
+use num_bigint::BigUint;
+use num_traits::One;
+use std::borrow::Cow;
+use std::fmt;
+use std::time::Duration;
+
+/// Type of primality check performed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CheckKind {
+    /// Pre-screen: Check if the exponent p itself is prime
+    ExponentPrime,
+    /// Trial factoring: Check for small factors using special properties
+    TrialFactor,
+    /// Probabilistic: Miller-Rabin test
+    MillerRabin,
+    /// Lucas-Lehmer: The definitive test for Mersenne primes
+    LucasLehmer,
+    /// P-1: Stage-1 Pollard P-1 factoring (runs after trial factoring, but is
+    /// listed last so the other kinds keep their `stage_bitmask` bits)
+    PMinusOne,
+}
+
+/// Represents the result of a primality check
+///
+/// With the `serde` feature this implements `Serialize` and `Deserialize`;
+/// `time_taken` uses serde's `Duration` form (`secs` and `nanos`) and a
+/// factor is written as a list of 32-bit digits, least significant first.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CheckResult {
+    /// Whether the check passed
+    pub passed: bool,
+    /// Description of the check result
+    pub message: String,
+    /// How long the check took
+    pub time_taken: Duration,
+    /// Type of check that was performed
+    pub kind: CheckKind,
+    /// A factor of M_p found by this check, if any
+    pub factor: Option<BigUint>,
+    /// The check could not reach a verdict (for example it timed out).
+    /// Such a result has `passed == false` but is no evidence of compositeness.
+    pub inconclusive: bool,
+}
+
+/// Different levels of thoroughness for primality checking
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CheckLevel {
+    /// Pre-screen: Check if the exponent p itself is prime
+    PreScreen,
+    /// Trial factoring: Check for small factors using special properties
+    TrialFactoring,
+    /// P-1: Look for factors q where q - 1 is smooth
+    PMinusOne,
+    /// Probabilistic: Miller-Rabin test (replaces Fermat test)
+    Probabilistic,
+    /// Lucas-Lehmer: The definitive test for Mersenne primes
+    LucasLehmer,
+    /// Full: every stage at greater depth, for candidates worth a serious run
+    ///
+    /// Trial factoring continues to 2^`FULL_TRIAL_FACTOR_BITS`, P-1 adds a
+    /// second stage, Miller-Rabin runs at least `FULL_MILLER_RABIN_ROUNDS`
+    /// rounds, and the Lucas-Lehmer result reports its res64 and any errors
+    /// the Jacobi check corrected, so the run can be compared with GIMPS.
+    Full,
+}
+
+impl From<CheckKind> for CheckLevel {
+    fn from(kind: CheckKind) -> Self {
+        match kind {
+            CheckKind::ExponentPrime => CheckLevel::PreScreen,
+            CheckKind::TrialFactor => CheckLevel::TrialFactoring,
+            CheckKind::MillerRabin => CheckLevel::Probabilistic,
+            CheckKind::LucasLehmer => CheckLevel::LucasLehmer,
+            CheckKind::PMinusOne => CheckLevel::PMinusOne,
+        }
+    }
+}
+
+impl CheckLevel {
+    /// Get a human-readable description of the check level
+    pub fn description(&self) -> String {
+        match self {
+            CheckLevel::PreScreen => "Pre-screen: Check if exponent is prime (instant)".to_string(),
+            CheckLevel::TrialFactoring => {
+                "Trial factoring: Check for small factors (~1 second)".to_string()
+            }
+            CheckLevel::PMinusOne => {
+                "P-1 factoring: Check for factors with smooth q - 1 (seconds)".to_string()
+            }
+            CheckLevel::Probabilistic => {
+                "Probabilistic: Miller-Rabin test (seconds to minutes)".to_string()
+            }
+            CheckLevel::LucasLehmer => {
+                "Lucas-Lehmer: Definitive test (minutes to hours)".to_string()
+            }
+            CheckLevel::Full => {
+                "Full: Deeper factoring, then Lucas-Lehmer with res64 (minutes to hours)".to_string()
+            }
+        }
+    }
+}
+
+/// Receives progress updates while a candidate is being checked
+///
+/// Every method has a default implementation, so a reporter only needs to
+/// override the events it cares about. Reporters must be `Sync` because stages
+/// may run work on several threads.
+pub trait ProgressReporter: Sync {
+    /// Called periodically during a long-running stage
+    ///
+    /// `current` counts the units of work done so far (iterations for the
+    /// Lucas-Lehmer test) out of `total`.
+    fn on_progress(&self, _kind: CheckKind, _current: u64, _total: u64) {}
+
+    /// Called as soon as a stage has produced its result
+    fn on_stage_complete(&self, _result: &CheckResult) {}
+
+    /// Whether the check should be abandoned
+    ///
+    /// Polled between stages and during the Lucas-Lehmer test. Once it returns
+    /// true no further stage starts, and a running Lucas-Lehmer test stops
+    /// with an inconclusive result.
+    fn should_stop(&self) -> bool {
+        false
+    }
+}
+
+/// A `ProgressReporter` that ignores every update
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoProgress;
+
+impl ProgressReporter for NoProgress {}
+
+/// Check a Mersenne number candidate with the specified level of thoroughness
+///
+/// This is the main entry point for testing Mersenne number candidates. It performs
+/// a strict pipeline of tests, failing fast if any test fails.
+///
+/// # Arguments
+///
+/// * `p` - The Mersenne exponent to test (testing 2^p - 1), as a `u64` or `Exponent`
+/// * `level` - How thorough the testing should be
+///
+/// # Returns
+///
+/// A vector of `CheckResult`s, one for each test performed. The candidate is
+/// considered promising if all tests pass. A Miller-Rabin stage that times out
+/// is marked `inconclusive` rather than failed outright; at
+/// `CheckLevel::LucasLehmer` the pipeline then goes on to the definitive test.
+/// `MersenneCheck` yields the same results one stage at a time.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::{CheckLevel, check_mersenne_candidate};
+///
+/// let results = check_mersenne_candidate(31, CheckLevel::LucasLehmer);
+/// assert!(results.iter().all(|r| r.passed)); // M31 is prime
+///
+/// let results = check_mersenne_candidate(32, CheckLevel::TrialFactoring);
+/// assert!(!results.iter().all(|r| r.passed)); // M32 is composite
+/// ```
+pub fn check_mersenne_candidate(p: impl Into<Exponent>, level: CheckLevel) -> Vec<CheckResult> {
+    check_mersenne_candidate_with_reporter(p, level, &NoProgress)
+}
+
+/// Check a Mersenne number candidate, reporting progress as the pipeline runs
+///
+/// Behaves exactly like `check_mersenne_candidate`, but hands each stage's
+/// result to `reporter` as soon as that stage finishes and forwards periodic
+/// progress from the Lucas-Lehmer stage. This lets interactive front-ends show
+/// output while a long test is still running.
+///
+/// # Arguments
+///
+/// * `p` - The Mersenne exponent to test (testing 2^p - 1), as a `u64` or `Exponent`
+/// * `level` - How thorough the testing should be
+/// * `reporter` - Receives stage results and progress updates
+///
+/// # Returns
+///
+/// The same vector of `CheckResult`s that `check_mersenne_candidate` returns.
+pub fn check_mersenne_candidate_with_reporter(
+    p: impl Into<Exponent>,
+    level: CheckLevel,
+    reporter: &dyn ProgressReporter,
+) -> Vec<CheckResult> {
+    run_pipeline(p.into().get(), level, &CheckConfig::default(), reporter)
+}
+
+/// Check a Mersenne number candidate using the given configuration
+///
+/// Runs the same pipeline as `check_mersenne_candidate`, with behavior tuned by
+/// `config`. Sharing one `CheckConfig` (and so one `PrimeCache`) across a
+/// batch lets later candidates reuse primality results from earlier ones.
+///
+/// # Arguments
+///
+/// * `p` - The Mersenne exponent to test (testing 2^p - 1), as a `u64` or `Exponent`
+/// * `level` - How thorough the testing should be
+/// * `config` - Options controlling the pipeline
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use primality_jones::{check_mersenne_candidate_with_config, CheckConfig, CheckLevel, PrimeCache};
+///
+/// let config = CheckConfig {
+///     prime_cache: Some(Arc::new(PrimeCache::new())),
+///     ..CheckConfig::default()
+/// };
+/// for p in [31, 61, 89] {
+///     let results = check_mersenne_candidate_with_config(p, CheckLevel::TrialFactoring, &config);
+///     assert!(results.iter().all(|r| r.passed));
+/// }
+/// ```
+pub fn check_mersenne_candidate_with_config(
+    p: impl Into<Exponent>,
+    level: CheckLevel,
+    config: &CheckConfig,
+) -> Vec<CheckResult> {
+    run_pipeline(p.into().get(), level, config, &NoProgress)
+}
+
+/// Check a Mersenne number candidate with both custom options and progress reporting
+///
+/// Combines `check_mersenne_candidate_with_config` and
+/// `check_mersenne_candidate_with_reporter`.
+pub fn check_mersenne_candidate_with_config_and_reporter(
+    p: impl Into<Exponent>,
+    level: CheckLevel,
+    config: &CheckConfig,
+    reporter: &dyn ProgressReporter,
+) -> Vec<CheckResult> {
+    run_pipeline(p.into().get(), level, config, reporter)
+}
+
+/// The staged pipeline behind every `check_mersenne_candidate*` entry point
+fn run_pipeline(
+    p: u64,
+    level: CheckLevel,
+    config: &CheckConfig,
+    reporter: &dyn ProgressReporter,
+) -> Vec<CheckResult> {
+    MersenneCheck {
+        reporter,
+        ..MersenneCheck::with_config(p, level, config)
+    }
+    .collect()
+}
+
+/// The stage a `MersenneCheck` will run next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PipelineStage {
+    PreScreen,
+    /// The exponent has the divisor `d`, so M_d divides M_p
+    AlgebraicFactor(u64),
+    KnownFactor,
+    TrialFactoring,
+    PMinusOne,
+    Probabilistic,
+    LucasLehmer,
+}
+
+/// The stages of `check_mersenne_candidate`, run one at a time
+///
+/// Each call to `next` runs the next stage and yields its result, so callers
+/// can stream results or stop early by simply dropping the iterator; stages
+/// that are never reached are never run. The iterator ends where the pipeline
+/// does: at `level`, or after the stage that shows M_p composite. Collecting
+/// it gives exactly the vector `check_mersenne_candidate` returns.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::{check_mersenne_candidate, CheckLevel, MersenneCheck};
+///
+/// // Stop as soon as any stage fails
+/// for result in MersenneCheck::new(127, CheckLevel::LucasLehmer) {
+///     println!("{:?}: {}", result.kind, result.message);
+///     if !result.passed {
+///         break;
+///     }
+/// }
+///
+/// let streamed: Vec<_> = MersenneCheck::new(67, CheckLevel::LucasLehmer).map(|r| r.passed).collect();
+/// let batch: Vec<_> = check_mersenne_candidate(67, CheckLevel::LucasLehmer).iter().map(|r| r.passed).collect();
+/// assert_eq!(streamed, batch);
+/// ```
+pub struct MersenneCheck<'a> {
+    p: u64,
+    level: CheckLevel,
+    config: Cow<'a, CheckConfig>,
+    reporter: &'a dyn ProgressReporter,
+    next_stage: Option<PipelineStage>,
+}
+
+impl MersenneCheck<'static> {
+    /// Check M_p up to `level` with the default configuration
+    pub fn new(p: impl Into<Exponent>, level: CheckLevel) -> Self {
+        MersenneCheck::start(p.into().get(), level, Cow::Owned(CheckConfig::default()))
+    }
+}
+
+impl<'a> MersenneCheck<'a> {
+    /// Check M_p up to `level` using the given configuration
+    pub fn with_config(p: impl Into<Exponent>, level: CheckLevel, config: &'a CheckConfig) -> Self {
+        MersenneCheck::start(p.into().get(), level, Cow::Borrowed(config))
+    }
+
+    fn start(p: u64, level: CheckLevel, config: Cow<'a, CheckConfig>) -> Self {
+        MersenneCheck {
+            p,
+            level,
+            config,
+            reporter: &NoProgress,
+            next_stage: Some(PipelineStage::PreScreen),
+        }
+    }
+
+    /// The exponent being checked
+    pub fn exponent(&self) -> u64 {
+        self.p
+    }
+
+    /// Run one stage, choosing the stage after it
+    ///
+    /// Returns `None` for a stage that has nothing to report, which only
+    /// happens when there is no known factor to look up.
+    fn run_stage(&mut self, stage: PipelineStage) -> Option<CheckResult> {
+        let p = self.p;
+        let level = self.level;
+        let config = &*self.config;
+        let prime_test = |n: u64| match &config.prime_cache {
+            Some(cache) => cache.is_prime(n),
+            None => is_prime(n),
+        };
+        let check_start = Instant::now();
+        self.next_stage = None;
+
+        match stage {
+            // PreScreen: Check if the exponent p itself is prime
+            PipelineStage::PreScreen => {
+                if let Err(e) = validate_exponent(p) {
+                    return Some(CheckResult {
+                        passed: false,
+                        message: e.to_string(),
+                        time_taken: check_start.elapsed(),
+                        kind: CheckKind::ExponentPrime,
+                        factor: None,
+                        inconclusive: true,
+                    });
+                }
+                let prescreen_result = prescreen_with(p, &prime_test);
+                let prime_passed = prescreen_result == PrescreenResult::ExponentPrime;
+                self.next_stage = match prescreen_result {
+                    PrescreenResult::ExponentComposite { factor: d } if level >= CheckLevel::TrialFactoring => {
+                        Some(PipelineStage::AlgebraicFactor(d))
+                    }
+                    _ if prime_passed && level > CheckLevel::PreScreen => Some(PipelineStage::KnownFactor),
+                    _ => None,
+                };
+                Some(CheckResult {
+                    passed: prime_passed,
+                    message: prescreen_result.to_string(),
+                    time_taken: check_start.elapsed(),
+                    kind: CheckKind::ExponentPrime,
+                    factor: None,
+                    inconclusive: false,
+                })
+            }
+
+            // M_d divides M_p; a small factor of M_d is a more useful answer than M_d itself
+            PipelineStage::AlgebraicFactor(d) => {
+                let m_d = (BigUint::one() << d) - BigUint::one();
+                let limit = if d < 64 {
+                    ((1u64 << d) - 1).isqrt().min(config.trial_factor_limit)
+                } else {
+                    config.trial_factor_limit
+                };
+                let factor = find_small_factor(d, limit, &prime_test).map_or(m_d, BigUint::from);
+                Some(CheckResult {
+                    passed: false,
+                    message: format!("Algebraic factor: M{d} divides M{p} since {d} divides {p} (factor {factor})"),
+                    time_taken: check_start.elapsed(),
+                    kind: CheckKind::TrialFactor,
+                    factor: Some(factor),
+                    inconclusive: false,
+                })
+            }
+
+            // A factor known from outside makes any search unnecessary
+            PipelineStage::KnownFactor => {
+                let known_factor = config
+                    .known_factors
+                    .get(&p)
+                    .and_then(|factors| factors.iter().find(|q| is_proper_mersenne_factor(p, q)));
+                let Some(q) = known_factor else {
+                    self.next_stage = Some(PipelineStage::TrialFactoring);
+                    return None;
+                };
+                Some(CheckResult {
+                    passed: false,
+                    message: format!("Known factor: {q}"),
+                    time_taken: check_start.elapsed(),
+                    kind: CheckKind::TrialFactor,
+                    factor: Some(q.clone()),
+                    inconclusive: false,
+                })
+            }
+
+            // TrialFactoring: Check for small factors
+            PipelineStage::TrialFactoring => {
+                // Full carries on with the sieved search from the bit level
+                // the usual limit reached; below 2^64 no composite M_p needs
+                // more than half its bits
+                let deep_bits = (level == CheckLevel::Full)
+                    .then(|| p.div_ceil(2).min(u64::from(FULL_TRIAL_FACTOR_BITS)) as u32);
+                let factor = find_small_factor(p, config.trial_factor_limit, &prime_test).or_else(|| {
+                    let from_bits = config.trial_factor_limit.max(1).ilog2();
+                    deep_bits.and_then(|to_bits| trial_factor_to_bits(p, from_bits, to_bits))
+                });
+                if let Some(factor) = factor {
+                    return Some(CheckResult {
+                        passed: false,
+                        message: format!("Found small factor: {factor}"),
+                        time_taken: check_start.elapsed(),
+                        kind: CheckKind::TrialFactor,
+                        factor: Some(BigUint::from(factor)),
+                        inconclusive: false,
+                    });
+                }
+                if level > CheckLevel::TrialFactoring {
+                    self.next_stage = Some(PipelineStage::PMinusOne);
+                }
+                let searched = match deep_bits {
+                    Some(bits) if 1u128 << bits > u128::from(config.trial_factor_limit) => format!("2^{bits}"),
+                    _ => config.trial_factor_limit.to_string(),
+                };
+                Some(CheckResult {
+                    passed: true,
+                    message: format!("No small factors found up to {searched}"),
+                    time_taken: check_start.elapsed(),
+                    kind: CheckKind::TrialFactor,
+                    factor: None,
+                    inconclusive: false,
+                })
+            }
+
+            // PMinusOne: Stage-1 P-1 factoring
+            PipelineStage::PMinusOne => {
+                // Every stage from here on works modulo M_p
+                if let Err(e) = config.check_memory(p, level) {
+                    return Some(CheckResult {
+                        passed: false,
+                        message: e.to_string(),
+                        time_taken: check_start.elapsed(),
+                        kind: CheckKind::PMinusOne,
+                        factor: None,
+                        inconclusive: true,
+                    });
+                }
+                let b1 = default_p_minus_1_bound(p);
+                let b2 = if level == CheckLevel::Full { b1 * FULL_P_MINUS_1_B2_MULTIPLIER } else { b1 };
+                let bounds = if b2 > b1 { format!("B1 = {b1}, B2 = {b2}") } else { format!("B1 = {b1}") };
+                if let Some(factor) = pollard_p_minus_1_with_stage2(p, b1, b2) {
+                    return Some(CheckResult {
+                        passed: false,
+                        message: format!("P-1 found factor: {factor} ({bounds})"),
+                        time_taken: check_start.elapsed(),
+                        kind: CheckKind::PMinusOne,
+                        factor: Some(factor),
+                        inconclusive: false,
+                    });
+                }
+                if level > CheckLevel::PMinusOne {
+                    self.next_stage = Some(PipelineStage::Probabilistic);
+                }
+                Some(CheckResult {
+                    passed: true,
+                    message: format!("No factor found by P-1 ({bounds})"),
+                    time_taken: check_start.elapsed(),
+                    kind: CheckKind::PMinusOne,
+                    factor: None,
+                    inconclusive: false,
+                })
+            }
+
+            // Probabilistic: Miller-Rabin test
+            // Skip for very large numbers (>100M digits means p > ~332M)
+            PipelineStage::Probabilistic if p > 332_000_000 => {
+                self.next_stage = Some(PipelineStage::LucasLehmer);
+                Some(CheckResult {
+                    passed: true,
+                    message: "Skipped Miller-Rabin test (number too large)".to_string(),
+                    time_taken: Duration::from_secs(0),
+                    kind: CheckKind::MillerRabin,
+                    factor: None,
+                    inconclusive: false,
+                })
+            }
+            PipelineStage::Probabilistic => {
+                // Progress goes through the reporter, so no bar of our own
+                let rounds = if level == CheckLevel::Full {
+                    config.miller_rabin_rounds.max(FULL_MILLER_RABIN_ROUNDS)
+                } else {
+                    config.miller_rabin_rounds
+                };
+                let miller_rabin = miller_rabin_rounds(
+                    p,
+                    rounds,
+                    check_start,
+                    config.probabilistic_timeout,
+                    false,
+                );
+                if miller_rabin != MillerRabinResult::Composite && level > CheckLevel::Probabilistic {
+                    self.next_stage = Some(PipelineStage::LucasLehmer);
+                }
+                Some(CheckResult {
+                    passed: miller_rabin == MillerRabinResult::ProbablyPrime,
+                    message: match miller_rabin {
+                        MillerRabinResult::ProbablyPrime => "Passed Miller-Rabin test".to_string(),
+                        MillerRabinResult::Composite => "Failed Miller-Rabin test".to_string(),
+                        MillerRabinResult::Inconclusive => {
+                            "Miller-Rabin test timed out (inconclusive)".to_string()
+                        }
+                    },
+                    time_taken: check_start.elapsed(),
+                    kind: CheckKind::MillerRabin,
+                    factor: None,
+                    inconclusive: miller_rabin == MillerRabinResult::Inconclusive,
+                })
+            }
+
+            // LucasLehmer: The definitive test
+            PipelineStage::LucasLehmer => {
+                let deadline = config.lucas_lehmer_timeout.and_then(|timeout| check_start.checked_add(timeout));
+                // Full records the residue and any corrected errors, as GIMPS does
+                let details = |checked: &CheckedLucasLehmer| match level {
+                    CheckLevel::Full => format!(
+                        ", res64 {}, {} error(s) corrected",
+                        format_res64(checked.res64),
+                        checked.errors_corrected
+                    ),
+                    _ => String::new(),
+                };
+                // A second backend, if configured, has to reproduce the residue
+                let cross_check = |s: &BigUint| match &config.cross_check_backend {
+                    Some(backend) => cross_check_residue(p, SchoolbookBackend.name(), s, backend.as_ref())
+                        .map(|()| format!(", confirmed by the {} backend", backend.name())),
+                    None => Ok(String::new()),
+                };
+                let (ll_passed, message, inconclusive) = match lucas_lehmer_until(p, self.reporter, deadline) {
+                    Ok((checked, s)) => match cross_check(&s) {
+                        Ok(confirmed) => (
+                            checked.is_prime,
+                            format!(
+                                "{} Lucas-Lehmer test (definitive){}{confirmed}",
+                                if checked.is_prime { "Passed" } else { "Failed" },
+                                details(&checked)
+                            ),
+                            false,
+                        ),
+                        Err(e) => (false, format!("{e} (inconclusive)"), true),
+                    },
+                    Err(LucasLehmerStop::Stopped(completed)) => (
+                        false,
+                        format!(
+                            "Lucas-Lehmer test {} after {}/{} iterations (inconclusive)",
+                            if self.reporter.should_stop() { "stopped" } else { "timed out" },
+                            completed,
+                            lucas_lehmer_iterations(p)
+                        ),
+                        true,
+                    ),
+                    Err(LucasLehmerStop::Inconsistent(e)) => (false, format!("{e} (inconclusive)"), true),
+                    Err(LucasLehmerStop::Checkpoint(_)) => unreachable!("nothing is saved"),
+                };
+                Some(CheckResult {
+                    passed: ll_passed,
+                    message,
+                    time_taken: check_start.elapsed(),
+                    kind: CheckKind::LucasLehmer,
+                    factor: None,
+                    inconclusive,
+                })
+            }
+        }
+    }
+}
+
+impl Iterator for MersenneCheck<'_> {
+    type Item = CheckResult;
+
+    fn next(&mut self) -> Option<CheckResult> {
+        while let Some(stage) = self.next_stage {
+            if self.reporter.should_stop() {
+                self.next_stage = None;
+                return None;
+            }
+            if let Some(result) = self.run_stage(stage) {
+                self.reporter.on_stage_complete(&result);
+                return Some(result);
+            }
+        }
+        None
+    }
+}
+
+impl std::iter::FusedIterator for MersenneCheck<'_> {}
+
+impl fmt::Debug for MersenneCheck<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MersenneCheck")
+            .field("p", &self.p)
+            .field("level", &self.level)
+            .field("config", &self.config)
+            .field("next_stage", &self.next_stage)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A single test on M_p that can be combined with others in a pipeline
+///
+/// `PreScreen`, `TrialFactoring`, `PMinusOne`, `MillerRabin` and
+/// `LucasLehmer` wrap the free functions of the same names; implement it for
+/// your own types to run them alongside these with `run_primality_tests`.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::{
+///     run_primality_tests, CheckOutcome, LucasLehmer, PrimalityError, PrimalityTest, TrialFactoring,
+/// };
+///
+/// /// Rejects exponents with a known factor from a lookup table
+/// struct KnownComposites;
+///
+/// impl PrimalityTest for KnownComposites {
+///     fn name(&self) -> &str {
+///         "known composites"
+///     }
+///
+///     fn test(&self, p: u64) -> Result<CheckOutcome, PrimalityError> {
+///         Ok(if p == 67 { CheckOutcome::Composite } else { CheckOutcome::ProbablyPrime })
+///     }
+/// }
+///
+/// let tests: Vec<Box<dyn PrimalityTest>> =
+///     vec![Box::new(KnownComposites), Box::new(TrialFactoring { limit: 1000, to_bits: None }), Box::new(LucasLehmer)];
+/// let reports = run_primality_tests(67, &tests);
+/// assert_eq!(reports.len(), 1);
+/// assert_eq!(reports[0].name, "known composites");
+/// ```
+pub trait PrimalityTest {
+    /// Short name of the test, for reports
+    fn name(&self) -> &str;
+
+    /// Run the test on M_p
+    ///
+    /// Tests that can only rule M_p out answer `ProbablyPrime` when they
+    /// find nothing against it, and `Composite` when they do.
+    fn test(&self, p: u64) -> Result<CheckOutcome, PrimalityError>;
+}
+
+/// The pre-screen as a `PrimalityTest`: M_p is composite when p is
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PreScreen;
+
+impl PrimalityTest for PreScreen {
+    fn name(&self) -> &str {
+        "pre-screen"
+    }
+
+    fn test(&self, p: u64) -> Result<CheckOutcome, PrimalityError> {
+        validate_exponent(p)?;
+        Ok(match prescreen(p) {
+            PrescreenResult::ExponentPrime => CheckOutcome::ProbablyPrime,
+            _ => CheckOutcome::Composite,
+        })
+    }
+}
+
+/// Trial factoring up to `limit` as a `PrimalityTest`, by `check_small_factors`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrialFactoring {
+    /// Largest candidate factor to try
+    pub limit: u64,
+    /// Carry on with `trial_factor_to_bits` up to 2^`to_bits` (at most half
+    /// the bits of M_p), as `CheckLevel::Full` does
+    pub to_bits: Option<u32>,
+}
+
+impl Default for TrialFactoring {
+    fn default() -> Self {
+        TrialFactoring { limit: DEFAULT_TRIAL_FACTOR_LIMIT, to_bits: None }
+    }
+}
+
+impl PrimalityTest for TrialFactoring {
+    fn name(&self) -> &str {
+        "trial factoring"
+    }
+
+    fn test(&self, p: u64) -> Result<CheckOutcome, PrimalityError> {
+        validate_exponent(p)?;
+        let factor = check_small_factors(p, self.limit).or_else(|| {
+            let to_bits = u64::from(self.to_bits?).min(p.div_ceil(2)) as u32;
+            trial_factor_to_bits(p, self.limit.max(1).ilog2(), to_bits)
+        });
+        Ok(match factor {
+            Some(_) => CheckOutcome::Composite,
+            None => CheckOutcome::ProbablyPrime,
+        })
+    }
+}
+
+/// P-1 factoring as a `PrimalityTest`, by `pollard_p_minus_1_with_stage2`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PMinusOne {
+    /// Smoothness bound, or `None` for the bound `check_mersenne_candidate`
+    /// would use for p
+    pub b1: Option<u64>,
+    /// Stage-2 bound as a multiple of B1; 0 or 1 runs stage 1 only
+    pub b2_multiplier: u64,
+}
+
+impl PrimalityTest for PMinusOne {
+    fn name(&self) -> &str {
+        "P-1"
+    }
+
+    fn test(&self, p: u64) -> Result<CheckOutcome, PrimalityError> {
+        validate_exponent(p)?;
+        let b1 = self.b1.unwrap_or_else(|| default_p_minus_1_bound(p));
+        Ok(match pollard_p_minus_1_with_stage2(p, b1, b1.saturating_mul(self.b2_multiplier)) {
+            Some(_) => CheckOutcome::Composite,
+            None => CheckOutcome::ProbablyPrime,
+        })
+    }
+}
+
+/// `rounds` rounds of Miller-Rabin as a `PrimalityTest`, without a progress bar
+/// or a time limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MillerRabin {
+    /// Number of random bases to try
+    pub rounds: u32,
+}
+
+impl Default for MillerRabin {
+    fn default() -> Self {
+        MillerRabin { rounds: DEFAULT_MILLER_RABIN_ROUNDS }
+    }
+}
+
+impl PrimalityTest for MillerRabin {
+    fn name(&self) -> &str {
+        "Miller-Rabin"
+    }
+
+    fn test(&self, p: u64) -> Result<CheckOutcome, PrimalityError> {
+        validate_exponent(p)?;
+        Ok(miller_rabin_rounds(p, self.rounds, Instant::now(), Duration::MAX, false).into())
+    }
+}
+
+/// The Lucas-Lehmer test as a `PrimalityTest`, by `lucas_lehmer_outcome`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LucasLehmer;
+
+impl PrimalityTest for LucasLehmer {
+    fn name(&self) -> &str {
+        "Lucas-Lehmer"
+    }
+
+    fn test(&self, p: u64) -> Result<CheckOutcome, PrimalityError> {
+        lucas_lehmer_outcome(p)
+    }
+}
+
+/// What one `PrimalityTest` said about M_p
+#[derive(Debug)]
+pub struct PrimalityTestReport {
+    /// The test's `name`
+    pub name: String,
+    /// The test's verdict
+    pub outcome: Result<CheckOutcome, PrimalityError>,
+    /// How long the test took
+    pub time_taken: Duration,
+}
+
+/// Run `tests` on M_p in order, stopping once one of them rules it out
+///
+/// Returns a report for every test that ran. The run also stops when a test
+/// rejects the exponent with `InvalidExponent`; other errors, such as a
+/// timeout, are reported and the next test runs, as in
+/// `check_mersenne_candidate`.
+pub fn run_primality_tests(p: impl Into<Exponent>, tests: &[Box<dyn PrimalityTest>]) -> Vec<PrimalityTestReport> {
+    let p = p.into().get();
+    let mut reports = Vec::new();
+    for test in tests {
+        let start = Instant::now();
+        let outcome = test.test(p);
+        let stop = match &outcome {
+            Ok(outcome) => outcome.is_composite(),
+            Err(e) => matches!(e, PrimalityError::InvalidExponent(_)),
+        };
+        reports.push(PrimalityTestReport { name: test.name().to_string(), outcome, time_taken: start.elapsed() });
+        if stop {
+            break;
+        }
+    }
+    reports
+}
+
+/// The tests `check_mersenne_candidate` runs at `level`, with the settings it
+/// uses there: the defaults, or at `CheckLevel::Full` trial factoring to
+/// 2^`FULL_TRIAL_FACTOR_BITS`, a P-1 second stage and
+/// `FULL_MILLER_RABIN_ROUNDS` rounds of Miller-Rabin
+///
+/// Running these with `run_primality_tests` reaches the same verdicts, but the
+/// pipeline does more along the way: it names the algebraic factor of a
+/// composite exponent, consults known factors, limits Miller-Rabin's time and
+/// reports progress.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::{run_primality_tests, standard_tests, CheckLevel, CheckOutcome};
+///
+/// let reports = run_primality_tests(127, &standard_tests(CheckLevel::LucasLehmer));
+/// assert_eq!(reports.len(), 5);
+/// assert_eq!(reports.last().unwrap().outcome.as_ref().unwrap(), &CheckOutcome::DefinitelyPrime);
+/// ```
+pub fn standard_tests(level: CheckLevel) -> Vec<Box<dyn PrimalityTest>> {
+    let full = level == CheckLevel::Full;
+    let mut tests: Vec<Box<dyn PrimalityTest>> = vec![Box::new(PreScreen)];
+    if level >= CheckLevel::TrialFactoring {
+        let to_bits = full.then_some(FULL_TRIAL_FACTOR_BITS);
+        tests.push(Box::new(TrialFactoring { to_bits, ..TrialFactoring::default() }));
+    }
+    if level >= CheckLevel::PMinusOne {
+        let b2_multiplier = if full { FULL_P_MINUS_1_B2_MULTIPLIER } else { 0 };
+        tests.push(Box::new(PMinusOne { b2_multiplier, ..PMinusOne::default() }));
+    }
+    if level >= CheckLevel::Probabilistic {
+        let rounds = if full { FULL_MILLER_RABIN_ROUNDS } else { DEFAULT_MILLER_RABIN_ROUNDS };
+        tests.push(Box::new(MillerRabin { rounds }));
+    }
+    if level >= CheckLevel::LucasLehmer {
+        tests.push(Box::new(LucasLehmer));
+    }
+    tests
+}
@@ -0,0 +1,208 @@
+/*!
+The list of known Mersenne primes, kept current from GIMPS.
+
+`fetch_known_mersenne_primes` reads the exponents from the public list at
+<https://www.mersenne.org/primes/>, so a newly discovered prime shows up
+without waiting for a release of this crate. The answer is cached on disk for
+a day, and whenever the list cannot be downloaded or makes no sense the
+exponents built into the crate (`known_mersenne_primes`) are used instead.
+
+This module is only compiled with the `primenet` feature.
+*/
+
+use crate::{is_prime, known_mersenne_primes, parse_exponent_list, PrimalityError};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Page listing the known Mersenne primes
+pub const PRIMENET_PRIMES_URL: &str = "https://www.mersenne.org/primes/";
+
+/// How long a cached list is used before it is downloaded again
+pub const CACHE_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long to wait for the server before falling back
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where `fetch_known_mersenne_primes` caches the list
+pub fn default_cache_path() -> PathBuf {
+    std::env::temp_dir().join("primality_jones").join("known_mersenne_primes.txt")
+}
+
+/// Exponents of all known Mersenne primes according to GIMPS, in ascending order
+///
+/// Uses the cache at `default_cache_path` if it is younger than
+/// `CACHE_MAX_AGE`, and otherwise downloads the list and refreshes the cache.
+/// If the download fails, or the page does not contain every exponent this
+/// crate already knows, the cached list is used however old it is, and
+/// failing that `known_mersenne_primes`. Network trouble therefore never
+/// causes an error.
+///
+/// # Errors
+///
+/// Fails only if the cache file exists but cannot be read, or a freshly
+/// downloaded list cannot be written to it.
+///
+/// # Examples
+///
+/// ```no_run
+/// use primality_jones::known_mersenne_primes;
+/// use primality_jones::primenet::fetch_known_mersenne_primes;
+///
+/// let exponents = fetch_known_mersenne_primes()?;
+/// assert!(exponents.starts_with(known_mersenne_primes()));
+/// # Ok::<(), primality_jones::PrimalityError>(())
+/// ```
+pub fn fetch_known_mersenne_primes() -> Result<Vec<u64>, PrimalityError> {
+    let cache = default_cache_path();
+    if let Some(dir) = cache.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fetch_with_cache(PRIMENET_PRIMES_URL, &cache, CACHE_MAX_AGE)
+}
+
+/// `fetch_known_mersenne_primes` with the page, cache file and cache lifetime given
+fn fetch_with_cache(url: &str, cache: &Path, max_age: Duration) -> Result<Vec<u64>, PrimalityError> {
+    let cached = read_cache(cache)?;
+    if let Some((fetched, exponents)) = &cached {
+        let age = SystemTime::now().duration_since(*fetched).unwrap_or(Duration::ZERO);
+        if age < max_age {
+            return Ok(exponents.clone());
+        }
+    }
+
+    match download(url) {
+        Some(exponents) => {
+            write_cache(cache, &exponents)?;
+            Ok(exponents)
+        }
+        None => Ok(cached.map_or_else(|| known_mersenne_primes().to_vec(), |(_, exponents)| exponents)),
+    }
+}
+
+/// Download and parse the list, or `None` if either step fails
+fn download(url: &str) -> Option<Vec<u64>> {
+    let page = ureq::get(url).timeout(REQUEST_TIMEOUT).call().ok()?.into_string().ok()?;
+    let exponents = parse_primes_page(&page);
+
+    // A change to the page layout would most likely lose entries, so insist on
+    // every exponent already known; anything new must at least be prime
+    let complete = known_mersenne_primes().iter().all(|p| exponents.binary_search(p).is_ok());
+    let plausible = exponents.iter().all(|&p| is_prime(p));
+    (complete && plausible).then_some(exponents)
+}
+
+/// The exponents p of every `2^p - 1` written on the page, sorted and deduplicated
+///
+/// Recognizes `2<sup>p</sup>-1` as well as the plain-text `2^p-1`, with or
+/// without spaces around the minus sign and with `&minus;` or `−` for it.
+fn parse_primes_page(page: &str) -> Vec<u64> {
+    let mut exponents = Vec::new();
+    for marker in ["2<sup>", "2^"] {
+        for (start, _) in page.match_indices(marker) {
+            // "12^3" is not "2^3"
+            if page[..start].ends_with(|c: char| c.is_ascii_digit()) {
+                continue;
+            }
+            let rest = &page[start + marker.len()..];
+            let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            let Ok(p) = rest[..digits].parse::<u64>() else {
+                continue;
+            };
+            let tail = rest[digits..].trim_start_matches("</sup>").trim_start();
+            let Some(tail) = ["-", "−", "&minus;"].iter().find_map(|minus| tail.strip_prefix(minus)) else {
+                continue;
+            };
+            let tail = tail.trim_start();
+            if tail.starts_with('1') && !tail[1..].starts_with(|c: char| c.is_ascii_digit()) {
+                exponents.push(p);
+            }
+        }
+    }
+    exponents.sort_unstable();
+    exponents.dedup();
+    exponents
+}
+
+/// The cached list and when it was downloaded, or `None` if there is no usable cache
+///
+/// The file holds a `fetched <unix seconds>` line followed by the exponents
+/// as a JSON array. A file in any other form is treated as missing, so it is
+/// simply replaced by the next download.
+fn read_cache(cache: &Path) -> Result<Option<(SystemTime, Vec<u64>)>, PrimalityError> {
+    let content = match fs::read_to_string(cache) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let Some((header, list)) = content.split_once('\n') else {
+        return Ok(None);
+    };
+    let Some(seconds) = header.strip_prefix("fetched ").and_then(|s| s.trim().parse::<u64>().ok()) else {
+        return Ok(None);
+    };
+    Ok(parse_exponent_list(list)
+        .ok()
+        .map(|exponents| (UNIX_EPOCH + Duration::from_secs(seconds), exponents)))
+}
+
+fn write_cache(cache: &Path, exponents: &[u64]) -> Result<(), PrimalityError> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO).as_secs();
+    let list = exponents.iter().map(u64::to_string).collect::<Vec<_>>().join(", ");
+    fs::write(cache, format!("fetched {now}\n[{list}]\n"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Nothing listens on the discard port, so this fails at once
+    const UNREACHABLE: &str = "http://127.0.0.1:9/primes/";
+
+    #[test]
+    fn test_parse_primes_page() {
+        let page = "<tr><td>52</td><td>2<sup>136279841</sup>-1</td></tr>\
+                    <tr><td>51</td><td>2<sup>82589933</sup> &minus; 1</td></tr>\
+                    M127 = 2^127 - 1, and 2^127-1 again; 2^31−1; not 2^61+1, 2^89-17 or 12^5-1";
+        assert_eq!(parse_primes_page(page), vec![31, 127, 82_589_933, 136_279_841]);
+        assert!(parse_primes_page("").is_empty());
+    }
+
+    #[test]
+    fn test_cache_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("primes.txt");
+        assert!(read_cache(&cache).unwrap().is_none());
+
+        write_cache(&cache, &[2, 3, 5, 7]).unwrap();
+        let (fetched, exponents) = read_cache(&cache).unwrap().unwrap();
+        assert_eq!(exponents, vec![2, 3, 5, 7]);
+        assert!(SystemTime::now().duration_since(fetched).unwrap() < Duration::from_secs(60));
+
+        fs::write(&cache, "not a cache").unwrap();
+        assert!(read_cache(&cache).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_fetch_uses_fresh_cache_without_network() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("primes.txt");
+        write_cache(&cache, &[2, 3, 5]).unwrap();
+        assert_eq!(fetch_with_cache(UNREACHABLE, &cache, CACHE_MAX_AGE).unwrap(), vec![2, 3, 5]);
+    }
+
+    #[test]
+    fn test_fetch_falls_back_when_offline() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("primes.txt");
+
+        // No cache: the bundled list
+        assert_eq!(fetch_with_cache(UNREACHABLE, &cache, CACHE_MAX_AGE).unwrap(), known_mersenne_primes());
+
+        // A stale cache beats the bundled list
+        fs::write(&cache, "fetched 0\n[2, 3, 5]\n").unwrap();
+        assert_eq!(fetch_with_cache(UNREACHABLE, &cache, CACHE_MAX_AGE).unwrap(), vec![2, 3, 5]);
+    }
+}
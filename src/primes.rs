@@ -0,0 +1,542 @@
+/*!
+Primality of 64-bit integers, the exponents and trial factors the pipeline
+works with: deterministic Miller-Rabin, a shared memo table, a segmented sieve,
+and the pre-screen built on them.
+*/
+
+use crate::{primes_up_to, Exponent};
+use num_bigint::BigUint;
+use num_traits::One;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// If `n` is itself a Mersenne number 2^k - 1 (k >= 1), return k
+///
+/// Useful for spotting a Mersenne number entered where its exponent was meant,
+/// such as 2147483647 instead of 31.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::is_mersenne_number;
+///
+/// assert_eq!(is_mersenne_number(2147483647), Some(31));
+/// assert_eq!(is_mersenne_number(2147483646), None);
+/// ```
+pub fn is_mersenne_number(n: u64) -> Option<u64> {
+    if n == 0 {
+        return None;
+    }
+    // n = 2^k - 1 exactly when its binary form is k ones
+    (n.count_zeros() == n.leading_zeros()).then(|| u64::from(n.count_ones()))
+}
+
+/// Primality test used internally by the pipeline stages
+pub(crate) type PrimeTest<'a> = dyn Fn(u64) -> bool + Sync + 'a;
+
+/// Number of independently locked shards in a `PrimeCache`
+const PRIME_CACHE_SHARDS: usize = 16;
+
+/// Thread-safe memo table for `is_prime`
+///
+/// A batch run calls `is_prime` on the same exponent in more than one stage,
+/// and on the same candidate factors q = 2kp + 1 for different exponents.
+/// Sharing one cache across the batch (via `CheckConfig::prime_cache`) answers
+/// those repeats from memory. The table is split into independently locked
+/// shards so parallel trial factoring does not serialize on a single lock.
+///
+/// The cache grows without bound, so it is opt-in: single-shot calls do not
+/// use one.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::PrimeCache;
+///
+/// let cache = PrimeCache::new();
+/// assert!(cache.is_prime(127));
+/// assert!(cache.is_prime(127));
+/// assert_eq!((cache.hits(), cache.misses()), (1, 1));
+/// ```
+#[derive(Debug)]
+pub struct PrimeCache {
+    shards: Vec<Mutex<HashMap<u64, bool>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl PrimeCache {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        PrimeCache {
+            shards: (0..PRIME_CACHE_SHARDS).map(|_| Mutex::new(HashMap::new())).collect(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Memoized `is_prime`
+    pub fn is_prime(&self, n: u64) -> bool {
+        let shard = &self.shards[(n % PRIME_CACHE_SHARDS as u64) as usize];
+        if let Some(&known) = shard.lock().unwrap().get(&n) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return known;
+        }
+
+        // Compute outside the lock so other lookups on this shard are not blocked
+        let result = is_prime(n);
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        shard.lock().unwrap().insert(n, result);
+        result
+    }
+
+    /// Number of lookups answered from the cache
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Number of lookups that had to run `is_prime`
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Fraction of lookups answered from the cache (0.0 if none were made)
+    pub fn hit_rate(&self) -> f64 {
+        let hits = self.hits();
+        let total = hits + self.misses();
+        if total == 0 {
+            0.0
+        } else {
+            hits as f64 / total as f64
+        }
+    }
+
+    /// Number of distinct values stored
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.lock().unwrap().len()).sum()
+    }
+
+    /// Whether the cache holds no values
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for PrimeCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Check if a number is prime using trial division or Miller-Rabin for larger values
+///
+/// # Arguments
+///
+/// * `n` - The number to test for primality
+///
+/// # Returns
+///
+/// * `true` if the number is prime
+/// * `false` if the number is composite or less than 2
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::is_prime;
+///
+/// assert!(is_prime(31));
+/// assert!(!is_prime(15));
+/// ```
+pub fn is_prime(n: u64) -> bool {
+    if n <= 1 {
+        return false;
+    }
+    if n <= 3 {
+        return true;
+    }
+    if n.is_multiple_of(2) || n.is_multiple_of(3) {
+        return false;
+    }
+    
+    // For larger numbers, use Miller-Rabin test which is much faster
+if n > 1_000_000 {
+        // Use deterministic Miller-Rabin with known witnesses for u64 range
+        let witnesses: &[u64] = if n < 2_047 {
+            &[2]
+        } else if n < 1_373_653 {
+            &[2, 3]
+        } else if n < 9_080_191 {
+            &[31, 73]
+        } else if n < 25_326_001 {
+            &[2, 3, 5]
+        } else if n < 3_215_031_751 {
+            &[2, 3, 5, 7]
+        } else if n < 4_759_123_141 {
+            &[2, 7, 61]
+        } else if n < 1_122_004_669_633 {
+            &[2, 13, 23, 1662803]
+        } else if n < 2_152_302_898_747 {
+            &[2, 3, 5, 7, 11]
+        } else if n < 3_474_749_660_383 {
+            &[2, 3, 5, 7, 11, 13]
+        } else if n < 341_550_071_728_321 {
+            &[2, 3, 5, 7, 11, 13, 17]
+        } else {
+            &[2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37]
+        };
+        return miller_rabin_u64(n, witnesses);
+    }
+    
+    // Use trial division for smaller numbers (isqrt is exact, unlike an f64 sqrt)
+    let sqrt_n = n.isqrt();
+    let mut i = 5;
+    while i <= sqrt_n {
+        if n.is_multiple_of(i) || n.is_multiple_of(i + 2) {
+            return false;
+        }
+        i += 6;
+    }
+    true
+}
+
+/// Deterministic Miller-Rabin primality test for any `u64`
+///
+/// Runs strong probable-prime tests to a fixed set of seven bases found by
+/// Jim Sinclair, which no composite below 2^64 passes. The often-quoted set
+/// {2, 3, 5, 7, 11, 13, 17} is only proven up to 341,550,071,728,321, so it
+/// would not cover the whole range. All arithmetic stays in `u64`/`u128`;
+/// unlike `is_prime` there is no trial-division path for small inputs.
+///
+/// # Arguments
+///
+/// * `n` - The number to test for primality
+///
+/// # Returns
+///
+/// * `true` if the number is prime
+/// * `false` if the number is composite or less than 2
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::is_prime_deterministic;
+///
+/// assert!(is_prime_deterministic(1_000_000_007));
+/// assert!(!is_prime_deterministic(341_550_071_728_321)); // fools bases 2 through 17
+/// ```
+pub fn is_prime_deterministic(n: u64) -> bool {
+    const BASES: [u64; 7] = [2, 325, 9_375, 28_178, 450_775, 9_780_504, 1_795_265_022];
+
+    // A base that is a multiple of n is skipped, which would leave a composite
+    // n with fewer witnesses. Every such n shares a prime factor with a base.
+    for q in [2u64, 3, 5, 13, 19, 73, 193, 407_521, 299_210_837] {
+        if n.is_multiple_of(q) {
+            return n == q;
+        }
+    }
+    n >= 2 && miller_rabin_u64(n, &BASES)
+}
+
+/// The smallest prime strictly greater than `n`
+///
+/// Steps through the odd numbers above `n`, testing each with
+/// `is_prime_deterministic`, so it stays fast anywhere in the `u64` range.
+/// Useful for walking candidate exponents one at a time; for a whole range
+/// `prime_sieve` is cheaper.
+///
+/// # Panics
+///
+/// Panics if `n` is at least 18,446,744,073,709,551,557, the largest prime
+/// that fits in a `u64`.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::next_prime;
+///
+/// assert_eq!(next_prime(0), 2);
+/// assert_eq!(next_prime(127), 131);
+/// ```
+pub fn next_prime(n: u64) -> u64 {
+    if n < 2 {
+        return 2;
+    }
+    let mut candidate = (n + 1) | 1;
+    while !is_prime_deterministic(candidate) {
+        candidate = candidate
+            .checked_add(2)
+            .unwrap_or_else(|| panic!("no prime above {} fits in a u64", n));
+    }
+    candidate
+}
+
+/// Numbers covered by each segment of a `PrimeSieve`
+pub(crate) const SIEVE_SEGMENT_LEN: u64 = 1 << 18;
+
+/// All primes up to and including `limit`, in increasing order
+///
+/// A segmented sieve of Eratosthenes: only the primes up to √limit and one
+/// segment of `SIEVE_SEGMENT_LEN` flags are held at a time, so memory stays
+/// bounded however large `limit` is. Since M_p can only be prime when p is,
+/// this is the natural source of exponents for a batch search.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::prime_sieve;
+///
+/// let primes: Vec<u64> = prime_sieve(30).collect();
+/// assert_eq!(primes, [2, 3, 5, 7, 11, 13, 17, 19, 23, 29]);
+///
+/// // Mersenne candidates between 10000 and 10100
+/// let exponents: Vec<u64> = prime_sieve(10_100).skip_while(|&p| p < 10_000).collect();
+/// assert_eq!(exponents.len(), 11);
+/// ```
+pub fn prime_sieve(limit: u64) -> PrimeSieve {
+    PrimeSieve {
+        limit,
+        base_primes: primes_up_to(limit.isqrt()),
+        next_low: Some(0),
+        low: 0,
+        composite: Vec::new(),
+        pos: 0,
+    }
+}
+
+/// Iterator over primes returned by `prime_sieve`
+#[derive(Debug, Clone)]
+pub struct PrimeSieve {
+    limit: u64,
+    /// Primes up to √limit, used to cross off each segment
+    base_primes: Vec<u64>,
+    /// Start of the next segment to sieve, if any remain
+    next_low: Option<u64>,
+    /// Start of the current segment
+    low: u64,
+    /// `composite[i]` is true when `low + i` is not prime
+    composite: Vec<bool>,
+    /// Next index of the current segment to yield from
+    pos: usize,
+}
+
+impl PrimeSieve {
+    /// Sieve the segment starting at `low`
+    fn fill(&mut self, low: u64) {
+        let high = self.limit.min(low.saturating_add(SIEVE_SEGMENT_LEN - 1));
+        self.next_low = high.checked_add(1).filter(|&next| next <= self.limit);
+        self.low = low;
+        self.pos = 0;
+        self.composite.clear();
+        self.composite.resize((high - low + 1) as usize, false);
+
+        for n in low..=high.min(1) {
+            self.composite[(n - low) as usize] = true;
+        }
+        for &q in &self.base_primes {
+            if q > high / q {
+                break;
+            }
+            let first = (q * q).max(low.div_ceil(q) * q);
+            for multiple in (first..=high).step_by(q as usize) {
+                self.composite[(multiple - low) as usize] = true;
+            }
+        }
+    }
+}
+
+impl Iterator for PrimeSieve {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        loop {
+            while let Some(&composite) = self.composite.get(self.pos) {
+                self.pos += 1;
+                if !composite {
+                    return Some(self.low + self.pos as u64 - 1);
+                }
+            }
+            let low = self.next_low?;
+            self.fill(low);
+        }
+    }
+}
+
+/// Outcome of the pre-screen, explaining why an exponent was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrescreenResult {
+    /// The exponent is prime, so M_p may be prime
+    ExponentPrime,
+    /// The exponent is composite; `factor` is its smallest prime factor
+    ///
+    /// If p = ab then M_a divides M_p, so M_p cannot be prime.
+    ExponentComposite {
+        /// Smallest prime factor of the exponent
+        factor: u64,
+    },
+    /// The exponent is 0 or 1 (M_0 = 0 and M_1 = 1 are not candidates)
+    ExponentTooSmall,
+}
+
+impl fmt::Display for PrescreenResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrescreenResult::ExponentPrime => write!(f, "Exponent is prime"),
+            PrescreenResult::ExponentComposite { factor } => {
+                write!(f, "Exponent is not prime (divisible by {factor})")
+            }
+            PrescreenResult::ExponentTooSmall => write!(f, "Exponent is not prime (less than 2)"),
+        }
+    }
+}
+
+/// Run only the pre-screen stage and report why the exponent was rejected
+///
+/// Primality is decided with `is_prime`; for a composite exponent the smallest
+/// prime factor is then found by trial division, which never needs to go past
+/// the square root of `p`.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::{prescreen, PrescreenResult};
+///
+/// assert_eq!(prescreen(31), PrescreenResult::ExponentPrime);
+/// assert_eq!(prescreen(35), PrescreenResult::ExponentComposite { factor: 5 });
+/// ```
+pub fn prescreen(p: impl Into<Exponent>) -> PrescreenResult {
+    let p = p.into().get();
+    prescreen_with(p, &is_prime)
+}
+
+/// Pre-screen using the supplied primality test (e.g. a cached `is_prime`)
+pub(crate) fn prescreen_with(p: u64, prime_test: &PrimeTest<'_>) -> PrescreenResult {
+    if p < 2 {
+        return PrescreenResult::ExponentTooSmall;
+    }
+    if prime_test(p) {
+        return PrescreenResult::ExponentPrime;
+    }
+
+    PrescreenResult::ExponentComposite {
+        factor: smallest_prime_factor(p),
+    }
+}
+
+/// Algebraic factor of M_p that follows from a divisor of the exponent
+///
+/// If d divides p then M_d = 2^d - 1 divides M_p = 2^p - 1, so every factor of
+/// M_d is also a factor of M_p. For a composite `p` this returns the smallest
+/// prime divisor d of `p` together with M_d; for prime `p` (or p < 2) there is
+/// no such factor and `None` is returned.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::mersenne_algebraic_factor;
+///
+/// // 3 divides 15, so M3 = 7 divides M15 = 32767
+/// assert_eq!(mersenne_algebraic_factor(15), Some((3, BigUint::from(7u32))));
+/// assert_eq!(mersenne_algebraic_factor(13), None);
+/// ```
+pub fn mersenne_algebraic_factor(p: impl Into<Exponent>) -> Option<(u64, BigUint)> {
+    let p = p.into().get();
+    match prescreen(p) {
+        PrescreenResult::ExponentComposite { factor } => {
+            Some((factor, (BigUint::one() << factor) - BigUint::one()))
+        }
+        _ => None,
+    }
+}
+
+/// Smallest prime factor of a composite `n` by trial division
+fn smallest_prime_factor(n: u64) -> u64 {
+    if n.is_multiple_of(2) {
+        return 2;
+    }
+    if n.is_multiple_of(3) {
+        return 3;
+    }
+    let mut i = 5;
+    while i <= n / i {
+        if n.is_multiple_of(i) {
+            return i;
+        }
+        if n.is_multiple_of(i + 2) {
+            return i + 2;
+        }
+        i += 6;
+    }
+    n
+}
+
+/// Fast Miller-Rabin primality test for u64 numbers
+/// This is much faster than trial division for large numbers
+fn miller_rabin_u64(n: u64, witnesses: &[u64]) -> bool {
+    if n < 2 {
+        return false;
+    }
+    if n == 2 || n == 3 {
+        return true;
+    }
+    if n.is_multiple_of(2) {
+        return false;
+    }
+
+    // Write n-1 as d * 2^r
+    let mut d = n - 1;
+    let mut r = 0;
+    while d.is_multiple_of(2) {
+        d /= 2;
+        r += 1;
+    }
+
+    'witness_loop: for &a in witnesses {
+        let a = a % n;
+        if a == 0 {
+            continue;
+        }
+        
+        let mut x = mod_pow_u64(a, d, n);
+        if x == 1 || x == n - 1 {
+            continue 'witness_loop;
+        }
+        
+        for _ in 0..r - 1 {
+            x = mod_mul_u64(x, x, n);
+            if x == n - 1 {
+                continue 'witness_loop;
+            }
+        }
+        
+        return false;
+    }
+    
+    true
+}
+
+/// Fast modular exponentiation for u64
+pub(crate) fn mod_pow_u64(mut base: u64, mut exp: u64, modulus: u64) -> u64 {
+    if modulus == 1 {
+        return 0;
+    }
+    let mut result = 1;
+    base %= modulus;
+    while exp > 0 {
+        if exp % 2 == 1 {
+            result = mod_mul_u64(result, base, modulus);
+        }
+        exp >>= 1;
+        base = mod_mul_u64(base, base, modulus);
+    }
+    result
+}
+
+/// Fast modular multiplication for u64 to avoid overflow
+/// Optimized for large moduli
+pub(crate) fn mod_mul_u64(a: u64, b: u64, modulus: u64) -> u64 {
+    // For huge moduli, use u128 arithmetic which is fast on 64-bit systems
+    ((a as u128 * b as u128) % modulus as u128) as u64
+}
@@ -0,0 +1,802 @@
+/*!
+Probable-prime tests: Miller-Rabin on M_p, with timeouts and checkpoints,
+and general tests on arbitrary integers (Fermat, Baillie-PSW, Solovay-Strassen)
+and on Wagstaff numbers.
+*/
+
+use crate::mersenne::pow_mod_mp_stoppable;
+use crate::{is_prime, jacobi, mod_mp, validate_exponent, CheckOutcome, Exponent, Instant, PrimalityError};
+#[cfg(not(target_arch = "wasm32"))]
+use indicatif::{ProgressBar, ProgressStyle};
+use num_bigint::{BigUint, RandBigInt};
+use num_traits::{One, Zero};
+use rand::thread_rng;
+use rayon::prelude::*;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// Perform a Miller-Rabin primality test with parallel rounds
+///
+/// This is an optimized version that runs Miller-Rabin rounds in parallel
+/// for better performance on multi-core systems.
+///
+/// # Arguments
+///
+/// * `p` - The Mersenne exponent to test (testing 2^p - 1)
+/// * `k` - Number of rounds of testing (higher k = lower probability of false positive)
+/// * `start_time` - Start time of the test
+/// * `timeout` - Timeout for the test
+///
+/// # Returns
+///
+/// * `true` if all tests pass (number is probably prime)
+/// * `false` if any test fails (number is definitely composite), or if the
+///   timeout expired; use `miller_rabin_test_checked` to tell the two apart
+pub fn miller_rabin_test_parallel(p: impl Into<Exponent>, k: u32, start_time: Instant, timeout: Duration) -> bool {
+    let p = p.into().get();
+    miller_rabin_test_checked(p, k, start_time, timeout) == MillerRabinResult::ProbablyPrime
+}
+
+/// Outcome of a Miller-Rabin test that may have run out of time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MillerRabinResult {
+    /// Every round passed
+    ProbablyPrime,
+    /// Some round found a witness, so the number is definitely composite
+    Composite,
+    /// The timeout expired before all rounds ran and none found a witness.
+    /// This says nothing about primality.
+    Inconclusive,
+}
+
+/// Miller-Rabin test on M_p that returns a `CheckOutcome`
+///
+/// Takes the same arguments as `miller_rabin_test` and returns
+/// `ProbablyPrime` or `Composite`.
+///
+/// # Errors
+///
+/// `InvalidExponent` if p < 2, and `TimedOut` if the timeout expired before
+/// every round ran without any of them finding a witness.
+///
+/// # Example
+///
+/// ```
+/// use primality_jones::{miller_rabin_outcome, CheckOutcome, PrimalityError};
+/// use std::time::{Duration, Instant};
+///
+/// let outcome = miller_rabin_outcome(31, 5, Instant::now(), Duration::from_secs(30));
+/// assert_eq!(outcome.unwrap(), CheckOutcome::ProbablyPrime);
+///
+/// let outcome = miller_rabin_outcome(31, 5, Instant::now(), Duration::ZERO);
+/// assert!(matches!(outcome, Err(PrimalityError::TimedOut)));
+/// ```
+pub fn miller_rabin_outcome(
+    p: impl Into<Exponent>,
+    k: u32,
+    start_time: Instant,
+    timeout: Duration,
+) -> Result<CheckOutcome, PrimalityError> {
+    let p = p.into().get();
+    validate_exponent(p)?;
+    match miller_rabin_test_checked(p, k, start_time, timeout) {
+        MillerRabinResult::Inconclusive => Err(PrimalityError::TimedOut),
+        result => Ok(result.into()),
+    }
+}
+
+/// Perform a Miller-Rabin test that reports a timeout as `Inconclusive`
+///
+/// Runs the same parallel rounds as `miller_rabin_test_parallel`, but a round
+/// skipped because the timeout expired is never counted as a failure. A
+/// witness found by any round that did run still proves M_p composite.
+///
+/// # Arguments
+///
+/// * `p` - The Mersenne exponent to test (testing 2^p - 1)
+/// * `k` - Number of rounds of testing (higher k = lower probability of false positive)
+/// * `start_time` - Start time of the test
+/// * `timeout` - Timeout for the test
+///
+/// # Example
+///
+/// ```
+/// use primality_jones::{miller_rabin_test_checked, MillerRabinResult};
+/// use std::time::{Duration, Instant};
+///
+/// let result = miller_rabin_test_checked(31, 5, Instant::now(), Duration::from_secs(30));
+/// assert_eq!(result, MillerRabinResult::ProbablyPrime);
+///
+/// // Out of time before the first round: no verdict either way
+/// let result = miller_rabin_test_checked(31, 5, Instant::now(), Duration::ZERO);
+/// assert_eq!(result, MillerRabinResult::Inconclusive);
+/// ```
+pub fn miller_rabin_test_checked(
+    p: impl Into<Exponent>,
+    k: u32,
+    start_time: Instant,
+    timeout: Duration,
+) -> MillerRabinResult {
+    let p = p.into().get();
+    miller_rabin_rounds(p, k, start_time, timeout, true)
+}
+
+/// Run `k` random-base Miller-Rabin rounds on M_p in parallel, drawing a
+/// progress bar only if `show_progress` is set
+pub(crate) fn miller_rabin_rounds(
+    p: u64,
+    k: u32,
+    start_time: Instant,
+    timeout: Duration,
+    show_progress: bool,
+) -> MillerRabinResult {
+    let m = (BigUint::one() << p) - BigUint::one();
+    let m_minus_1 = &m - BigUint::one();
+
+    // Write m-1 = 2^s * d where d is odd
+    let mut s = 0;
+    let mut d = m_minus_1.clone();
+    while &d % BigUint::from(2u32) == BigUint::zero() {
+        s += 1;
+        d /= BigUint::from(2u32);
+    }
+
+    // Create progress bar for Miller-Rabin tests; WebAssembly has no terminal to draw it on
+    #[cfg(target_arch = "wasm32")]
+    let _ = show_progress;
+    #[cfg(not(target_arch = "wasm32"))]
+    let pb = if show_progress { ProgressBar::new(k as u64) } else { ProgressBar::hidden() };
+    #[cfg(not(target_arch = "wasm32"))]
+    pb.set_style(ProgressStyle::default_bar()
+        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} tests ({eta})")
+        .unwrap()
+        .progress_chars("#>-"));
+
+    // Run Miller-Rabin rounds in parallel; a round skipped for time is `None`
+    let results: Vec<Option<bool>> = (0..k).into_par_iter().map(|_| {
+        // Check timeout
+        if start_time.elapsed() >= timeout {
+            return None;
+        }
+
+        // Generate random base between 2 and m-1
+        let mut rng = thread_rng();
+        let a = rng.gen_biguint_range(&BigUint::from(2u32), &m);
+
+        // Compute x = a^d mod m
+        let mut x = a.modpow(&d, &m);
+
+        // If x == 1 or x == m-1, this round passes
+        if x == BigUint::one() || x == m_minus_1 {
+            return Some(true);
+        }
+
+        // Check x^(2^r) mod m for r = 1 to s-1
+        let mut is_witness = true;
+        for _r in 1..s {
+            x = x.modpow(&BigUint::from(2u32), &m);
+
+            if x == m_minus_1 {
+                is_witness = false;
+                break;
+            }
+
+            if x == BigUint::one() {
+                // Found a non-trivial square root of 1, so m is composite
+                return Some(false);
+            }
+        }
+
+        Some(!is_witness)
+    }).collect();
+
+    // Update progress bar
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        pb.inc(k as u64);
+        pb.finish_with_message("Completed");
+    }
+
+    // A witness is proof no matter how many rounds were skipped
+    if results.contains(&Some(false)) {
+        MillerRabinResult::Composite
+    } else if results.contains(&None) {
+        MillerRabinResult::Inconclusive
+    } else {
+        MillerRabinResult::ProbablyPrime
+    }
+}
+
+/// Single-base Fermat test on M_p: does `base`^(M_p - 1) ≡ 1 (mod M_p)?
+///
+/// A prime M_p always passes, so a failure proves M_p composite. A pass
+/// proves much less than a Miller-Rabin round: Fermat pseudoprimes are far
+/// more common than strong pseudoprimes, and Carmichael numbers pass for
+/// every base coprime to them. It is a cheap first-pass filter, one modular
+/// exponentiation reduced with `mod_mp`, to run before committing to
+/// `miller_rabin_test`, never a substitute for it.
+///
+/// Base 2 is useless here: for prime p every M_p, prime or not, passes it,
+/// since 2^p ≡ 1 (mod M_p) and p divides M_p - 1. A base that is 0 mod M_p
+/// proves nothing and passes. M_0 and M_1 are not primes and never pass.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::fermat_test;
+///
+/// let three = BigUint::from(3u32);
+/// assert!(fermat_test(31, &three));   // M31 is prime
+/// assert!(!fermat_test(11, &three));  // M11 = 23 * 89
+/// assert!(fermat_test(11, &BigUint::from(2u32))); // ...yet passes base 2
+/// ```
+pub fn fermat_test(p: impl Into<Exponent>, base: &BigUint) -> bool {
+    fermat_test_stoppable(p.into().get(), base, &|| false).expect("never stopped")
+}
+
+/// `fermat_test` that gives up with `None` when `stop` says so
+pub(crate) fn fermat_test_stoppable(p: u64, base: &BigUint, stop: &dyn Fn() -> bool) -> Option<bool> {
+    if p < 2 {
+        return Some(false);
+    }
+    let base = mod_mp(base, p);
+    if base.is_zero() {
+        return Some(true);
+    }
+    let m_minus_1 = (BigUint::one() << p) - BigUint::from(2u32);
+    pow_mod_mp_stoppable(&base, &m_minus_1, p, stop).map(|r| r.is_one())
+}
+
+/// Vrba-Reix probable-prime test on the Wagstaff number W_p = (2^p + 1)/3
+///
+/// The Wagstaff counterpart of the Lucas-Lehmer test: starting from
+/// S_0 = 3/2 (mod W_p), apply x -> x^2 - 2 p - 1 times; W_p passes when
+/// S_{p-1} ≡ S_0. Every Wagstaff prime passes, and no composite W_p is known
+/// to, but unlike Lucas-Lehmer this has not been proven, so a pass means
+/// "probable prime". The squarings are reduced modulo 2^p + 1 = 3W_p, where
+/// 2^p ≡ -1 turns the reduction into a subtraction of the high bits from the
+/// low ones, much as `mod_mp` adds them; only the final comparison is made
+/// modulo W_p.
+///
+/// W_p is an integer only for odd p, and composite whenever p is, so even
+/// exponents and composite ones answer false. W_3 = 3, where 3/2 ≡ 0 makes
+/// the test degenerate, answers true directly.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::wagstaff_prp_test;
+///
+/// assert!(wagstaff_prp_test(43));  // W43 = 2932031007403 is prime
+/// assert!(!wagstaff_prp_test(29)); // W29 = 59 * 3033169
+/// ```
+pub fn wagstaff_prp_test(p: u64) -> bool {
+    if p == 3 {
+        return true;
+    }
+    if p < 3 || !is_prime(p) {
+        return false;
+    }
+
+    let low_mask = (BigUint::one() << p) - BigUint::one();
+    let fermat = &low_mask + 2u32; // 2^p + 1
+    let wagstaff = &fermat / 3u32;
+
+    // Reduce modulo 2^p + 1: with k = high * 2^p + low, k ≡ low - high
+    let reduce = |mut k: BigUint| {
+        let mut negated = false;
+        while k.bits() > p {
+            let high = &k >> p;
+            k &= &low_mask;
+            if k >= high {
+                k -= high;
+            } else {
+                k = high - k;
+                negated = !negated;
+            }
+        }
+        if negated && !k.is_zero() {
+            &fermat - k
+        } else {
+            k
+        }
+    };
+
+    // 3/2 = 3 * (W_p + 1)/2, since W_p is odd
+    let start = (&wagstaff + 1u32) * 3u32 / 2u32 % &wagstaff;
+    let mut s = start.clone();
+    for _ in 0..p - 1 {
+        s = reduce(&s * &s + &fermat - 2u32);
+    }
+    s % &wagstaff == start
+}
+
+/// Perform a Miller-Rabin primality test with specified parameters
+///
+/// The Miller-Rabin test is a probabilistic primality test that is strictly stronger
+/// than the Fermat test. It can detect all strong pseudoprimes and is the standard
+/// for probabilistic primality testing.
+///
+/// # Arguments
+///
+/// * `p` - The Mersenne exponent to test (testing 2^p - 1)
+/// * `k` - Number of rounds of testing (higher k = lower probability of false positive)
+/// * `start_time` - Start time of the test
+/// * `timeout` - Timeout for the test
+///
+/// # Returns
+///
+/// * `true` if all tests pass (number is probably prime)
+/// * `false` if any test fails (number is definitely composite), or if the
+///   timeout expired; use `miller_rabin_test_checked` to tell the two apart
+///
+/// # Algorithm
+///
+/// For a Mersenne number M_p = 2^p - 1:
+/// 1. Write M_p - 1 = 2^s * d where d is odd
+/// 2. For each round, choose a random base a in [2, M_p-1]
+/// 3. Compute x = a^d mod M_p
+/// 4. If x == 1 or x == M_p-1, continue to next round
+/// 5. For r = 1 to s-1, compute x = x^2 mod M_p
+/// 6. If x == 1, the number is composite
+/// 7. If x == M_p-1, continue to next round
+/// 8. If we reach here, the number is composite
+/// 9. If all rounds pass, the number is probably prime
+pub fn miller_rabin_test(p: impl Into<Exponent>, k: u32, start_time: Instant, timeout: Duration) -> bool {
+    let p = p.into().get();
+    // Use parallel version for better performance
+    miller_rabin_test_parallel(p, k, start_time, timeout)
+}
+
+/// Perform the same Miller-Rabin test as `miller_rabin_test` without drawing
+/// a progress bar
+///
+/// Use this from tests, benchmarks and batch runs, where the bar would only
+/// clutter the output.
+///
+/// # Example
+///
+/// ```
+/// use primality_jones::miller_rabin_test_quiet;
+/// use std::time::{Duration, Instant};
+///
+/// assert!(miller_rabin_test_quiet(31, 5, Instant::now(), Duration::from_secs(30)));
+/// assert!(!miller_rabin_test_quiet(11, 5, Instant::now(), Duration::from_secs(30)));
+/// ```
+pub fn miller_rabin_test_quiet(p: impl Into<Exponent>, k: u32, start_time: Instant, timeout: Duration) -> bool {
+    let p = p.into().get();
+    miller_rabin_rounds(p, k, start_time, timeout, false) == MillerRabinResult::ProbablyPrime
+}
+
+/// Perform a Miller-Rabin test on M_p with caller-chosen bases
+///
+/// Runs exactly one round per entry of `bases`, in parallel, so two runs with
+/// the same bases always agree. Each base is reduced mod M_p first; a base
+/// that is 0, 1 or M_p - 1 there proves nothing and always passes. Use
+/// `miller_rabin_test` for random bases.
+///
+/// # Arguments
+///
+/// * `p` - The Mersenne exponent to test (testing 2^p - 1)
+/// * `bases` - The bases to test with, one round each
+/// * `start_time` - Start time of the test
+/// * `timeout` - Timeout for the test
+///
+/// # Returns
+///
+/// * `true` if every base passes (number is probably prime)
+/// * `false` if some base is a witness (number is definitely composite), or
+///   if the timeout expired before every base was tried
+///
+/// # Example
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::miller_rabin_test_with_bases;
+/// use std::time::{Duration, Instant};
+///
+/// let bases = [BigUint::from(2u32), BigUint::from(3u32)];
+/// assert!(miller_rabin_test_with_bases(31, &bases, Instant::now(), Duration::from_secs(30)));
+/// assert!(!miller_rabin_test_with_bases(11, &bases, Instant::now(), Duration::from_secs(30)));
+/// ```
+pub fn miller_rabin_test_with_bases(
+    p: impl Into<Exponent>,
+    bases: &[BigUint],
+    start_time: Instant,
+    timeout: Duration,
+) -> bool {
+    let p = p.into().get();
+    let m = (BigUint::one() << p) - BigUint::one();
+    if m < BigUint::from(4u32) {
+        return m > BigUint::one(); // M_2 = 3 is prime, M_0 and M_1 are not
+    }
+    let m_minus_1 = &m - BigUint::one();
+    let s = m_minus_1.trailing_zeros().unwrap_or(0);
+    let d = &m_minus_1 >> s;
+
+    bases.par_iter().all(|a| {
+        if start_time.elapsed() >= timeout {
+            return false;
+        }
+        let a = a % &m;
+        a.is_zero() || miller_rabin_round(&m, &m_minus_1, &d, s, &a)
+    })
+}
+
+/// Miller-Rabin test with random bases for an arbitrary odd modulus
+///
+/// Unlike `miller_rabin_test`, which always works on M_p, this accepts any `n`
+/// and runs its rounds sequentially. It is used where the number under test is
+/// not itself a Mersenne number, such as the cofactor left after dividing out
+/// known factors.
+pub(crate) fn miller_rabin_biguint(n: &BigUint, rounds: u32) -> bool {
+    let two = BigUint::from(2u32);
+    if n < &two {
+        return false;
+    }
+    if n == &two || n == &BigUint::from(3u32) {
+        return true;
+    }
+    if !n.bit(0) {
+        return false;
+    }
+
+    // Write n-1 = 2^s * d where d is odd
+    let n_minus_1 = n - BigUint::one();
+    let s = n_minus_1.trailing_zeros().unwrap_or(0);
+    let d = &n_minus_1 >> s;
+
+    let mut rng = thread_rng();
+    for _ in 0..rounds {
+        let a = rng.gen_biguint_range(&two, &n_minus_1);
+        if !miller_rabin_round(n, &n_minus_1, &d, s, &a) {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// One Miller-Rabin round on odd `n` with base `a`, where n - 1 = 2^s * d
+///
+/// Returns `false` if `a` proves `n` composite.
+fn miller_rabin_round(n: &BigUint, n_minus_1: &BigUint, d: &BigUint, s: u64, a: &BigUint) -> bool {
+    let mut x = a.modpow(d, n);
+    if x.is_one() || &x == n_minus_1 {
+        return true;
+    }
+    for _ in 1..s {
+        x = (&x * &x) % n;
+        if &x == n_minus_1 {
+            return true;
+        }
+    }
+    false
+}
+
+/// Baillie-PSW probable-prime test
+///
+/// Combines a strong Fermat test to base 2 with a strong Lucas test whose
+/// parameters are chosen by Selfridge's method: D is the first of 5, -7, 9,
+/// -11, ... with Jacobi symbol (D / n) = -1, P = 1 and Q = (1 - D) / 4. No
+/// composite number is known to pass both parts, and the test is fully
+/// deterministic, which makes it a stronger screen than a few rounds of
+/// Miller-Rabin with random bases.
+///
+/// It works on any `n`, so it can check both an exponent and the Mersenne
+/// number itself. For M_p with p prime the base-2 part always passes (every
+/// such M_p is a strong pseudoprime to base 2), so there the verdict rests on
+/// the Lucas part alone.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::{baillie_psw, Exponent};
+///
+/// assert!(baillie_psw(&BigUint::from(127u32)));
+/// assert!(baillie_psw(&Exponent::new(127).unwrap().mersenne_number()));
+/// assert!(!baillie_psw(&Exponent::new(67).unwrap().mersenne_number()));
+/// ```
+pub fn baillie_psw(n: &BigUint) -> bool {
+    if let Some(verdict) = small_prime_prefilter(n) {
+        return verdict;
+    }
+
+    let n_minus_1 = n - BigUint::one();
+    let s = n_minus_1.trailing_zeros().unwrap_or(0);
+    let d = &n_minus_1 >> s;
+    miller_rabin_round(n, &n_minus_1, &d, s, &BigUint::from(2u32)) && strong_lucas_test(n)
+}
+
+/// The cases `baillie_psw` and `strong_lucas_prp` settle before their main
+/// test: `Some(verdict)` for n < 2, the primes up to 37 and their multiples,
+/// and perfect squares, and `None` for everything else
+///
+/// A square never has (D / n) = -1, so `strong_lucas_test`'s search for D
+/// would not end on one.
+fn small_prime_prefilter(n: &BigUint) -> Option<bool> {
+    if n < &BigUint::from(2u32) {
+        return Some(false);
+    }
+    for q in [2u32, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37] {
+        if n == &BigUint::from(q) {
+            return Some(true);
+        }
+        if (n % q).is_zero() {
+            return Some(false);
+        }
+    }
+    let root = n.sqrt();
+    if &(&root * &root) == n {
+        return Some(false);
+    }
+    None
+}
+
+/// Strong Lucas probable-prime test with Selfridge's parameters
+///
+/// Uses Selfridge's Method A: D is the first of 5, -7, 9, -11, ... with
+/// Jacobi symbol (D / n) = -1, P = 1 and Q = (1 - D) / 4. Writing
+/// n + 1 = k * 2^s with k odd, n passes if U_k ≡ 0 or V_(k * 2^r) ≡ 0
+/// (mod n) for some 0 <= r < s, with the sequences computed by the binary
+/// method. Every prime passes.
+///
+/// Its pseudoprimes are a different family from those of Fermat and
+/// Miller-Rabin tests: Carmichael numbers and strong pseudoprimes to base 2
+/// such as 2047 fail it, while the strong Lucas pseudoprimes 5459, 5777,
+/// 10877, ... fail base 2. Pairing the two gives `baillie_psw`. Even
+/// numbers, perfect squares and numbers below 2 never pass, except 2.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::strong_lucas_prp;
+///
+/// assert!(strong_lucas_prp(&BigUint::from(127u32)));
+/// assert!(!strong_lucas_prp(&BigUint::from(561u32)));  // Carmichael number
+/// assert!(!strong_lucas_prp(&BigUint::from(2047u32))); // strong pseudoprime to base 2
+/// assert!(strong_lucas_prp(&BigUint::from(5459u32)));  // 53 * 103 fools it
+/// ```
+pub fn strong_lucas_prp(n: &BigUint) -> bool {
+    small_prime_prefilter(n).unwrap_or_else(|| strong_lucas_test(n))
+}
+
+/// Solovay-Strassen probable-prime test with `rounds` random bases
+///
+/// Each round picks a random base a with 2 <= a < n - 1 and checks Euler's
+/// criterion: a must be coprime to n and a^((n - 1)/2) ≡ (a / n) (mod n),
+/// with (a / n) the Jacobi symbol. Every prime passes; an odd composite fails
+/// each round with probability at least 1/2, so `rounds` rounds leave at most
+/// 2^-rounds. That bound is weaker than Miller-Rabin's 4^-rounds, and every
+/// base that fools Miller-Rabin also fools Solovay-Strassen, so
+/// `miller_rabin_test` and `baillie_psw` remain the tests to rely on; this one
+/// is here for comparison and teaching. Numbers below 2 and even numbers
+/// other than 2 never pass.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::{solovay_strassen, Exponent};
+///
+/// assert!(solovay_strassen(&Exponent::new(31).unwrap().mersenne_number(), 20));
+/// assert!(!solovay_strassen(&BigUint::from(561u32), 20)); // Carmichael number
+/// ```
+pub fn solovay_strassen(n: &BigUint, rounds: u32) -> bool {
+    let two = BigUint::from(2u32);
+    if n < &two {
+        return false;
+    }
+    if n == &two || n == &BigUint::from(3u32) {
+        return true;
+    }
+    if !n.bit(0) {
+        return false;
+    }
+
+    let n_minus_1 = n - BigUint::one();
+    let half = &n_minus_1 >> 1;
+    let mut rng = thread_rng();
+    for _ in 0..rounds {
+        let a = rng.gen_biguint_range(&two, &n_minus_1);
+        // For odd n the symbol is 0 exactly when gcd(a, n) > 1
+        let expected = match jacobi(&a, n) {
+            0 => return false,
+            1 => BigUint::one(),
+            _ => n_minus_1.clone(),
+        };
+        if a.modpow(&half, n) != expected {
+            return false;
+        }
+    }
+    true
+}
+
+/// Strong Lucas probable-prime test on odd `n` with Selfridge's parameters
+///
+/// `n` must not be a perfect square or divisible by a prime below 40.
+pub(crate) fn strong_lucas_test(n: &BigUint) -> bool {
+    // Residues of signed values modulo n
+    let residue = |x: i64| {
+        let magnitude = BigUint::from(x.unsigned_abs()) % n;
+        if x < 0 && !magnitude.is_zero() { n - magnitude } else { magnitude }
+    };
+    let sub = |a: &BigUint, b: &BigUint| if a >= b { a - b } else { n - b + a };
+    let half = |x: BigUint| if x.bit(0) { (x + n) >> 1 } else { x >> 1 };
+
+    let mut big_d: i64 = 5;
+    loop {
+        match jacobi(&residue(big_d), n) {
+            -1 => break,
+            0 if BigUint::from(big_d.unsigned_abs()) != *n => return false,
+            _ => big_d = if big_d > 0 { -(big_d + 2) } else { -big_d + 2 },
+        }
+    }
+    let d_mod = residue(big_d);
+    let q = (1 - big_d) / 4;
+    let q_mod = residue(q);
+
+    // n + 1 = k * 2^s with k odd
+    let n_plus_1 = n + BigUint::one();
+    let s = n_plus_1.trailing_zeros().unwrap_or(0);
+    let k = &n_plus_1 >> s;
+
+    // U_1 = 1, V_1 = P = 1, then walk the bits of k below the top one
+    let mut u = BigUint::one();
+    let mut v = BigUint::one();
+    let mut q_k = q_mod.clone();
+    for bit in (0..k.bits() - 1).rev() {
+        u = (&u * &v) % n;
+        v = sub(&((&v * &v) % n), &((&q_k << 1) % n));
+        q_k = (&q_k * &q_k) % n;
+        if k.bit(bit) {
+            let next_u = half((&u + &v) % n);
+            v = half((&d_mod * &u + &v) % n);
+            u = next_u;
+            q_k = (&q_k * &q_mod) % n;
+        }
+    }
+
+    if u.is_zero() || v.is_zero() {
+        return true;
+    }
+    for _ in 1..s {
+        v = sub(&((&v * &v) % n), &((&q_k << 1) % n));
+        if v.is_zero() {
+            return true;
+        }
+        q_k = (&q_k * &q_k) % n;
+    }
+    false
+}
+
+/// Rounds of a resumable Miller-Rabin test on M_p that have already passed
+///
+/// Stored as two `key=value` lines so a checkpoint can be inspected by hand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MillerRabinCheckpoint {
+    /// The exponent p of the Mersenne number under test
+    pub exponent: u64,
+    /// How many rounds, in base order, have passed
+    pub rounds_passed: u32,
+}
+
+impl MillerRabinCheckpoint {
+    /// Read a checkpoint written by `save`
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let field = |key: &str| {
+            content
+                .lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("checkpoint is missing '{}'", key))
+                })
+        };
+        let invalid = |key: &str| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("checkpoint has an invalid '{}'", key))
+        };
+        Ok(Self {
+            exponent: field("exponent")?.parse().map_err(|_| invalid("exponent"))?,
+            rounds_passed: field("rounds_passed")?.parse().map_err(|_| invalid("rounds_passed"))?,
+        })
+    }
+
+    /// Write the checkpoint, replacing any previous one
+    ///
+    /// The new contents go to a temporary file that is then renamed over
+    /// `path`, so an interruption never leaves a half-written checkpoint.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(
+            &tmp,
+            format!("exponent={}\nrounds_passed={}\n", self.exponent, self.rounds_passed),
+        )?;
+        fs::rename(&tmp, path)
+    }
+}
+
+/// Base used for round `round` of the resumable Miller-Rabin test
+///
+/// The bases are the odd primes 3, 5, 7, 11, ... in order, so a resumed run
+/// knows exactly which base comes next. Base 2 is skipped because
+/// 2^p ≡ 1 (mod M_p) makes it a strong liar for every Mersenne number.
+fn resumable_miller_rabin_base(round: u32) -> u64 {
+    (3..).step_by(2).filter(|&b| is_prime(b)).nth(round as usize).unwrap()
+}
+
+/// Miller-Rabin test on M_p that records each passed round in a checkpoint file
+///
+/// If `checkpoint` exists, the rounds it records are skipped and the test
+/// resumes at the next base; after each passing round the file is updated.
+/// It is removed once the test reaches a verdict. Unlike `miller_rabin_test`
+/// the bases are fixed, the odd primes 3, 5, 7, 11, ... in order, rather than
+/// random, which is what makes resuming possible.
+///
+/// # Errors
+///
+/// Fails if the checkpoint cannot be read or written, or if it belongs to a
+/// different exponent.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::miller_rabin_test_resumable;
+///
+/// let checkpoint = std::env::temp_dir().join("primality_jones_doc_mr_127.ckpt");
+/// assert!(miller_rabin_test_resumable(127, 5, &checkpoint).unwrap());
+/// assert!(!checkpoint.exists());
+/// ```
+pub fn miller_rabin_test_resumable<P: AsRef<Path>>(
+    p: impl Into<Exponent>,
+    rounds: u32,
+    checkpoint: P,
+) -> io::Result<bool> {
+    let p = p.into().get();
+    let checkpoint = checkpoint.as_ref();
+    if p < 3 {
+        return Ok(p == 2);
+    }
+
+    let mut state = if checkpoint.exists() {
+        let state = MillerRabinCheckpoint::load(checkpoint)?;
+        if state.exponent != p {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("checkpoint is for M{}, not M{}", state.exponent, p),
+            ));
+        }
+        state
+    } else {
+        MillerRabinCheckpoint { exponent: p, rounds_passed: 0 }
+    };
+
+    let n = (BigUint::one() << p) - BigUint::one();
+    let n_minus_1 = &n - BigUint::one();
+    let s = n_minus_1.trailing_zeros().unwrap_or(0);
+    let d = &n_minus_1 >> s;
+
+    let mut probably_prime = true;
+    while state.rounds_passed < rounds {
+        let a = BigUint::from(resumable_miller_rabin_base(state.rounds_passed)) % &n;
+        // a ≡ 0 only when M_p is the (prime) base itself
+        if !a.is_zero() && !miller_rabin_round(&n, &n_minus_1, &d, s, &a) {
+            probably_prime = false;
+            break;
+        }
+        state.rounds_passed += 1;
+        state.save(checkpoint)?;
+    }
+
+    if checkpoint.exists() {
+        fs::remove_file(checkpoint)?;
+    }
+    Ok(probably_prime)
+}
@@ -0,0 +1,505 @@
+/*!
+Searching M_p for small factors, by trial factoring and Pollard's P-1, and
+testing the cofactor left once the known factors are divided out.
+*/
+
+use crate::{
+    is_prime, is_prime_deterministic, miller_rabin_biguint, mod_mp_with_modulus, mod_mul_u64, mod_pow_u64, prime_sieve,
+    Exponent, PrimeTest,
+};
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use rayon::prelude::*;
+
+/// Check for small factors of a Mersenne number using parallel processing
+///
+/// This is an optimized version that uses parallel processing to check
+/// multiple potential factors simultaneously.
+///
+/// # Arguments
+///
+/// * `p` - The Mersenne exponent
+/// * `limit` - Maximum factor to check up to
+///
+/// # Returns
+///
+/// * `Some(factor)` if a factor is found
+/// * `None` if no factors are found
+pub fn check_small_factors_parallel(p: impl Into<Exponent>, limit: u64) -> Option<u64> {
+    let p = p.into().get();
+    find_small_factor(p, limit, &is_prime)
+}
+
+/// Parallel trial factoring using the supplied primality test for p and each q
+pub(crate) fn find_small_factor(p: u64, limit: u64, prime_test: &PrimeTest<'_>) -> Option<u64> {
+    if limit < 3 || !prime_test(p) {
+        return None;
+    }
+
+    // Calculate the maximum k value to check
+    let max_k = (limit - 1) / 2 / p;
+    
+    // Use parallel iterator to check factors
+    (1..=max_k).into_par_iter()
+        .map(|k| {
+            let q = 2 * k * p + 1;
+            if q > limit {
+                return None;
+            }
+            
+            // Check if q satisfies the congruence condition
+            if (q % 8 == 1 || q % 8 == 7) && prime_test(q) {
+                // Check if q divides 2^p - 1 using modular arithmetic
+                let remainder = BigUint::from(2u32).modpow(&BigUint::from(p), &BigUint::from(q));
+                if remainder == BigUint::one() {
+                    // Don't count M_p itself as a factor
+                    let m_p = (BigUint::one() << p) - BigUint::one();
+                    if BigUint::from(q) != m_p {
+                        return Some(q);
+                    }
+                }
+            }
+            None
+        })
+        .find_any(|result| result.is_some())
+        .flatten()
+}
+
+/// Check for small factors of a Mersenne number using special properties
+pub fn check_small_factors(p: impl Into<Exponent>, limit: u64) -> Option<u64> {
+    let p = p.into().get();
+    // Use parallel version for better performance
+    check_small_factors_parallel(p, limit)
+}
+
+/// k-values sieved at a time by `check_small_factors_sieved`
+const TRIAL_FACTOR_SEGMENT_LEN: u64 = 1 << 15;
+
+/// Largest prime `check_small_factors_sieved` sieves candidate factors with
+///
+/// Sieving with larger primes removes too few candidates to pay for itself;
+/// a composite that slips through costs one wasted `modpow`, never a wrong
+/// answer.
+const TRIAL_FACTOR_SIEVE_LIMIT: u64 = 1 << 16;
+
+/// Candidate factors whose `modpow`s `check_small_factors_sieved` runs together
+const TRIAL_FACTOR_BATCH: usize = 8;
+
+/// Trial factoring of M_p that sieves the candidate factors first
+///
+/// Finds the same factors as `check_small_factors`, but rather than testing
+/// every q = 2kp + 1 with `is_prime`, it works through the k-values in
+/// segments and crosses off each k for which q is divisible by a prime below
+/// 2^16, the way a sieve of Eratosthenes would; for q ≤ 2^32 that leaves only
+/// primes. The survivors that are ≡ ±1 (mod 8) are checked for 2^p ≡ 1
+/// (mod q) in batches of eight that share one pass over the bits of p, with
+/// `u64` arithmetic instead of `BigUint`.
+///
+/// The k-values are searched in order, so unlike `check_small_factors` the
+/// factor returned is always the smallest one up to `limit`. A composite
+/// survivor cannot be returned, since its prime factors divide M_p too and
+/// would have been found first. M_p itself is not counted as a factor.
+///
+/// # Arguments
+///
+/// * `p` - The Mersenne exponent
+/// * `limit` - Maximum factor to check up to
+///
+/// # Returns
+///
+/// * `Some(factor)` with the smallest factor of M_p up to `limit`
+/// * `None` if there is none, or p is not prime
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::check_small_factors_sieved;
+///
+/// assert_eq!(check_small_factors_sieved(11, 1000), Some(23));
+/// // M67 = 193707721 * 761838257287
+/// assert_eq!(check_small_factors_sieved(67, 200_000_000), Some(193_707_721));
+/// assert_eq!(check_small_factors_sieved(127, 1_000_000), None);
+/// ```
+pub fn check_small_factors_sieved(p: impl Into<Exponent>, limit: u64) -> Option<u64> {
+    let p = p.into().get();
+    if p == 0 || limit < 3 {
+        return None;
+    }
+    let max_k = (limit - 1) / 2 / p;
+    if max_k == 0 || !is_prime(p) {
+        return None;
+    }
+    sieved_factor_search(p, 1, max_k)
+}
+
+/// The smallest prime factor q = 2kp + 1 of M_p with `min_k` ≤ k ≤ `max_k`
+///
+/// The search behind `check_small_factors_sieved` and `trial_factor_to_bits`.
+/// p must be prime, `min_k` at least 1 and 2 * `max_k` * p + 1 must fit in a
+/// `u64`.
+fn sieved_factor_search(p: u64, min_k: u64, max_k: u64) -> Option<u64> {
+    let mersenne = (p < 64).then(|| (1u64 << p) - 1);
+    let largest = 2 * max_k * p + 1;
+
+    // For each sieving prime r, q = 2kp + 1 is a multiple of r exactly when
+    // k ≡ -(2p)^-1 (mod r); `own_k` is the k for which q is r itself
+    let sieve: Vec<(u64, u64, u64)> = primes_up_to(largest.isqrt().min(TRIAL_FACTOR_SIEVE_LIMIT))
+        .into_iter()
+        .filter(|&r| r > 2 && r != p)
+        .map(|r| {
+            let inverse = mod_pow_u64(2 * p % r, r - 2, r);
+            let own_k = if (r - 1).is_multiple_of(2 * p) { (r - 1) / (2 * p) } else { 0 };
+            ((r - inverse) % r, r, own_k)
+        })
+        .collect();
+
+    let mut crossed_off = vec![false; TRIAL_FACTOR_SEGMENT_LEN as usize];
+    let mut candidates = Vec::new();
+    let mut start = min_k;
+    while start <= max_k {
+        let len = (max_k - start + 1).min(TRIAL_FACTOR_SEGMENT_LEN);
+        crossed_off.fill(false);
+        for &(k0, r, own_k) in &sieve {
+            // First k ≥ start with k ≡ k0 (mod r)
+            let mut k = start + (k0 + r - start % r) % r;
+            while k < start + len {
+                if k != own_k {
+                    crossed_off[(k - start) as usize] = true;
+                }
+                k += r;
+            }
+        }
+
+        candidates.clear();
+        candidates.extend(
+            (start..start + len)
+                .filter(|&k| !crossed_off[(k - start) as usize])
+                .map(|k| 2 * k * p + 1)
+                .filter(|&q| q % 8 == 1 || q % 8 == 7),
+        );
+        for batch in candidates.chunks(TRIAL_FACTOR_BATCH) {
+            let residues = pow2_mod_batch(p, batch);
+            // Searching from k = 1 a composite q never gets here, as its prime
+            // factors would have been found first, but a range starting higher
+            // may have skipped them
+            let factor = batch
+                .iter()
+                .zip(residues)
+                .find(|&(&q, x)| x == 1 && Some(q) != mersenne && is_prime_deterministic(q));
+            if let Some((&q, _)) = factor {
+                return Some(q);
+            }
+        }
+        start += len;
+    }
+    None
+}
+
+/// Trial factoring of M_p over one range of bit levels
+///
+/// GIMPS records trial factoring by bit level: M_p "factored to 2^72" has
+/// had every candidate below 2^72 checked. This searches the factors
+/// q = 2kp + 1 with 2^`from_bits` ≤ q < 2^`to_bits`, so taking M_p from one
+/// bit level to a higher one repeats none of the earlier work, and a search
+/// can be split into consecutive ranges. In terms of k the range is
+///
+/// ```text
+/// ceil((2^from_bits - 1) / 2p)  ≤  k  ≤  floor((2^to_bits - 2) / 2p)
+/// ```
+///
+/// (with k at least 1), since q is odd and so below 2^`to_bits` exactly when
+/// it is at most 2^`to_bits` - 1. The candidates are sieved and tested as in
+/// `check_small_factors_sieved`.
+///
+/// # Arguments
+///
+/// * `p` - The Mersenne exponent
+/// * `from_bits` - Bit level already reached; 0 searches from the start
+/// * `to_bits` - Bit level to reach, at most 64 so that a factor fits in a
+///   `u64`; larger values are treated as 64
+///
+/// # Returns
+///
+/// * `Some(factor)` with the smallest prime factor of M_p in the range
+/// * `None` if there is none, p is not prime or `from_bits` ≥ `to_bits`
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::trial_factor_to_bits;
+///
+/// // M67 = 193707721 * 761838257287, and 2^27 < 193707721 < 2^28
+/// assert_eq!(trial_factor_to_bits(67, 0, 27), None);
+/// assert_eq!(trial_factor_to_bits(67, 27, 28), Some(193_707_721));
+///
+/// // Extending M1000133 from 2^32 to 2^33 finds 8007064799
+/// assert_eq!(trial_factor_to_bits(1_000_133, 0, 32), None);
+/// assert_eq!(trial_factor_to_bits(1_000_133, 32, 33), Some(8_007_064_799));
+/// ```
+pub fn trial_factor_to_bits(p: impl Into<Exponent>, from_bits: u32, to_bits: u32) -> Option<u64> {
+    let p = p.into().get();
+    let to_bits = to_bits.min(u64::BITS);
+    if p == 0 || from_bits >= to_bits || !is_prime(p) {
+        return None;
+    }
+    let two_p = 2 * u128::from(p);
+    let min_k = ((1u128 << from_bits) - 1).div_ceil(two_p).max(1);
+    let max_k = ((1u128 << to_bits) - 2) / two_p;
+    if min_k > max_k {
+        return None;
+    }
+    // Both fit: max_k * 2p < 2^64
+    sieved_factor_search(p, min_k as u64, max_k as u64)
+}
+
+/// 2^p mod q for up to `TRIAL_FACTOR_BATCH` moduli at once
+///
+/// The moduli share the same square-and-double steps, so running them in
+/// lockstep leaves the multiplications independent of each other and lets
+/// the CPU overlap them. Entries past `moduli.len()` are meaningless.
+fn pow2_mod_batch(p: u64, moduli: &[u64]) -> [u64; TRIAL_FACTOR_BATCH] {
+    let mut x = [1u64; TRIAL_FACTOR_BATCH];
+    for bit in (0..u64::BITS - p.leading_zeros()).rev() {
+        let double = (p >> bit) & 1 == 1;
+        for (x, &q) in x.iter_mut().zip(moduli) {
+            *x = mod_mul_u64(*x, *x, q);
+            if double {
+                // 2x < 2q may not fit in a u64, but 2x - q does
+                let (twice, overflowed) = x.overflowing_add(*x);
+                *x = if overflowed || twice >= q { twice.wrapping_sub(q) } else { twice };
+            }
+        }
+    }
+    x
+}
+
+/// Stage-1 bound the pipeline uses for P-1 on M_p
+///
+/// Stage 1 costs about 1.44 * B1 modular squarings against the p - 2 of a
+/// Lucas-Lehmer test, so B1 = p / 20 keeps it near 7% of the definitive test.
+/// Small exponents get B1 = 1000 regardless, which is still only milliseconds.
+pub(crate) fn default_p_minus_1_bound(p: u64) -> u64 {
+    (p / 20).max(1000)
+}
+
+/// Stage-1 Pollard P-1 factoring of M_p with smoothness bound `b1`
+///
+/// Finds a factor q of M_p when q - 1 is `b1`-smooth apart from the factor
+/// 2p that every Mersenne factor q = 2kp + 1 has anyway. It computes
+/// x = 3^(2p * E) mod M_p, where E is the product of the largest powers of the
+/// primes up to `b1` that do not exceed it, and returns gcd(x - 1, M_p).
+/// `pollard_p_minus_1_with_stage2` extends the search to factors with one
+/// larger prime in q - 1.
+///
+/// # Returns
+///
+/// * `Some(factor)` - a proper divisor of M_p, which may be a product of
+///   several prime factors if more than one is smooth
+/// * `None` - no factor was found, or every factor was found at once and the
+///   gcd is M_p itself
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::pollard_p_minus_1;
+///
+/// // M67 = 193707721 * 761838257287, and 193707720 = 2^3 * 3^3 * 5 * 67 * 2677
+/// assert_eq!(pollard_p_minus_1(67, 3000), Some(BigUint::from(193707721u32)));
+/// assert_eq!(pollard_p_minus_1(67, 2000), None);
+/// ```
+pub fn pollard_p_minus_1(p: impl Into<Exponent>, b1: u64) -> Option<BigUint> {
+    let p = p.into().get();
+    pollard_p_minus_1_with_stage2(p, b1, 0)
+}
+
+/// Pollard P-1 factoring of M_p with stage 2 up to `b2`
+///
+/// Runs stage 1 as `pollard_p_minus_1` does, and if that finds nothing,
+/// stage 2 as well: it also finds a factor q when (q - 1) / 2p is `b1`-smooth
+/// apart from a single prime s with `b1` < s ≤ `b2`. With x from stage 1 it
+/// multiplies together x^s - 1 for every such s and takes one gcd with M_p at
+/// the end. Consecutive primes differ by a small even gap d, so each x^s is
+/// the previous one times x^d, read from a table of the powers x^2, x^4, ...
+/// built as gaps appear; a step therefore costs two multiplications modulo
+/// M_p, against the modular exponentiation per prime a naive loop would need.
+///
+/// Stage 2 costs about 2 / ln s multiplications per unit of `b2`, against
+/// about 1.44 per unit of `b1` for stage 1, so `b2` = 10 * `b1` adds roughly
+/// one and a half times the work of stage 1. A `b2` no greater than `b1`
+/// skips stage 2.
+///
+/// # Returns
+///
+/// * `Some(factor)` - a proper divisor of M_p, which may be a product of
+///   several prime factors
+/// * `None` - no factor was found, or every factor was found at once and the
+///   gcd is M_p itself
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::{pollard_p_minus_1, pollard_p_minus_1_with_stage2};
+///
+/// // 745988807 is a factor of M109, and 745988806 = 2 * 107 * 109 * 31981:
+/// // only 31981 is out of reach of stage 1 with B1 = 1000
+/// assert_eq!(pollard_p_minus_1(109, 1000), None);
+/// assert_eq!(pollard_p_minus_1_with_stage2(109, 1000, 100_000), Some(BigUint::from(745988807u32)));
+/// ```
+pub fn pollard_p_minus_1_with_stage2(p: impl Into<Exponent>, b1: u64, b2: u64) -> Option<BigUint> {
+    let p = p.into().get();
+    if p < 2 {
+        return None;
+    }
+    let mp = (BigUint::one() << p) - BigUint::one();
+
+    let mut exponent = BigUint::from(2 * p);
+    for q in primes_up_to(b1) {
+        let mut power = q;
+        while power <= b1 / q {
+            power *= q;
+        }
+        exponent *= power;
+    }
+
+    let x = BigUint::from(3u32).modpow(&exponent, &mp);
+    if x.is_zero() {
+        return None;
+    }
+    let g = gcd(&(&x - BigUint::one()), &mp);
+    if !g.is_one() || b2 <= b1 {
+        return (!g.is_one() && g != mp).then_some(g);
+    }
+
+    // Stage 2. 2 is already in the exponent as part of 2p, which also keeps
+    // every gap between the primes even.
+    let mul = |a: &BigUint, b: &BigUint| mod_mp_with_modulus(&(a * b), &mp, p);
+    let minus_one = |y: &BigUint| if y.is_zero() { &mp - 1u32 } else { y - 1u32 };
+    let mut primes = prime_sieve(b2).skip_while(|&s| s <= b1.max(2));
+    let first = primes.next()?;
+    let x_squared = mul(&x, &x);
+    // gap_powers[i] = x^(2i + 2)
+    let mut gap_powers = vec![x_squared.clone()];
+    let mut y = x.modpow(&BigUint::from(first), &mp);
+    let mut product = minus_one(&y);
+    let mut previous = first;
+    for s in primes {
+        let half_gap = ((s - previous) / 2) as usize;
+        while gap_powers.len() < half_gap {
+            let next = mul(&gap_powers[gap_powers.len() - 1], &x_squared);
+            gap_powers.push(next);
+        }
+        y = mul(&y, &gap_powers[half_gap - 1]);
+        product = mul(&product, &minus_one(&y));
+        previous = s;
+    }
+
+    let g = gcd(&product, &mp);
+    (!g.is_one() && g != mp).then_some(g)
+}
+
+/// All primes up to and including `n`, by the sieve of Eratosthenes
+pub(crate) fn primes_up_to(n: u64) -> Vec<u64> {
+    if n < 2 {
+        return Vec::new();
+    }
+    let mut composite = vec![false; n as usize + 1];
+    let mut primes = Vec::new();
+    for i in 2..=n as usize {
+        if !composite[i] {
+            primes.push(i as u64);
+            for j in (i * i..=n as usize).step_by(i) {
+                composite[j] = true;
+            }
+        }
+    }
+    primes
+}
+
+/// Greatest common divisor of two `BigUint`s
+pub(crate) fn gcd(a: &BigUint, b: &BigUint) -> BigUint {
+    let (mut a, mut b) = (a.clone(), b.clone());
+    while !b.is_zero() {
+        let r = &a % &b;
+        a = b;
+        b = r;
+    }
+    a
+}
+
+/// Status of the cofactor left after dividing known factors out of M_p
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CofactorStatus {
+    /// The cofactor passed every Miller-Rabin round and is probably prime
+    ProbablePrime,
+    /// The cofactor is definitely composite
+    Composite,
+    /// The known factors account for all of M_p, leaving a cofactor of 1
+    FullyFactored,
+    /// One of the supplied factors does not divide what remains of M_p
+    InvalidFactor(BigUint),
+}
+
+/// Result of testing the cofactor of a partially factored Mersenne number
+#[derive(Debug, Clone)]
+pub struct CofactorResult {
+    /// M_p divided by every known factor
+    pub cofactor: BigUint,
+    /// What the test concluded about the cofactor
+    pub status: CofactorStatus,
+}
+
+/// Test whether the cofactor of M_p is prime once known factors are removed
+///
+/// Many composite Mersenne numbers have a few known small factors and a large
+/// remaining cofactor whose primality is the open question. This divides each
+/// supplied factor out of M_p (a factor listed twice is divided out twice) and
+/// runs a Miller-Rabin probable-prime test on what remains.
+///
+/// # Arguments
+///
+/// * `p` - The Mersenne exponent (M_p = 2^p - 1), as a `u64` or `Exponent`
+/// * `factors` - Known factors of M_p
+/// * `rounds` - Number of Miller-Rabin rounds to run on the cofactor
+///
+/// # Returns
+///
+/// A `CofactorResult` holding the cofactor and its status. If a factor does
+/// not divide M_p the status is `InvalidFactor` and the cofactor is whatever
+/// remained before that factor was tried.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint::BigUint;
+/// use primality_jones::{test_cofactor, CofactorStatus};
+///
+/// // M11 = 2047 = 23 * 89
+/// let result = test_cofactor(11, &[BigUint::from(23u32)], 10);
+/// assert_eq!(result.cofactor, BigUint::from(89u32));
+/// assert_eq!(result.status, CofactorStatus::ProbablePrime);
+/// ```
+pub fn test_cofactor(p: impl Into<Exponent>, factors: &[BigUint], rounds: u32) -> CofactorResult {
+    let mut cofactor = p.into().mersenne_number();
+
+    for factor in factors {
+        if factor <= &BigUint::one() || !(&cofactor % factor).is_zero() {
+            return CofactorResult {
+                cofactor,
+                status: CofactorStatus::InvalidFactor(factor.clone()),
+            };
+        }
+        cofactor /= factor;
+    }
+
+    let status = if cofactor.is_one() {
+        CofactorStatus::FullyFactored
+    } else if miller_rabin_biguint(&cofactor, rounds) {
+        CofactorStatus::ProbablePrime
+    } else {
+        CofactorStatus::Composite
+    };
+
+    CofactorResult { cofactor, status }
+}
@@ -0,0 +1,207 @@
+/*!
+Primality tests for numbers of other special forms: Fermat numbers (Pépin),
+k·2^n - 1 (Lucas-Lehmer-Riesel) and k·2^n + 1 (Proth).
+*/
+
+use crate::jacobi;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+
+/// Perform Pépin's test for primality of the Fermat number F_n = 2^(2^n) + 1
+///
+/// Pépin's test is the Fermat-number counterpart of the Lucas-Lehmer test: for
+/// n >= 1, F_n is prime if and only if 3^((F_n - 1)/2) ≡ -1 (mod F_n). F_0 = 3
+/// is handled directly since the base 3 is not coprime to it.
+///
+/// The exponentiation uses the generic `modpow`. Like M_p, F_n has a special
+/// form that admits a cheap fold: because 2^(2^n) ≡ -1 (mod F_n), a value can be
+/// reduced by subtracting its high 2^n-bit chunks from its low chunk, analogous
+/// to the add-fold in `mod_mp`.
+///
+/// # Arguments
+///
+/// * `n` - The Fermat index (testing 2^(2^n) + 1)
+///
+/// # Returns
+///
+/// * `true` if F_n is prime
+/// * `false` if F_n is composite
+///
+/// # Panics
+///
+/// Panics if `n >= 64`, since 2^n no longer fits in a `u64` bit count.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::pepin_test;
+///
+/// assert!(pepin_test(4));  // F4 = 65537 is prime
+/// assert!(!pepin_test(5)); // F5 = 641 * 6700417
+/// ```
+pub fn pepin_test(n: u32) -> bool {
+    assert!(n < 64, "F_{} is too large to represent", n);
+
+    if n == 0 {
+        return true; // F0 = 3
+    }
+
+    let f = (BigUint::one() << (1u64 << n)) + BigUint::one();
+    let exponent = (&f - BigUint::one()) >> 1;
+    let f_minus_1 = &f - BigUint::one();
+
+    BigUint::from(3u32).modpow(&exponent, &f) == f_minus_1
+}
+
+/// Perform the Lucas-Lehmer-Riesel test on N = k·2^n - 1
+///
+/// This generalizes the Lucas-Lehmer test from M_p = 1·2^p - 1 to any odd
+/// multiplier k < 2^n: N is prime if and only if u_(n-2) ≡ 0 (mod N), where
+/// u_(i+1) = u_i² - 2 exactly as in `lucas_lehmer_test`. Only the starting
+/// value changes. It is u_0 = V_k(P, 1) mod N, the k-th term of the Lucas V
+/// sequence, with P chosen by Rödseth's criterion: the smallest P >= 3 with
+/// (P-2 / N) = 1 and (P+2 / N) = -1. For k = 1 this picks 3 or 4, either of
+/// which is a valid Lucas-Lehmer seed. The criterion works whether or not
+/// 3 divides k, so no separate table of seeds is needed.
+///
+/// An even k is folded into the power of two first, so 6·2^n - 1 is tested
+/// as 3·2^(n+1) - 1.
+///
+/// # Arguments
+///
+/// * `k` - The multiplier
+/// * `n` - The power of two (testing k·2^n - 1)
+///
+/// # Returns
+///
+/// * `true` if k·2^n - 1 is prime
+/// * `false` if k·2^n - 1 is composite
+///
+/// # Panics
+///
+/// Panics if `k` is zero or if, once k is odd, k >= 2^n, since the theorem
+/// does not cover those numbers.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::llr_test;
+///
+/// assert!(llr_test(3, 18));  // 3·2^18 - 1 = 786431 is prime
+/// assert!(!llr_test(3, 5));  // 3·2^5 - 1 = 95 = 5 * 19
+/// assert!(llr_test(1, 127)); // k = 1 is the Lucas-Lehmer test on M127
+/// ```
+pub fn llr_test(k: u64, n: u64) -> bool {
+    assert!(k > 0, "k must be positive");
+    let twos = k.trailing_zeros() as u64;
+    let (k, n) = (k >> twos, n + twos);
+    assert!(n >= 64 || k < 1u64 << n, "k = {} must be below 2^{}", k, n);
+
+    let big_n = (BigUint::from(k) << n) - BigUint::one();
+    if big_n < BigUint::from(3u32) {
+        return false; // k = 1, n = 1
+    }
+
+    // Rödseth's criterion. A zero symbol means P ± 2 shares a factor with N,
+    // which proves N composite unless N itself divides P ± 2.
+    let mut p = 3u64;
+    loop {
+        let minus = jacobi(&BigUint::from(p - 2), &big_n);
+        let plus = jacobi(&BigUint::from(p + 2), &big_n);
+        if (minus == 0 || plus == 0) && big_n > BigUint::from(p + 2) {
+            return false;
+        }
+        if minus == 1 && plus == -1 {
+            break;
+        }
+        p += 1;
+    }
+
+    let mut u = lucas_v(k, &BigUint::from(p), &big_n);
+    let two = BigUint::from(2u32);
+    for _ in 0..n - 2 {
+        u = (&u * &u + &big_n - &two) % &big_n;
+    }
+    u.is_zero()
+}
+
+/// V_k(P, 1) mod `m` by the Lucas ladder over the bits of `k`
+///
+/// Keeps the pair (V_j, V_(j+1)) and uses V_(2j) = V_j² - 2 and
+/// V_(2j+1) = V_j·V_(j+1) - P. Requires `m` > 2.
+fn lucas_v(k: u64, p: &BigUint, m: &BigUint) -> BigUint {
+    let p = p % m;
+    let two = BigUint::from(2u32);
+    let mut low = p.clone();
+    let mut high = (&p * &p + m - &two) % m;
+    for bit in (0..63 - k.leading_zeros()).rev() {
+        let mixed = (&low * &high + m - &p) % m;
+        if k >> bit & 1 == 1 {
+            low = mixed;
+            high = (&high * &high + m - &two) % m;
+        } else {
+            high = mixed;
+            low = (&low * &low + m - &two) % m;
+        }
+    }
+    low
+}
+
+/// Perform Proth's test on N = k·2^n + 1
+///
+/// For odd k < 2^n, Proth's theorem says N is prime if and only if
+/// a^((N-1)/2) ≡ -1 (mod N) for some a with (a / N) = -1. The base is the
+/// smallest a >= 2 with that Jacobi symbol, so a single exponentiation proves
+/// the answer either way: for prime N Euler's criterion forces the congruence.
+/// `pepin_test` is the special case k = 1, n = 2^m with the base fixed at 3.
+///
+/// An even k is folded into the power of two first, as in `llr_test`.
+///
+/// # Arguments
+///
+/// * `k` - The multiplier
+/// * `n` - The power of two (testing k·2^n + 1)
+///
+/// # Returns
+///
+/// * `true` if k·2^n + 1 is prime
+/// * `false` if k·2^n + 1 is composite
+///
+/// # Panics
+///
+/// Panics if `k` is zero or if, once k is odd, k >= 2^n.
+///
+/// # Examples
+///
+/// ```
+/// use primality_jones::proth_test;
+///
+/// assert!(proth_test(3, 2));   // 3·2^2 + 1 = 13 is prime
+/// assert!(!proth_test(3, 3));  // 3·2^3 + 1 = 25 = 5²
+/// assert!(proth_test(3, 189)); // 3·2^189 + 1 is prime
+/// ```
+pub fn proth_test(k: u64, n: u64) -> bool {
+    assert!(k > 0, "k must be positive");
+    let twos = k.trailing_zeros() as u64;
+    let (k, n) = (k >> twos, n + twos);
+    assert!(n >= 64 || k < 1u64 << n, "k = {} must be below 2^{}", k, n);
+
+    let big_n = (BigUint::from(k) << n) + BigUint::one();
+    // A square has no non-residue base, so the search below would not end
+    let root = big_n.sqrt();
+    if &root * &root == big_n {
+        return false;
+    }
+
+    let mut a = BigUint::from(2u32);
+    loop {
+        match jacobi(&a, &big_n) {
+            -1 => break,
+            0 => return a == big_n,
+            _ => a += 1u32,
+        }
+    }
+
+    let n_minus_1 = &big_n - BigUint::one();
+    a.modpow(&(&n_minus_1 >> 1), &big_n) == n_minus_1
+}
@@ -0,0 +1,80 @@
+/*!
+JavaScript bindings for running the tests in a browser.
+
+Build with [wasm-pack](https://rustwasm.github.io/wasm-pack/):
+
+```text
+wasm-pack build --target web --no-default-features --features wasm
+```
+
+Exponents and numbers are `u64`, which JavaScript sees as `BigInt`, so call
+`isPrime(127n)` rather than `isPrime(127)`. The checks run synchronously on
+the calling thread: run long ones in a Web Worker to keep the page
+responsive. `examples/wasm/index.html` is a small demo page.
+
+This module is only compiled with the `wasm` feature.
+*/
+
+use crate::{CheckLevel, CheckResult};
+use wasm_bindgen::prelude::*;
+
+/// One stage of a check, the JavaScript view of `CheckResult`
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Debug, Clone)]
+pub struct CheckStage {
+    /// The stage, e.g. "TrialFactor" or "LucasLehmer"
+    pub kind: String,
+    /// Whether the stage passed
+    pub passed: bool,
+    /// The stage could not reach a verdict (for example it timed out)
+    pub inconclusive: bool,
+    /// Description of the result
+    pub message: String,
+    /// A factor of M_p found by this stage, in decimal, if any
+    pub factor: Option<String>,
+    /// How long the stage took, in milliseconds
+    #[wasm_bindgen(js_name = timeMs)]
+    pub time_ms: f64,
+}
+
+impl From<CheckResult> for CheckStage {
+    fn from(result: CheckResult) -> Self {
+        CheckStage {
+            kind: format!("{:?}", result.kind),
+            passed: result.passed,
+            inconclusive: result.inconclusive,
+            message: result.message,
+            factor: result.factor.map(|f| f.to_string()),
+            time_ms: result.time_taken.as_secs_f64() * 1000.0,
+        }
+    }
+}
+
+/// Whether M_p is prime, by the Lucas-Lehmer test
+#[wasm_bindgen(js_name = lucasLehmerTest)]
+pub fn lucas_lehmer_test(p: u64) -> bool {
+    crate::lucas_lehmer_test(p)
+}
+
+/// Whether `n` is prime
+#[wasm_bindgen(js_name = isPrime)]
+pub fn is_prime(n: u64) -> bool {
+    crate::is_prime(n)
+}
+
+/// Check M_p up to `level`, 1 (pre-screen) to 6 (full) as in the CLI
+///
+/// Returns one `CheckStage` per stage that ran.
+#[wasm_bindgen(js_name = checkMersenneCandidate)]
+pub fn check_mersenne_candidate(p: u64, level: u8) -> Result<Vec<CheckStage>, JsError> {
+    let level = match level {
+        1 => CheckLevel::PreScreen,
+        2 => CheckLevel::TrialFactoring,
+        3 => CheckLevel::PMinusOne,
+        4 => CheckLevel::Probabilistic,
+        5 => CheckLevel::LucasLehmer,
+        6 => CheckLevel::Full,
+        _ => return Err(JsError::new("level must be between 1 and 6")),
+    };
+    Ok(crate::check_mersenne_candidate(p, level).into_iter().map(CheckStage::from).collect())
+}
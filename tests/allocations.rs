@@ -0,0 +1,99 @@
+//! Allocation counts for the Lucas-Lehmer inner loop
+//!
+//! A counting wrapper around the system allocator is installed for this test
+//! binary only. Counts are kept per thread so tests running in parallel do
+//! not see each other's allocations.
+
+use num_bigint::BigUint;
+use primality_jones::{square_and_subtract_two_mod_mp, ModMpScratch};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Upper bound on allocations per `square_and_subtract_two_mod_mp` call
+///
+/// Lower this as allocation churn in the loop is reduced.
+const MAX_ALLOCATIONS_PER_ITERATION: f64 = 25.0;
+
+/// Number of allocations (including reallocations) made by `f` on this thread
+fn count_allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let value = f();
+    (value, ALLOCATIONS.with(Cell::get) - before)
+}
+
+#[test]
+fn test_lucas_lehmer_iteration_allocations() {
+    const P: u64 = 4423;
+    const ITERATIONS: usize = 100;
+
+    // Warm up so the sequence value has reached its full size
+    let mut s = BigUint::from(4u32);
+    for _ in 0..20 {
+        s = square_and_subtract_two_mod_mp(&s, P);
+    }
+
+    let (_, allocations) = count_allocations(|| {
+        for _ in 0..ITERATIONS {
+            s = square_and_subtract_two_mod_mp(&s, P);
+        }
+    });
+    let per_iteration = allocations as f64 / ITERATIONS as f64;
+    println!("{:.1} allocations per Lucas-Lehmer iteration for M{}", per_iteration, P);
+
+    assert!(
+        per_iteration <= MAX_ALLOCATIONS_PER_ITERATION,
+        "{:.1} allocations per iteration, expected at most {}",
+        per_iteration,
+        MAX_ALLOCATIONS_PER_ITERATION
+    );
+}
+
+#[test]
+fn test_scratch_reduction_does_not_allocate() {
+    const P: u64 = 4423;
+
+    let mut scratch = ModMpScratch::new(P);
+    let mut s = BigUint::from(4u32);
+    for _ in 0..20 {
+        scratch.square_and_subtract_two(&mut s);
+    }
+
+    // The squaring itself has to allocate its product; build the products
+    // up front so only the reductions are counted
+    let mut products = Vec::new();
+    for _ in 0..100 {
+        scratch.square_and_subtract_two(&mut s);
+        products.push(&s * &s);
+    }
+    let (_, allocations) = count_allocations(|| {
+        for product in products.iter_mut() {
+            scratch.reduce(product);
+        }
+    });
+    assert_eq!(allocations, 0, "ModMpScratch::reduce allocated {} times", allocations);
+}
@@ -1,12 +1,10 @@
 use primality_jones::*;
 use std::time::{Duration, Instant};
-use indicatif::{ProgressBar, ProgressStyle};
 use num_traits::{One, Zero};
 
 /// Comprehensive verification test suite that combines all three levels
 pub struct ComprehensiveVerification {
     test_results: Vec<TestResult>,
-    start_time: Instant,
 }
 
 #[derive(Debug, Clone)]
@@ -25,11 +23,16 @@ pub enum VerificationLevel {
     Formal,       // Level 3: Formal verification (placeholder)
 }
 
+impl Default for ComprehensiveVerification {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ComprehensiveVerification {
     pub fn new() -> Self {
         Self {
             test_results: Vec::new(),
-            start_time: Instant::now(),
         }
     }
 
@@ -55,7 +58,7 @@ impl ComprehensiveVerification {
         
         // Test 1: Known Mersenne primes
         self.run_test("Known Mersenne Primes", VerificationLevel::Empirical, || {
-            let known_primes = [2, 3, 5, 7, 13, 17, 19, 31, 61, 89, 107, 127];
+            let known_primes: Vec<u64> = known_mersenne_primes().iter().copied().take_while(|&p| p <= 127).collect();
             let mut all_correct = true;
             let mut details = String::new();
             
@@ -76,7 +79,7 @@ impl ComprehensiveVerification {
         
         // Test 2: Known composite Mersenne numbers
         self.run_test("Known Composite Mersenne Numbers", VerificationLevel::Empirical, || {
-            let known_composites = [11, 23, 29, 37, 41, 43, 47, 53, 59, 67, 71, 73, 79, 83, 97];
+            let known_composites: Vec<u64> = known_composite_exponents().iter().copied().take_while(|&p| p <= 127).collect();
             let mut all_correct = true;
             let mut details = String::new();
             
@@ -127,34 +130,16 @@ impl ComprehensiveVerification {
         
         // Test 4: Differential testing against GIMPS data
         self.run_test("Differential Testing vs GIMPS", VerificationLevel::Empirical, || {
-            // This would normally load actual GIMPS data
-            // For now, we test against our known dataset
-            let gimps_primes = [2, 3, 5, 7, 13, 17, 19, 31, 61, 89, 107, 127, 521, 607, 1279, 2203, 2281];
-            let gimps_composites = [11, 23, 29, 37, 41, 43, 47, 53, 59, 67, 71, 73, 79, 83, 97];
-            
-            let mut perfect_match = true;
-            let mut details = String::new();
-            
-            // Test primes
-            for &p in &gimps_primes {
-                if !lucas_lehmer_test(p) {
-                    perfect_match = false;
-                    details.push_str(&format!("GIMPS prime M{} failed, ", p));
-                }
-            }
-            
-            // Test composites
-            for &p in &gimps_composites {
-                if lucas_lehmer_test(p) {
-                    perfect_match = false;
-                    details.push_str(&format!("GIMPS composite M{} passed, ", p));
-                }
-            }
-            
-            if perfect_match {
-                details = format!("Perfect match with GIMPS data ({} primes, {} composites)", 
-                    gimps_primes.len(), gimps_composites.len());
-            }
+            let report = verify_against_known(2281);
+            let perfect_match = report.passed();
+            let details = if perfect_match {
+                format!("Perfect match with GIMPS data ({} primes, {} composites)",
+                    report.primes_checked, report.composites_checked)
+            } else {
+                report.mismatches.iter()
+                    .map(|m| format!("M{} expected {}, ", m.exponent, if m.expected_prime { "prime" } else { "composite" }))
+                    .collect()
+            };
             
             (perfect_match, details)
         });
@@ -229,7 +214,7 @@ impl ComprehensiveVerification {
             // Test with a known prime
             let p = 31; // M31 = 2147483647 is prime
             let start_time = Instant::now();
-            let result = miller_rabin_test(p, 5, start_time, Duration::from_secs(30));
+            let result = miller_rabin_test_quiet(p, 5, start_time, Duration::from_secs(30));
             
             if result {
                 (true, "Miller-Rabin correctly identifies known Mersenne prime".to_string())
@@ -1,3 +1,5 @@
+use num_bigint::BigUint;
+use num_traits::One;
 use primality_jones::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -23,10 +25,10 @@ impl DifferentialTestSuite {
     fn new() -> Self {
         // Load test data from external JSON files
         let known_mersenne_primes = Self::load_mersenne_primes()
-            .unwrap_or_else(|_| vec![2, 3, 5, 7, 13, 17, 19, 31, 61, 89, 107, 127]);
+            .unwrap_or_else(|_| known_mersenne_primes().to_vec());
         
         let known_composite_mersenne = Self::load_composite_mersenne()
-            .unwrap_or_else(|_| vec![11, 23, 29, 37, 41, 43, 47, 53, 59, 67, 71, 73, 79, 83, 97]);
+            .unwrap_or_else(|_| known_composite_exponents().to_vec());
         
         Self {
             known_mersenne_primes,
@@ -35,14 +37,12 @@ impl DifferentialTestSuite {
         }
     }
 
-    fn load_mersenne_primes() -> Result<Vec<u64>, Box<dyn std::error::Error>> {
-        let content = fs::read_to_string("test_data/known_mersenne_primes.json")?;
-        Ok(serde_json::from_str(&content)?)
+    fn load_mersenne_primes() -> std::io::Result<Vec<u64>> {
+        load_known_primes("test_data/known_mersenne_primes.json")
     }
 
-    fn load_composite_mersenne() -> Result<Vec<u64>, Box<dyn std::error::Error>> {
-        let content = fs::read_to_string("test_data/known_composite_mersenne.json")?;
-        Ok(serde_json::from_str(&content)?)
+    fn load_composite_mersenne() -> std::io::Result<Vec<u64>> {
+        load_known_primes("test_data/known_composite_mersenne.json")
     }
 
     fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
@@ -100,7 +100,7 @@ impl DifferentialTestSuite {
         } else {
             std::time::Duration::from_secs(30)
         };
-        let mr_result = miller_rabin_test(p, 5, mr_start, timeout);
+        let mr_result = miller_rabin_test_quiet(p, 5, mr_start, timeout);
         let mr_time = mr_start.elapsed();
         
         SingleTestResult {
@@ -187,6 +187,8 @@ impl DifferentialTestReport {
             (self.lucas_lehmer_correct as f64 / self.total_tests as f64) * 100.0);
         println!("  False positives: {}", self.lucas_lehmer_false_positives);
         println!("  False negatives: {}", self.lucas_lehmer_false_negatives);
+        println!("  Total time: {:?}",
+            self.results.iter().map(|r| r.lucas_lehmer_time).sum::<std::time::Duration>());
         println!();
         
         println!("Miller-Rabin Test:");
@@ -195,6 +197,8 @@ impl DifferentialTestReport {
             (self.miller_rabin_correct as f64 / self.total_tests as f64) * 100.0);
         println!("  False positives: {}", self.miller_rabin_false_positives);
         println!("  False negatives: {}", self.miller_rabin_false_negatives);
+        println!("  Total time: {:?}",
+            self.results.iter().map(|r| r.miller_rabin_time).sum::<std::time::Duration>());
         println!();
         
         if self.lucas_lehmer_false_positives > 0 || self.lucas_lehmer_false_negatives > 0 {
@@ -230,11 +234,85 @@ mod tests {
         report.print_summary();
     }
 
+    #[test]
+    fn test_suite_save_load_round_trip() {
+        let suite = DifferentialTestSuite::new();
+        let file = tempfile::NamedTempFile::new().unwrap();
+
+        suite.save_to_file(file.path()).unwrap();
+        let loaded = DifferentialTestSuite::load_from_file(file.path()).unwrap();
+
+        assert_eq!(loaded.known_mersenne_primes, suite.known_mersenne_primes);
+        assert_eq!(loaded.known_composite_mersenne, suite.known_composite_mersenne);
+    }
+
+    /// Lucas-Lehmer residues from a textbook implementation that keeps values
+    /// in the range 1..=M_p, so a zero residue is stored as M_p
+    fn reference_residues(p: u64) -> Vec<BigUint> {
+        let mp = (BigUint::one() << p) - BigUint::one();
+        let mut s = BigUint::from(4u32);
+        let mut residues = Vec::new();
+        for _ in 0..p - 2 {
+            s = (&s * &s + &mp - 2u32) % &mp;
+            if s == BigUint::from(0u32) {
+                s = mp.clone();
+            }
+            residues.push(s.clone());
+        }
+        residues
+    }
+
+    #[test]
+    fn test_residues_match_reference() {
+        for p in [3, 5, 7, 11, 13, 17, 19, 23, 31, 61, 89, 107, 127] {
+            let mut s = BigUint::from(4u32);
+            for (i, expected) in reference_residues(p).iter().enumerate() {
+                s = square_and_subtract_two_mod_mp(&s, p);
+                assert!(residues_equal_mod_mp(&s, expected, p),
+                    "M{} residue mismatch at iteration {}: {} vs {}", p, i + 1, s, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_data_matches_embedded_table() {
+        let from_file = DifferentialTestSuite::load_mersenne_primes().unwrap();
+        assert_eq!(from_file, known_mersenne_primes());
+    }
+
+    #[test]
+    fn test_composite_data_matches_embedded_table() {
+        let from_file = DifferentialTestSuite::load_composite_mersenne().unwrap();
+        assert_eq!(from_file, known_composite_exponents());
+    }
+
+    #[test]
+    fn test_optimized_matches_reference() {
+        for p in 2..=607 {
+            assert_eq!(lucas_lehmer_test(p), lucas_lehmer_reference(p),
+                "Optimized and reference Lucas-Lehmer disagree on M{}", p);
+        }
+    }
+
+    #[cfg(feature = "fft")]
+    #[test]
+    fn test_fft_matches_schoolbook() {
+        for p in 2..=607 {
+            assert_eq!(dwt::lucas_lehmer_test_fft(p), lucas_lehmer_test(p),
+                "FFT and schoolbook Lucas-Lehmer disagree on M{}", p);
+        }
+        // The schoolbook path is too slow to rerun in a debug build beyond
+        // that, so every larger prime exponent up to 4423 is checked against
+        // the known Mersenne primes instead
+        for p in (608..=4423).filter(|&p| is_prime(p)) {
+            assert_eq!(dwt::lucas_lehmer_test_fft(p), KNOWN_MERSENNE_PRIME_EXPONENTS.contains(&p),
+                "FFT Lucas-Lehmer is wrong about M{}", p);
+        }
+    }
+
     #[test]
     fn test_known_mersenne_primes() {
-        let known_primes = [2, 3, 5, 7, 13, 17, 19, 31, 61, 89, 107, 127];
-        
-        for &p in &known_primes {
+        for &p in known_mersenne_primes().iter().take_while(|&&p| p <= 127) {
             assert!(lucas_lehmer_test(p), 
                 "Known Mersenne prime M{} failed Lucas-Lehmer test", p);
         }
@@ -242,9 +320,7 @@ mod tests {
 
     #[test]
     fn test_known_composite_mersenne() {
-        let known_composites = [11, 23, 29, 37, 41, 43, 47, 53, 59, 67, 71, 73, 79, 83, 97];
-        
-        for &p in &known_composites {
+        for &p in known_composite_exponents().iter().take_while(|&&p| p <= 127) {
             assert!(!lucas_lehmer_test(p), 
                 "Known composite Mersenne number M{} passed Lucas-Lehmer test", p);
         }